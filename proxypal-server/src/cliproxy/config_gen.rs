@@ -8,6 +8,10 @@ use crate::db::Database;
 
 const SERVER_CONFIG_KEY: &str = "server_config";
 
+/// Client key written into the generated CLIProxyAPI config; used by the
+/// server itself when calling the proxy's `/v1` endpoints.
+pub const PROXY_API_KEY: &str = "proxypal-default-key";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct ServerConfig {
@@ -15,10 +19,25 @@ pub struct ServerConfig {
     pub admin_port: u16,
     pub log_level: String,
     pub auto_start_proxy: bool,
+    /// Model aliases applied by the `/v1` routes before forwarding. Keys may
+    /// be `*` patterns.
     pub model_mappings: HashMap<String, String>,
+    /// Models to try in order when a model answers 429 or 5xx, or does not
+    /// answer within `fallback_timeout_secs`. Keys are matched against the
+    /// model after aliasing, and may be `*` patterns.
+    #[serde(default)]
+    pub model_fallbacks: HashMap<String, Vec<String>>,
+    /// How long to wait for a model before trying its next fallback. Not
+    /// applied to the last model in a chain.
+    #[serde(default = "default_fallback_timeout_secs")]
+    pub fallback_timeout_secs: u64,
     pub rate_limits: RateLimits,
 }
 
+fn default_fallback_timeout_secs() -> u64 {
+    60
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
@@ -27,6 +46,8 @@ impl Default for ServerConfig {
             log_level: "info".to_string(),
             auto_start_proxy: true,
             model_mappings: HashMap::new(),
+            model_fallbacks: HashMap::new(),
+            fallback_timeout_secs: default_fallback_timeout_secs(),
             rate_limits: RateLimits::default(),
         }
     }
@@ -36,6 +57,12 @@ impl Default for ServerConfig {
 pub struct RateLimits {
     pub requests_per_minute: u64,
     pub tokens_per_day: Option<i64>,
+    /// In-flight `/v1` requests allowed per user; 0 means unlimited.
+    #[serde(default)]
+    pub max_concurrent_requests: u64,
+    /// Tokens per minute allowed per user; 0 means unlimited.
+    #[serde(default)]
+    pub tokens_per_minute: u64,
 }
 
 impl Default for RateLimits {
@@ -43,6 +70,8 @@ impl Default for RateLimits {
         Self {
             requests_per_minute: 60,
             tokens_per_day: None,
+            max_concurrent_requests: 0,
+            tokens_per_minute: 0,
         }
     }
 }
@@ -74,7 +103,7 @@ pub fn build_proxy_config_yaml(
     yaml_parts.push(format!("log-level: {}", server_config.log_level));
     yaml_parts.push("auth-dir: ./auth".to_string());
     yaml_parts.push("api-keys:".to_string());
-    yaml_parts.push("  - proxypal-default-key".to_string());
+    yaml_parts.push(format!("  - {}", PROXY_API_KEY));
 
     if !server_config.model_mappings.is_empty() {
         yaml_parts.push("model-mappings:".to_string());
@@ -3,14 +3,17 @@ pub mod process;
 
 pub use config_gen::{
     build_proxy_config_yaml, generate_proxy_config, load_server_config, save_server_config,
-    RateLimits, ServerConfig,
+    RateLimits, ServerConfig, PROXY_API_KEY,
 };
 pub use process::{LocalProxyProcessManager, MockProxyProcessManager, ProxyProcessManager};
 
 use async_trait::async_trait;
 use axum::body::Bytes;
+use futures_util::{stream::BoxStream, StreamExt};
 use http::{HeaderMap, Method};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -42,6 +45,21 @@ pub struct AuthStatusResponse {
     pub completed: bool,
 }
 
+/// Entry from CLIProxyAPI's OpenAI-compatible `/v1/models` list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyModel {
+    pub id: String,
+    #[serde(default)]
+    pub owned_by: Option<String>,
+    #[serde(default)]
+    pub created: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxyModelList {
+    data: Vec<ProxyModel>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ProxyResponse {
     pub status: u16,
@@ -49,6 +67,99 @@ pub struct ProxyResponse {
     pub body: Bytes,
 }
 
+/// Upstream response whose body is relayed chunk by chunk (used for SSE).
+pub struct ProxyStreamResponse {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: BoxStream<'static, anyhow::Result<Bytes>>,
+}
+
+fn convert_headers(headers: &reqwest::header::HeaderMap) -> HeaderMap {
+    let mut header_map = HeaderMap::new();
+    for (key, value) in headers.iter() {
+        if let Ok(name) = http::header::HeaderName::from_bytes(key.as_str().as_bytes()) {
+            if let Ok(val) = http::header::HeaderValue::from_bytes(value.as_bytes()) {
+                header_map.insert(name, val);
+            }
+        }
+    }
+    header_map
+}
+
+/// Which `ProxyManagementClient` implementation the server runs with,
+/// selected by the `PROXY_CLIENT` env var (`http` by default).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyBackend {
+    Http,
+    Mock,
+}
+
+impl ProxyBackend {
+    pub fn parse(value: Option<&str>) -> anyhow::Result<Self> {
+        match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("http") => Ok(Self::Http),
+            Some("mock") => Ok(Self::Mock),
+            Some(other) => anyhow::bail!("Invalid PROXY_CLIENT '{}': expected 'http' or 'mock'", other),
+        }
+    }
+
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::parse(std::env::var("PROXY_CLIENT").ok().as_deref())
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Mock => "mock",
+        }
+    }
+
+    pub fn build_client(&self) -> anyhow::Result<Arc<dyn ProxyManagementClient>> {
+        Ok(match self {
+            Self::Http => Arc::new(HttpProxyManagementClient::from_env()?),
+            Self::Mock => Arc::new(MockProxyManagementClient::default()),
+        })
+    }
+}
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Result of the connectivity check made against CLIProxyAPI at startup.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyProbe {
+    pub backend: String,
+    pub reachable: bool,
+    pub error: Option<String>,
+    pub checked_at: String,
+}
+
+impl ProxyProbe {
+    /// Placeholder for states built without contacting the proxy (tests).
+    pub fn unchecked(backend: ProxyBackend) -> Self {
+        Self {
+            backend: backend.as_str().to_string(),
+            reachable: false,
+            error: Some("Not probed".to_string()),
+            checked_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub async fn run(backend: ProxyBackend, client: &dyn ProxyManagementClient) -> Self {
+        let error = match tokio::time::timeout(PROBE_TIMEOUT, client.health_check()).await {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!("Timed out after {}s", PROBE_TIMEOUT.as_secs())),
+        };
+
+        Self {
+            backend: backend.as_str().to_string(),
+            reachable: error.is_none(),
+            error,
+            checked_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
 #[async_trait]
 pub trait ProxyManagementClient: Send + Sync {
     async fn health_check(&self) -> anyhow::Result<ProxyHealthResponse>;
@@ -58,6 +169,7 @@ pub trait ProxyManagementClient: Send + Sync {
     async fn check_oauth_status(&self, state: &str) -> anyhow::Result<bool>;
     async fn sync_provider(&self, provider: &str) -> anyhow::Result<()>;
     async fn remove_provider(&self, provider: &str) -> anyhow::Result<()>;
+    async fn list_models(&self) -> anyhow::Result<Vec<ProxyModel>>;
     async fn forward_request(
         &self,
         path: &str,
@@ -65,6 +177,13 @@ pub trait ProxyManagementClient: Send + Sync {
         headers: HeaderMap,
         body: Bytes,
     ) -> anyhow::Result<ProxyResponse>;
+    async fn forward_stream(
+        &self,
+        path: &str,
+        method: Method,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> anyhow::Result<ProxyStreamResponse>;
 }
 
 pub struct HttpProxyManagementClient {
@@ -93,6 +212,38 @@ impl HttpProxyManagementClient {
     fn auth_header(&self) -> (&'static str, &str) {
         ("X-Management-Key", &self.management_key)
     }
+
+    async fn send_forward(
+        &self,
+        path: &str,
+        method: Method,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> anyhow::Result<reqwest::Response> {
+        let url = format!("{}{}", self.base_url, path);
+
+        let mut req = self.client.request(
+            reqwest::Method::from_bytes(method.as_str().as_bytes())?,
+            &url,
+        );
+
+        // The caller's ProxyPal key means nothing to CLIProxyAPI; authenticate
+        // with the key written into its generated config instead. Encoding is
+        // left to reqwest so usage can be parsed from the upstream body.
+        for (key, value) in headers.iter() {
+            let skip = key == http::header::HOST
+                || key == http::header::CONNECTION
+                || key == http::header::AUTHORIZATION
+                || key == http::header::ACCEPT_ENCODING
+                || key == "x-api-key"
+                || key == "x-goog-api-key";
+            if !skip {
+                req = req.header(key.as_str(), value.to_str().unwrap_or(""));
+            }
+        }
+
+        Ok(req.bearer_auth(PROXY_API_KEY).body(body).send().await?)
+    }
 }
 
 #[async_trait]
@@ -205,6 +356,20 @@ impl ProxyManagementClient for HttpProxyManagementClient {
         Ok(())
     }
 
+    async fn list_models(&self) -> anyhow::Result<Vec<ProxyModel>> {
+        let url = format!("{}/v1/models", self.base_url);
+        let resp = self
+            .client
+            .get(&url)
+            .bearer_auth(PROXY_API_KEY)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ProxyModelList>()
+            .await?;
+        Ok(resp.data)
+    }
+
     async fn forward_request(
         &self,
         path: &str,
@@ -212,41 +377,36 @@ impl ProxyManagementClient for HttpProxyManagementClient {
         headers: HeaderMap,
         body: Bytes,
     ) -> anyhow::Result<ProxyResponse> {
-        let url = format!("{}{}", self.base_url, path);
-        
-        let mut req = self.client.request(
-            reqwest::Method::from_bytes(method.as_str().as_bytes())?,
-            &url,
-        );
-        
-        for (key, value) in headers.iter() {
-            if key != http::header::HOST && key != http::header::CONNECTION {
-                req = req.header(key.as_str(), value.to_str().unwrap_or(""));
-            }
-        }
-        
-        req = req.body(body);
-        
-        let resp = req.send().await?;
+        let resp = self.send_forward(path, method, headers, body).await?;
         let status = resp.status().as_u16();
-        let resp_headers = resp.headers().clone();
+        let header_map = convert_headers(resp.headers());
         let resp_body = resp.bytes().await?;
-        
-        let mut header_map = HeaderMap::new();
-        for (key, value) in resp_headers.iter() {
-            if let Ok(name) = http::header::HeaderName::from_bytes(key.as_str().as_bytes()) {
-                if let Ok(val) = http::header::HeaderValue::from_bytes(value.as_bytes()) {
-                    header_map.insert(name, val);
-                }
-            }
-        }
-        
+
         Ok(ProxyResponse {
             status,
             headers: header_map,
             body: resp_body,
         })
     }
+
+    async fn forward_stream(
+        &self,
+        path: &str,
+        method: Method,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> anyhow::Result<ProxyStreamResponse> {
+        let resp = self.send_forward(path, method, headers, body).await?;
+        let status = resp.status().as_u16();
+        let header_map = convert_headers(resp.headers());
+        let stream = resp.bytes_stream().map(|chunk| chunk.map_err(anyhow::Error::from));
+
+        Ok(ProxyStreamResponse {
+            status,
+            headers: header_map,
+            body: stream.boxed(),
+        })
+    }
 }
 
 #[derive(Default)]
@@ -257,6 +417,12 @@ pub struct MockProxyManagementClient {
     pub oauth_status: std::sync::Mutex<bool>,
     pub call_log: std::sync::Mutex<Vec<String>>,
     pub forward_response: std::sync::Mutex<Option<ProxyResponse>>,
+    /// Served in order, each after its delay, before `forward_response`;
+    /// `None` fails as if the upstream were unreachable.
+    pub forward_sequence: std::sync::Mutex<std::collections::VecDeque<(std::time::Duration, Option<ProxyResponse>)>>,
+    pub stream_chunks: std::sync::Mutex<Option<Vec<Bytes>>>,
+    pub models: std::sync::Mutex<Vec<ProxyModel>>,
+    pub forwarded_bodies: std::sync::Mutex<Vec<Bytes>>,
 }
 
 impl MockProxyManagementClient {
@@ -316,26 +482,108 @@ impl ProxyManagementClient for MockProxyManagementClient {
         Ok(())
     }
 
+    async fn list_models(&self) -> anyhow::Result<Vec<ProxyModel>> {
+        self.log_call("list_models");
+        Ok(self.models.lock().unwrap().clone())
+    }
+
     async fn forward_request(
         &self,
         path: &str,
         method: Method,
         _headers: HeaderMap,
-        _body: Bytes,
+        body: Bytes,
     ) -> anyhow::Result<ProxyResponse> {
         self.log_call(&format!("forward_request:{}:{}", method, path));
+        self.forwarded_bodies.lock().unwrap().push(body);
+        let queued = self.forward_sequence.lock().unwrap().pop_front();
+        if let Some((delay, response)) = queued {
+            tokio::time::sleep(delay).await;
+            return response.ok_or_else(|| anyhow::anyhow!("Connection refused"));
+        }
         self.forward_response
             .lock()
             .unwrap()
             .clone()
             .ok_or_else(|| anyhow::anyhow!("No mock forward response configured"))
     }
+
+    async fn forward_stream(
+        &self,
+        path: &str,
+        method: Method,
+        _headers: HeaderMap,
+        body: Bytes,
+    ) -> anyhow::Result<ProxyStreamResponse> {
+        self.log_call(&format!("forward_stream:{}:{}", method, path));
+        self.forwarded_bodies.lock().unwrap().push(body);
+        let chunks = self
+            .stream_chunks
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("No mock stream chunks configured"))?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::header::HeaderValue::from_static("text/event-stream"),
+        );
+
+        Ok(ProxyStreamResponse {
+            status: 200,
+            headers,
+            body: futures_util::stream::iter(chunks.into_iter().map(Ok)).boxed(),
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn proxy_backend_parses_env_values() {
+        assert_eq!(ProxyBackend::parse(None).unwrap(), ProxyBackend::Http);
+        assert_eq!(ProxyBackend::parse(Some("http")).unwrap(), ProxyBackend::Http);
+        assert_eq!(ProxyBackend::parse(Some(" MOCK ")).unwrap(), ProxyBackend::Mock);
+        assert!(ProxyBackend::parse(Some("grpc")).is_err());
+    }
+
+    #[tokio::test]
+    async fn probe_reports_reachable_proxy() {
+        let mock = MockProxyManagementClient::default();
+        *mock.health_response.lock().unwrap() = Some(ProxyHealthResponse {
+            running: true,
+            uptime_seconds: None,
+            version: None,
+        });
+
+        let probe = ProxyProbe::run(ProxyBackend::Mock, &mock).await;
+        assert_eq!(probe.backend, "mock");
+        assert!(probe.reachable);
+        assert!(probe.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn probe_reports_unreachable_proxy() {
+        // A port that was just free, so nothing is listening on it
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = HttpProxyManagementClient::new(
+            format!("http://127.0.0.1:{}", port),
+            "key".to_string(),
+        );
+
+        let probe = ProxyProbe::run(ProxyBackend::Http, &client).await;
+        assert_eq!(probe.backend, "http");
+        assert!(!probe.reachable);
+        assert!(probe.error.is_some());
+    }
+
     #[tokio::test]
     async fn mock_client_records_calls() {
         let mock = MockProxyManagementClient::default();
@@ -6,6 +6,8 @@ use anyhow::{anyhow, Context, Result};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use rand::RngCore;
 
+pub mod totp;
+
 const NONCE_SIZE: usize = 12;
 
 fn get_encryption_key() -> Result<[u8; 32]> {
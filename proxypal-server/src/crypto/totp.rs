@@ -0,0 +1,165 @@
+//! RFC 6238 time-based one-time passwords (SHA-1, 6 digits, 30 s steps),
+//! the parameters every authenticator app supports.
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+const STEP_SECS: u64 = 30;
+const DIGITS: u32 = 6;
+/// Accept codes from one step either side, for clock drift.
+const ALLOWED_DRIFT: u64 = 1;
+const SECRET_BYTES: usize = 20;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// A fresh 160-bit secret, base32-encoded as authenticator apps expect.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base32_encode(&bytes)
+}
+
+/// `otpauth://` URI for enrolment; render it as a QR code to scan.
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECS}",
+        issuer = percent_encode(issuer),
+        account = percent_encode(account),
+        secret = secret,
+    )
+}
+
+pub fn current_step(unix_time: u64) -> u64 {
+    unix_time / STEP_SECS
+}
+
+pub fn code_at(secret: &str, step: u64) -> Option<String> {
+    let key = base32_decode(secret)?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&key).ok()?;
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation (RFC 4226 section 5.3)
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    Some(format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    ))
+}
+
+/// Returns the time step `code` belongs to if it is valid around
+/// `unix_time`. Callers should reject steps at or before the last one used.
+pub fn verify(secret: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let now = current_step(unix_time);
+    (now.saturating_sub(ALLOWED_DRIFT)..=now + ALLOWED_DRIFT)
+        .find(|&step| code_at(secret, step).is_some_and(|expected| constant_time_eq(&expected, code)))
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(encoded.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for c in encoded.bytes().filter(|&c| c != b'=' && c != b' ') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, SHA-1 secret "12345678901234567890"
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn matches_rfc_6238_test_vectors() {
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ];
+        for (time, expected) in vectors {
+            assert_eq!(code_at(RFC_SECRET, current_step(time)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn verify_allows_one_step_of_drift() {
+        assert_eq!(verify(RFC_SECRET, "081804", 1111111109), Some(37037036));
+        assert_eq!(verify(RFC_SECRET, "081804", 1111111109 + 30), Some(37037036));
+        assert_eq!(verify(RFC_SECRET, "081804", 1111111109 + 90), None);
+        assert_eq!(verify(RFC_SECRET, "08180", 1111111109), None);
+        assert_eq!(verify(RFC_SECRET, "abcdef", 1111111109), None);
+    }
+
+    #[test]
+    fn secrets_round_trip_through_base32() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert_eq!(base32_decode(&secret).unwrap().len(), SECRET_BYTES);
+        assert_eq!(base32_encode(b"12345678901234567890"), RFC_SECRET);
+    }
+
+    #[test]
+    fn provisioning_uri_escapes_labels() {
+        let uri = provisioning_uri("ABC", "jane doe", "ProxyPal");
+        assert_eq!(
+            uri,
+            "otpauth://totp/ProxyPal:jane%20doe?secret=ABC&issuer=ProxyPal&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
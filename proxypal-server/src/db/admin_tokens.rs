@@ -0,0 +1,216 @@
+use anyhow::Result;
+use rand::Rng;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::admins::{Admin, AdminRole};
+use super::Database;
+
+/// Tokens look like `ppa-<public id>-<secret>`. The public ID is indexed;
+/// only a SHA-256 of the whole token is stored, which is enough for 128
+/// random bits and cheap to check on every request.
+const TOKEN_PREFIX: &str = "ppa-";
+const PUBLIC_ID_LEN: usize = 16;
+
+/// SQL condition selecting tokens that may currently authenticate.
+const ACTIVE_TOKEN: &str =
+    "t.revoked_at IS NULL AND (t.expires_at IS NULL OR t.expires_at > datetime('now'))";
+
+const TOKEN_COLUMNS: &str = "t.id, t.admin_id, a.username, t.name, t.public_id, t.role, t.created_at, \
+     t.expires_at, t.last_used_at, t.revoked_at";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminToken {
+    pub id: i64,
+    pub admin_id: i64,
+    pub username: String,
+    pub name: String,
+    /// `ppa-<public id>`, enough to recognise the token without the secret.
+    pub token_prefix: String,
+    /// The most the token may do. Requests get the lower of this and the
+    /// owning admin's current role.
+    pub role: AdminRole,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+    /// Neither revoked nor expired.
+    pub active: bool,
+}
+
+fn row_to_admin_token(row: &rusqlite::Row) -> rusqlite::Result<AdminToken> {
+    let public_id: String = row.get(4)?;
+    Ok(AdminToken {
+        id: row.get(0)?,
+        admin_id: row.get(1)?,
+        username: row.get(2)?,
+        name: row.get(3)?,
+        token_prefix: format!("{}{}", TOKEN_PREFIX, public_id),
+        role: AdminRole::parse(&row.get::<_, String>(5)?).unwrap_or(AdminRole::Viewer),
+        created_at: row.get(6)?,
+        expires_at: row.get(7)?,
+        last_used_at: row.get(8)?,
+        revoked_at: row.get(9)?,
+        active: row.get(10)?,
+    })
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Whether a bearer credential is shaped like an admin token rather than a
+/// user's API key.
+pub fn is_admin_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+/// Extracts the public ID from a `ppa-<id>-<secret>` token.
+fn parse_public_id(token: &str) -> Option<&str> {
+    let (public_id, secret) = token.strip_prefix(TOKEN_PREFIX)?.split_once('-')?;
+    let well_formed = public_id.len() == PUBLIC_ID_LEN
+        && public_id.bytes().all(|b| b.is_ascii_hexdigit())
+        && !secret.is_empty();
+    well_formed.then_some(public_id)
+}
+
+fn get_admin_token_in(conn: &rusqlite::Connection, id: i64) -> Result<Option<AdminToken>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, {} FROM admin_tokens t JOIN admins a ON a.id = t.admin_id WHERE t.id = ?1",
+        TOKEN_COLUMNS, ACTIVE_TOKEN
+    ))?;
+    Ok(stmt.query_row([id], row_to_admin_token).optional()?)
+}
+
+impl Database {
+    /// Issues a token for `admin_id`. Returns the token and its secret, which
+    /// is not stored and cannot be shown again.
+    pub fn create_admin_token(
+        &self,
+        admin_id: i64,
+        name: &str,
+        role: AdminRole,
+        expires_at: Option<&str>,
+    ) -> Result<(AdminToken, String)> {
+        let mut rng = rand::thread_rng();
+        let public_id = hex::encode(rng.gen::<[u8; PUBLIC_ID_LEN / 2]>());
+        let secret = hex::encode(rng.gen::<[u8; 16]>());
+        let token = format!("{}{}-{}", TOKEN_PREFIX, public_id, secret);
+
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO admin_tokens (admin_id, name, public_id, token_hash, role, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![admin_id, name, public_id, hash_token(&token), role.as_str(), expires_at],
+            )?;
+            let created = get_admin_token_in(conn, conn.last_insert_rowid())?
+                .ok_or_else(|| anyhow::anyhow!("Inserted admin token not found"))?;
+            Ok((created, token))
+        })
+    }
+
+    /// Newest first; only `admin_id`'s if given.
+    pub fn list_admin_tokens(&self, admin_id: Option<i64>) -> Result<Vec<AdminToken>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}, {} FROM admin_tokens t JOIN admins a ON a.id = t.admin_id
+                 WHERE ?1 IS NULL OR t.admin_id = ?1
+                 ORDER BY t.created_at DESC, t.id DESC",
+                TOKEN_COLUMNS, ACTIVE_TOKEN
+            ))?;
+            let tokens = stmt
+                .query_map([admin_id], row_to_admin_token)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(tokens)
+        })
+    }
+
+    /// Revokes a token; only `admin_id`'s if given. Revoking twice keeps the
+    /// first time. Returns `None` if there is no such token.
+    pub fn revoke_admin_token(&self, id: i64, admin_id: Option<i64>) -> Result<Option<AdminToken>> {
+        self.with_conn(|conn| {
+            let Some(token) = get_admin_token_in(conn, id)? else {
+                return Ok(None);
+            };
+            if admin_id.is_some_and(|admin_id| admin_id != token.admin_id) {
+                return Ok(None);
+            }
+            conn.execute(
+                "UPDATE admin_tokens SET revoked_at = datetime('now') WHERE id = ?1 AND revoked_at IS NULL",
+                [id],
+            )?;
+            get_admin_token_in(conn, id)
+        })
+    }
+
+    /// The active token matching `token`, with its admin, and marks it used.
+    pub fn authenticate_admin_token(&self, token: &str) -> Result<Option<(AdminToken, Admin)>> {
+        let Some(public_id) = parse_public_id(token) else {
+            return Ok(None);
+        };
+        let found = self.with_conn(|conn| {
+            let id: Option<i64> = conn
+                .query_row(
+                    &format!(
+                        "SELECT id FROM admin_tokens t WHERE public_id = ?1 AND token_hash = ?2 AND {}",
+                        ACTIVE_TOKEN
+                    ),
+                    params![public_id, hash_token(token)],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(id) = id else {
+                return Ok(None);
+            };
+            conn.execute(
+                "UPDATE admin_tokens SET last_used_at = datetime('now') WHERE id = ?1",
+                [id],
+            )?;
+            get_admin_token_in(conn, id)
+        })?;
+
+        let Some(found) = found else {
+            return Ok(None);
+        };
+        Ok(self.get_admin(found.admin_id)?.map(|admin| (found, admin)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_authenticate_until_revoked_or_expired() {
+        let db = Database::new_in_memory().unwrap();
+        let admin = db.create_admin("ops", "hash", AdminRole::Admin).unwrap();
+        let (created, token) = db
+            .create_admin_token(admin.id, "terraform", AdminRole::Viewer, None)
+            .unwrap();
+        assert!(is_admin_token(&token));
+        assert!(token.starts_with(&created.token_prefix));
+        assert_eq!(created.username, "ops");
+
+        let (found, owner) = db.authenticate_admin_token(&token).unwrap().unwrap();
+        assert_eq!((found.id, owner.id), (created.id, admin.id));
+        assert_eq!(found.role, AdminRole::Viewer);
+        assert!(found.last_used_at.is_some());
+
+        let forged = format!("{}-forged", created.token_prefix);
+        assert!(db.authenticate_admin_token(&forged).unwrap().is_none());
+
+        let other = db.create_admin("other", "hash", AdminRole::Owner).unwrap();
+        assert!(db.revoke_admin_token(created.id, Some(other.id)).unwrap().is_none());
+        let revoked = db.revoke_admin_token(created.id, Some(admin.id)).unwrap().unwrap();
+        assert!(!revoked.active);
+        assert!(db.authenticate_admin_token(&token).unwrap().is_none());
+
+        let (_, expired) = db
+            .create_admin_token(admin.id, "old", AdminRole::Admin, Some("2000-01-01 00:00:00"))
+            .unwrap();
+        assert!(db.authenticate_admin_token(&expired).unwrap().is_none());
+        assert_eq!(db.list_admin_tokens(Some(admin.id)).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,348 @@
+use anyhow::{anyhow, Result};
+use argon2::{
+    password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use rand::rngs::OsRng;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::sessions::Session;
+use super::Database;
+
+/// What an admin may do. Ordered, so `role >= AdminRole::Admin` reads as
+/// "at least admin".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminRole {
+    /// Read-only access to the admin API.
+    Viewer,
+    /// Manages users, keys, teams, providers and the proxy.
+    Admin,
+    /// Everything, including server config and other admin accounts.
+    Owner,
+}
+
+impl AdminRole {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "viewer" => Some(Self::Viewer),
+            "admin" => Some(Self::Admin),
+            "owner" => Some(Self::Owner),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Admin => "admin",
+            Self::Owner => "owner",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Admin {
+    pub id: i64,
+    pub username: String,
+    pub role: AdminRole,
+    pub created_at: String,
+    pub last_login_at: Option<String>,
+    /// Whether login asks for a TOTP code after the password.
+    pub totp_enabled: bool,
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow!("Failed to hash password: {}", e))?
+        .to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// A hash of a password nobody has, with the same parameters as real ones.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$y7kEXnoOnkNNZlZMwKtlKg$cYeM1cjqOUR92l5LhjxZh2JB+Hf5qrDvr9fPnIGXDYQ";
+
+/// Checks a login password against the admin's hash. Without one (no such
+/// admin, or an SSO-only account) it still runs a full verification against
+/// a dummy hash, so an unknown username takes as long to reject as a wrong
+/// password.
+pub fn verify_login_password(password: &str, hash: Option<&str>) -> bool {
+    #[cfg(test)]
+    LOGIN_VERIFICATIONS.with(|count| count.set(count.get() + 1));
+    match hash.filter(|hash| PasswordHash::new(hash).is_ok()) {
+        Some(hash) => verify_password(password, hash),
+        None => {
+            verify_password(password, DUMMY_PASSWORD_HASH);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Calls to `verify_login_password` on this thread, for timing tests.
+    pub(crate) static LOGIN_VERIFICATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+const ADMIN_COLUMNS: &str = "id, username, role, created_at, last_login_at, totp_enabled";
+
+fn row_to_admin(row: &rusqlite::Row) -> rusqlite::Result<Admin> {
+    Ok(Admin {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        role: AdminRole::parse(&row.get::<_, String>(2)?).unwrap_or(AdminRole::Viewer),
+        created_at: row.get(3)?,
+        last_login_at: row.get(4)?,
+        totp_enabled: row.get(5)?,
+    })
+}
+
+impl Database {
+    pub fn list_admins(&self) -> Result<Vec<Admin>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare(&format!("SELECT {} FROM admins ORDER BY id", ADMIN_COLUMNS))?;
+            let admins = stmt
+                .query_map([], row_to_admin)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(admins)
+        })
+    }
+
+    pub fn count_admins(&self) -> Result<i64> {
+        self.with_conn(|conn| {
+            Ok(conn.query_row("SELECT COUNT(*) FROM admins", [], |row| row.get(0))?)
+        })
+    }
+
+    pub fn create_admin(&self, username: &str, password_hash: &str, role: AdminRole) -> Result<Admin> {
+        self.with_conn(|conn| {
+            let result = conn.execute(
+                "INSERT INTO admins (username, password_hash, role) VALUES (?1, ?2, ?3)",
+                params![username, password_hash, role.as_str()],
+            );
+            match result {
+                Ok(_) => {
+                    let mut stmt = conn.prepare(&format!(
+                        "SELECT {} FROM admins WHERE id = ?1",
+                        ADMIN_COLUMNS
+                    ))?;
+                    Ok(stmt.query_row([conn.last_insert_rowid()], row_to_admin)?)
+                }
+                Err(rusqlite::Error::SqliteFailure(err, _))
+                    if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+                {
+                    Err(anyhow!("Admin with username '{}' already exists", username))
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    pub fn get_admin(&self, id: i64) -> Result<Option<Admin>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare(&format!("SELECT {} FROM admins WHERE id = ?1", ADMIN_COLUMNS))?;
+            Ok(stmt.query_row([id], row_to_admin).optional()?)
+        })
+    }
+
+    /// The admin and their password hash, for login.
+    pub fn find_admin_by_username(&self, username: &str) -> Result<Option<(Admin, String)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}, password_hash FROM admins WHERE username = ?1",
+                ADMIN_COLUMNS
+            ))?;
+            let found = stmt
+                .query_row([username], |row| Ok((row_to_admin(row)?, row.get(6)?)))
+                .optional()?;
+            Ok(found)
+        })
+    }
+
+    pub fn find_admin_by_oidc_subject(&self, subject: &str) -> Result<Option<Admin>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM admins WHERE oidc_subject = ?1",
+                ADMIN_COLUMNS
+            ))?;
+            Ok(stmt.query_row([subject], row_to_admin).optional()?)
+        })
+    }
+
+    /// Creates an admin who signs in through the OIDC provider. They have no
+    /// usable password, so password login never matches them.
+    pub fn create_oidc_admin(&self, username: &str, subject: &str, role: AdminRole) -> Result<Admin> {
+        let id = self.with_conn(|conn| {
+            let result = conn.execute(
+                "INSERT INTO admins (username, password_hash, role, oidc_subject) VALUES (?1, '', ?2, ?3)",
+                params![username, role.as_str(), subject],
+            );
+            match result {
+                Ok(_) => Ok(conn.last_insert_rowid()),
+                Err(rusqlite::Error::SqliteFailure(err, _))
+                    if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+                {
+                    Err(anyhow!("Admin with username '{}' already exists", username))
+                }
+                Err(e) => Err(e.into()),
+            }
+        })?;
+        self.get_admin(id)?
+            .ok_or_else(|| anyhow!("Admin {} vanished after insert", id))
+    }
+
+    /// Changes role and/or password. Either change signs the admin out
+    /// everywhere, since their existing sessions carry the old privileges.
+    pub fn update_admin(
+        &self,
+        id: i64,
+        role: Option<AdminRole>,
+        password_hash: Option<&str>,
+    ) -> Result<Option<Admin>> {
+        let rows_affected = self.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            let rows = tx.execute(
+                "UPDATE admins SET role = COALESCE(?1, role), password_hash = COALESCE(?2, password_hash)
+                 WHERE id = ?3",
+                params![role.map(|r| r.as_str()), password_hash, id],
+            )?;
+            if rows > 0 && (role.is_some() || password_hash.is_some()) {
+                tx.execute("DELETE FROM sessions WHERE admin_id = ?1", [id])?;
+            }
+            tx.commit()?;
+            Ok(rows)
+        })?;
+
+        if rows_affected == 0 {
+            return Ok(None);
+        }
+        self.get_admin(id)
+    }
+
+    pub fn delete_admin(&self, id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            tx.execute("DELETE FROM sessions WHERE admin_id = ?1", [id])?;
+            tx.execute("DELETE FROM admin_recovery_codes WHERE admin_id = ?1", [id])?;
+            tx.execute("DELETE FROM login_challenges WHERE admin_id = ?1", [id])?;
+            tx.execute("DELETE FROM admin_tokens WHERE admin_id = ?1", [id])?;
+            let rows_affected = tx.execute("DELETE FROM admins WHERE id = ?1", [id])?;
+            tx.commit()?;
+            Ok(rows_affected > 0)
+        })
+    }
+
+    pub fn count_owners(&self) -> Result<i64> {
+        self.with_conn(|conn| {
+            Ok(conn.query_row(
+                "SELECT COUNT(*) FROM admins WHERE role = 'owner'",
+                [],
+                |row| row.get(0),
+            )?)
+        })
+    }
+
+    pub fn touch_admin_login(&self, id: i64) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE admins SET last_login_at = datetime('now') WHERE id = ?1",
+                [id],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// An unexpired session together with the admin who owns it.
+    pub fn get_session_admin(&self, session_id: &str) -> Result<Option<(Session, Admin)>> {
+        let Some(session) = self.get_session(session_id)? else {
+            return Ok(None);
+        };
+        let Some(admin_id) = session.admin_id else {
+            return Ok(None);
+        };
+        Ok(self.get_admin(admin_id)?.map(|admin| (session, admin)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn login_without_a_hash_still_verifies_and_fails() {
+        assert!(PasswordHash::new(DUMMY_PASSWORD_HASH).is_ok());
+        assert!(!verify_login_password("not-a-real-password", None));
+        assert!(!verify_login_password("anything", Some("")));
+        let hash = hash_password("secret").unwrap();
+        assert!(verify_login_password("secret", Some(&hash)));
+    }
+
+    #[test]
+    fn roles_are_ordered() {
+        assert!(AdminRole::Owner > AdminRole::Admin);
+        assert!(AdminRole::Admin > AdminRole::Viewer);
+        assert_eq!(AdminRole::parse("admin"), Some(AdminRole::Admin));
+        assert_eq!(AdminRole::parse("root"), None);
+    }
+
+    #[test]
+    fn admin_lifecycle_and_sessions() {
+        let db = Database::new_in_memory().unwrap();
+        let owner = db.create_admin("root", "hash", AdminRole::Owner).unwrap();
+        let viewer = db.create_admin("auditor", "hash", AdminRole::Viewer).unwrap();
+        assert!(db.create_admin("root", "hash", AdminRole::Viewer).is_err());
+        assert_eq!(db.count_owners().unwrap(), 1);
+
+        db.create_session("s1", "csrf", viewer.id, 7).unwrap();
+        let (session, admin) = db.get_session_admin("s1").unwrap().unwrap();
+        assert_eq!((session.admin_id, admin.role), (Some(viewer.id), AdminRole::Viewer));
+
+        // Promotion ends the sessions that carried the old role
+        let promoted = db.update_admin(viewer.id, Some(AdminRole::Admin), None).unwrap().unwrap();
+        assert_eq!(promoted.role, AdminRole::Admin);
+        assert!(db.get_session_admin("s1").unwrap().is_none());
+
+        db.create_session("s2", "csrf", owner.id, 7).unwrap();
+        let (found, hash) = db.find_admin_by_username("root").unwrap().unwrap();
+        assert_eq!((found.id, hash.as_str()), (owner.id, "hash"));
+        assert!(db.delete_admin(owner.id).unwrap());
+        assert!(db.get_session("s2").unwrap().is_none());
+    }
+
+    #[test]
+    fn legacy_admin_password_is_migrated_to_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy.db");
+        let hash = hash_password("secret").unwrap();
+        {
+            let db = Database::new(path.clone()).unwrap();
+            db.with_conn(|conn| {
+                conn.execute("DELETE FROM settings WHERE key = 'admins_migrated'", [])?;
+                Ok(())
+            })
+            .unwrap();
+            db.set_setting("admin_password_hash", &hash).unwrap();
+        }
+
+        let db = Database::new(path).unwrap();
+        let (admin, stored) = db.find_admin_by_username("admin").unwrap().unwrap();
+        assert_eq!(admin.role, AdminRole::Owner);
+        assert!(verify_password("secret", &stored));
+        assert!(db.get_setting("admin_password_hash").unwrap().is_none());
+    }
+}
@@ -0,0 +1,394 @@
+use anyhow::Result;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Argon2,
+};
+use rand::Rng;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::scopes::{parse_scopes, Scopes};
+use super::Database;
+
+/// Keys look like `sk-pp-<public id>-<secret>`. The public ID is random and
+/// indexed; only the Argon2 hash of the whole key is stored.
+const KEY_PREFIX: &str = "sk-pp-";
+const PUBLIC_ID_LEN: usize = 16;
+
+/// SQL condition selecting keys that may currently authenticate.
+const ACTIVE_KEY: &str =
+    "revoked_at IS NULL AND (expires_at IS NULL OR expires_at > datetime('now'))";
+
+const API_KEY_COLUMNS: &str = "id, user_id, name, key_prefix, created_at, expires_at, revoked_at, \
+     last_used_at, (revoked_at IS NULL AND (expires_at IS NULL OR expires_at > datetime('now'))), \
+     public_id IS NULL, scopes";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub key_prefix: String,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub revoked_at: Option<String>,
+    /// Written at most once a minute while the key is in use.
+    pub last_used_at: Option<String>,
+    /// Neither revoked nor expired.
+    pub active: bool,
+    /// Old `sk-<username>-<hex>` key; rotate it to get the opaque format.
+    pub legacy: bool,
+    /// Narrows what this key may call, on top of the user's own scopes.
+    pub scopes: Scopes,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKeyWithHash {
+    pub key: ApiKey,
+    pub key_hash: String,
+}
+
+fn row_to_api_key(row: &rusqlite::Row) -> rusqlite::Result<ApiKey> {
+    Ok(ApiKey {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        key_prefix: row.get(3)?,
+        created_at: row.get(4)?,
+        expires_at: row.get(5)?,
+        revoked_at: row.get(6)?,
+        last_used_at: row.get(7)?,
+        active: row.get(8)?,
+        legacy: row.get(9)?,
+        scopes: parse_scopes(row.get(10)?),
+    })
+}
+
+/// Returns `(full_key, public_id, hash)` for a freshly generated key.
+fn generate_api_key() -> (String, String, String) {
+    let mut rng = rand::thread_rng();
+    let public_id = hex::encode(rng.gen::<[u8; PUBLIC_ID_LEN / 2]>());
+    let secret = hex::encode(rng.gen::<[u8; 16]>());
+    let full_key = format!("{}{}-{}", KEY_PREFIX, public_id, secret);
+
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(full_key.as_bytes(), &salt)
+        .expect("Failed to hash API key")
+        .to_string();
+
+    (full_key, public_id, hash)
+}
+
+/// Extracts the public ID from an `sk-pp-<id>-<secret>` key.
+pub fn parse_public_id(api_key: &str) -> Option<&str> {
+    let (public_id, secret) = api_key.strip_prefix(KEY_PREFIX)?.split_once('-')?;
+    let well_formed = public_id.len() == PUBLIC_ID_LEN
+        && public_id.bytes().all(|b| b.is_ascii_hexdigit())
+        && !secret.is_empty();
+    well_formed.then_some(public_id)
+}
+
+fn get_api_key_in(
+    conn: &rusqlite::Connection,
+    user_id: i64,
+    key_id: i64,
+) -> Result<Option<ApiKey>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM api_keys WHERE id = ?1 AND user_id = ?2",
+        API_KEY_COLUMNS
+    ))?;
+    Ok(stmt
+        .query_row(params![key_id, user_id], row_to_api_key)
+        .optional()?)
+}
+
+/// Stores a new key for `user_id` and returns it with the plaintext secret.
+pub(crate) fn insert_api_key(
+    conn: &rusqlite::Connection,
+    user_id: i64,
+    key_name: &str,
+    expires_at: Option<&str>,
+) -> Result<(ApiKey, String)> {
+    let (full_key, public_id, hash) = generate_api_key();
+    conn.execute(
+        "INSERT INTO api_keys (user_id, name, key_prefix, public_id, key_hash, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            user_id,
+            key_name,
+            format!("{}{}", KEY_PREFIX, public_id),
+            public_id,
+            hash,
+            expires_at
+        ],
+    )?;
+    let key = get_api_key_in(conn, user_id, conn.last_insert_rowid())?
+        .ok_or_else(|| anyhow::anyhow!("Inserted API key not found"))?;
+    Ok((key, full_key))
+}
+
+impl Database {
+    /// Creates an additional key for the user; existing keys stay valid.
+    /// Returns `None` if the user does not exist.
+    pub fn create_api_key(
+        &self,
+        user_id: i64,
+        name: &str,
+        expires_at: Option<&str>,
+    ) -> Result<Option<(ApiKey, String)>> {
+        self.with_conn(|conn| {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM users WHERE id = ?1)",
+                [user_id],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Ok(None);
+            }
+            insert_api_key(conn, user_id, name, expires_at).map(Some)
+        })
+    }
+
+    pub fn list_api_keys(&self, user_id: i64) -> Result<Vec<ApiKey>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM api_keys WHERE user_id = ?1 ORDER BY created_at DESC, id DESC",
+                API_KEY_COLUMNS
+            ))?;
+            let keys = stmt
+                .query_map([user_id], row_to_api_key)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(keys)
+        })
+    }
+
+    pub fn get_api_key(&self, user_id: i64, key_id: i64) -> Result<Option<ApiKey>> {
+        self.with_conn(|conn| get_api_key_in(conn, user_id, key_id))
+    }
+
+    pub fn update_api_key(
+        &self,
+        user_id: i64,
+        key_id: i64,
+        name: Option<&str>,
+        expires_at: Option<Option<&str>>,
+    ) -> Result<Option<ApiKey>> {
+        self.with_conn(|conn| {
+            if let Some(name) = name {
+                conn.execute(
+                    "UPDATE api_keys SET name = ?1 WHERE id = ?2 AND user_id = ?3",
+                    params![name, key_id, user_id],
+                )?;
+            }
+            if let Some(expires_at) = expires_at {
+                conn.execute(
+                    "UPDATE api_keys SET expires_at = ?1 WHERE id = ?2 AND user_id = ?3",
+                    params![expires_at, key_id, user_id],
+                )?;
+            }
+            get_api_key_in(conn, user_id, key_id)
+        })
+    }
+
+    /// Marks the key revoked; it stays listed but no longer authenticates.
+    pub fn revoke_api_key(&self, user_id: i64, key_id: i64) -> Result<Option<ApiKey>> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE api_keys SET revoked_at = datetime('now')
+                 WHERE id = ?1 AND user_id = ?2 AND revoked_at IS NULL",
+                params![key_id, user_id],
+            )?;
+            get_api_key_in(conn, user_id, key_id)
+        })
+    }
+
+    pub fn delete_api_key(&self, user_id: i64, key_id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let rows_affected = conn.execute(
+                "DELETE FROM api_keys WHERE id = ?1 AND user_id = ?2",
+                params![key_id, user_id],
+            )?;
+            Ok(rows_affected > 0)
+        })
+    }
+
+    /// Replaces an active key with a fresh one of the same name, expiry and
+    /// scopes, and revokes the old one. Returns `None` if the key is not active.
+    pub fn rotate_api_key(&self, user_id: i64, key_id: i64) -> Result<Option<(ApiKey, String)>> {
+        self.with_conn(|conn| {
+            let Some(old) = get_api_key_in(conn, user_id, key_id)? else {
+                return Ok(None);
+            };
+            if !old.active {
+                return Ok(None);
+            }
+
+            let tx = conn.unchecked_transaction()?;
+            tx.execute(
+                "UPDATE api_keys SET revoked_at = datetime('now') WHERE id = ?1",
+                [key_id],
+            )?;
+            let (new, full_key) = insert_api_key(&tx, user_id, &old.name, old.expires_at.as_deref())?;
+            tx.execute(
+                "UPDATE api_keys SET scopes = (SELECT scopes FROM api_keys WHERE id = ?1) WHERE id = ?2",
+                params![key_id, new.id],
+            )?;
+            tx.execute(
+                "UPDATE users SET api_key_prefix = ?1 WHERE id = ?2 AND api_key_prefix = ?3",
+                params![new.key_prefix, user_id, old.key_prefix],
+            )?;
+            tx.commit()?;
+
+            let new = get_api_key_in(conn, user_id, new.id)?
+                .ok_or_else(|| anyhow::anyhow!("Rotated API key not found"))?;
+            Ok(Some((new, full_key)))
+        })
+    }
+
+    /// The active key with this public ID; the caller verifies the hash.
+    pub fn find_active_api_key_by_public_id(
+        &self,
+        public_id: &str,
+    ) -> Result<Option<ApiKeyWithHash>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}, key_hash FROM api_keys WHERE public_id = ?1 AND {}",
+                API_KEY_COLUMNS, ACTIVE_KEY
+            ))?;
+            let key = stmt
+                .query_row([public_id], |row| {
+                    Ok(ApiKeyWithHash {
+                        key: row_to_api_key(row)?,
+                        key_hash: row.get("key_hash")?,
+                    })
+                })
+                .optional()?;
+            Ok(key)
+        })
+    }
+
+    /// Active legacy keys whose name-derived prefix matches; the caller
+    /// verifies the hash.
+    pub fn find_active_legacy_api_keys(&self, prefix: &str) -> Result<Vec<ApiKeyWithHash>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}, key_hash FROM api_keys
+                 WHERE key_prefix = ?1 AND public_id IS NULL AND {}",
+                API_KEY_COLUMNS, ACTIVE_KEY
+            ))?;
+            let keys = stmt
+                .query_map([prefix], |row| {
+                    Ok(ApiKeyWithHash {
+                        key: row_to_api_key(row)?,
+                        key_hash: row.get("key_hash")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(keys)
+        })
+    }
+
+    pub fn touch_api_key(&self, key_id: i64) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE api_keys SET last_used_at = datetime('now') WHERE id = ?1",
+                [key_id],
+            )?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_user_issues_default_key() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, api_key) = db.create_user("alice", None).unwrap();
+
+        let keys = db.list_api_keys(user.id).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].name, "default");
+        assert!(!keys[0].legacy);
+        assert!(api_key.starts_with(&keys[0].key_prefix));
+        assert!(!api_key.contains("alice"));
+        assert_eq!(user.api_key_prefix, keys[0].key_prefix);
+    }
+
+    #[test]
+    fn parse_public_id_accepts_only_opaque_keys() {
+        let (full_key, public_id, _) = generate_api_key();
+        assert_eq!(parse_public_id(&full_key), Some(public_id.as_str()));
+
+        assert_eq!(parse_public_id("sk-alice-0123456789abcdef"), None);
+        assert_eq!(parse_public_id("sk-pp-short-secret"), None);
+        assert_eq!(parse_public_id("sk-pp-0123456789abcdef-"), None);
+        assert_eq!(parse_public_id("sk-pp-0123456789abcdef"), None);
+    }
+
+    #[test]
+    fn revoked_and_expired_keys_are_not_active() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, _) = db.create_user("alice", None).unwrap();
+        let (ci, ci_key) = db.create_api_key(user.id, "ci", None).unwrap().unwrap();
+        let (old, old_key) = db
+            .create_api_key(user.id, "old", Some("2000-01-01 00:00:00"))
+            .unwrap()
+            .unwrap();
+        assert!(!old.active);
+
+        let ci_id = parse_public_id(&ci_key).unwrap();
+        let found = db.find_active_api_key_by_public_id(ci_id).unwrap().unwrap();
+        assert_eq!(found.key.id, ci.id);
+        let old_id = parse_public_id(&old_key).unwrap();
+        assert!(db.find_active_api_key_by_public_id(old_id).unwrap().is_none());
+
+        let revoked = db.revoke_api_key(user.id, ci.id).unwrap().unwrap();
+        assert!(revoked.revoked_at.is_some());
+        assert!(!revoked.active);
+        assert!(db.find_active_api_key_by_public_id(ci_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn keys_are_scoped_to_their_user() {
+        let db = Database::new_in_memory().unwrap();
+        let (alice, _) = db.create_user("alice", None).unwrap();
+        let (bob, _) = db.create_user("bob", None).unwrap();
+        let (key, _) = db.create_api_key(alice.id, "ci", None).unwrap().unwrap();
+
+        assert!(db.get_api_key(bob.id, key.id).unwrap().is_none());
+        assert!(!db.delete_api_key(bob.id, key.id).unwrap());
+        assert!(db.create_api_key(9999, "ci", None).unwrap().is_none());
+        assert!(db.delete_api_key(alice.id, key.id).unwrap());
+    }
+
+    #[test]
+    fn rotating_a_key_keeps_its_settings_and_revokes_it() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, _) = db.create_user("alice", None).unwrap();
+        let (ci, ci_key) = db
+            .create_api_key(user.id, "ci", Some("2999-01-01 00:00:00"))
+            .unwrap()
+            .unwrap();
+        let scopes = Scopes {
+            models: Some(vec!["gpt-*".to_string()]),
+            ..Scopes::default()
+        };
+        db.set_api_key_scopes(user.id, ci.id, &scopes).unwrap();
+
+        let (rotated, new_key) = db.rotate_api_key(user.id, ci.id).unwrap().unwrap();
+        assert_ne!(rotated.id, ci.id);
+        assert_eq!(rotated.name, "ci");
+        assert_eq!(rotated.expires_at, ci.expires_at);
+        assert_eq!(rotated.scopes.models, scopes.models);
+        assert!(new_key.starts_with(&rotated.key_prefix));
+
+        let old_id = parse_public_id(&ci_key).unwrap();
+        assert!(db.find_active_api_key_by_public_id(old_id).unwrap().is_none());
+        assert!(db.rotate_api_key(user.id, ci.id).unwrap().is_none());
+    }
+}
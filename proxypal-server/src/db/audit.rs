@@ -0,0 +1,298 @@
+use anyhow::Result;
+use rusqlite::{params, Row, ToSql};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::admins::Admin;
+use super::Database;
+
+/// Fields never written to the log, wherever they appear.
+const REDACTED_FIELDS: &[&str] = &[
+    "password",
+    "apiKey",
+    "api_key",
+    "secret",
+    "accessToken",
+    "access_token",
+    "refreshToken",
+    "refresh_token",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: i64,
+    /// `None` once the admin is deleted, or for actions without a login.
+    pub actor_id: Option<i64>,
+    pub actor: String,
+    /// Dotted `<target>.<verb>`, e.g. `user.update`.
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    /// Changed fields as `{ "path": { "before": .., "after": .. } }`.
+    pub changes: Option<Value>,
+    pub ip: Option<String>,
+    pub created_at: String,
+}
+
+/// An action about to be logged.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl AuditEvent {
+    pub fn new(action: &str) -> Self {
+        Self {
+            action: action.to_string(),
+            target_type: None,
+            target_id: None,
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn target(mut self, target_type: &str, target_id: impl ToString) -> Self {
+        self.target_type = Some(target_type.to_string());
+        self.target_id = Some(target_id.to_string());
+        self
+    }
+
+    pub fn before(mut self, value: &impl Serialize) -> Self {
+        self.before = serde_json::to_value(value).ok();
+        self
+    }
+
+    pub fn after(mut self, value: &impl Serialize) -> Self {
+        self.after = serde_json::to_value(value).ok();
+        self
+    }
+
+    /// The fields that differ between before and after; `None` if nothing did.
+    pub fn changes(&self) -> Option<Value> {
+        diff(self.before.as_ref(), self.after.as_ref())
+    }
+}
+
+/// Flattens nested objects into dotted paths; arrays and scalars are leaves.
+fn flatten(prefix: &str, value: &Value, out: &mut Map<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&path, child, out);
+            }
+        }
+        _ => {
+            let path = if prefix.is_empty() { "value" } else { prefix };
+            out.insert(path.to_string(), value.clone());
+        }
+    }
+}
+
+fn redact(path: &str, value: Option<&Value>) -> Value {
+    let field = path.rsplit('.').next().unwrap_or(path);
+    match value {
+        None | Some(Value::Null) => Value::Null,
+        Some(_) if REDACTED_FIELDS.contains(&field) => Value::String("[redacted]".to_string()),
+        Some(value) => value.clone(),
+    }
+}
+
+/// Compares two JSON values field by field. Secrets show only that they changed.
+pub fn diff(before: Option<&Value>, after: Option<&Value>) -> Option<Value> {
+    let mut old = Map::new();
+    let mut new = Map::new();
+    if let Some(value) = before {
+        flatten("", value, &mut old);
+    }
+    if let Some(value) = after {
+        flatten("", value, &mut new);
+    }
+
+    let mut changes = Map::new();
+    for key in old.keys().chain(new.keys()) {
+        let (b, a) = (old.get(key), new.get(key));
+        if b != a && !changes.contains_key(key) {
+            changes.insert(
+                key.clone(),
+                serde_json::json!({
+                    "before": redact(key, b),
+                    "after": redact(key, a),
+                }),
+            );
+        }
+    }
+    (!changes.is_empty()).then_some(Value::Object(changes))
+}
+
+/// Narrows [`Database::list_audit_log`]. `action` also matches its
+/// sub-actions, so `user` finds `user.create` and `user.delete`.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+const AUDIT_COLUMNS: &str =
+    "id, actor_id, actor, action, target_type, target_id, changes, ip, created_at";
+
+const AUDIT_FILTER: &str = "(?1 IS NULL OR actor = ?1)
+    AND (?2 IS NULL OR action = ?2 OR action LIKE ?2 || '.%')
+    AND (?3 IS NULL OR target_type = ?3)
+    AND (?4 IS NULL OR target_id = ?4)
+    AND (?5 IS NULL OR datetime(created_at) >= datetime(?5))
+    AND (?6 IS NULL OR datetime(created_at) < datetime(?6))";
+
+fn row_to_audit_entry(row: &Row) -> rusqlite::Result<AuditEntry> {
+    let changes: Option<String> = row.get(6)?;
+    Ok(AuditEntry {
+        id: row.get(0)?,
+        actor_id: row.get(1)?,
+        actor: row.get(2)?,
+        action: row.get(3)?,
+        target_type: row.get(4)?,
+        target_id: row.get(5)?,
+        changes: changes.and_then(|c| serde_json::from_str(&c).ok()),
+        ip: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+impl Database {
+    pub fn record_audit(&self, actor: Option<&Admin>, ip: Option<&str>, event: &AuditEvent) -> Result<()> {
+        self.record_audit_as(
+            actor.map(|a| a.id),
+            actor.map_or("anonymous", |a| a.username.as_str()),
+            ip,
+            event,
+        )
+    }
+
+    /// Logs an action taken by someone other than an admin, e.g. a user
+    /// acting through their own API key; `actor_id` is then `None`.
+    pub fn record_audit_as(
+        &self,
+        actor_id: Option<i64>,
+        actor: &str,
+        ip: Option<&str>,
+        event: &AuditEvent,
+    ) -> Result<()> {
+        let changes = event.changes().map(|c| c.to_string());
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO audit_log (actor_id, actor, action, target_type, target_id, changes, ip)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    actor_id,
+                    actor,
+                    event.action,
+                    event.target_type,
+                    event.target_id,
+                    changes,
+                    ip,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Matching entries newest first, and how many match in total.
+    pub fn list_audit_log(&self, filter: &AuditFilter, limit: u32, offset: u32) -> Result<(Vec<AuditEntry>, u64)> {
+        self.with_conn(|conn| {
+            let mut args: Vec<&dyn ToSql> = vec![
+                &filter.actor,
+                &filter.action,
+                &filter.target_type,
+                &filter.target_id,
+                &filter.since,
+                &filter.until,
+            ];
+            let total: u64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM audit_log WHERE {}", AUDIT_FILTER),
+                args.as_slice(),
+                |row| row.get(0),
+            )?;
+
+            args.extend([&limit as &dyn ToSql, &offset]);
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM audit_log WHERE {} ORDER BY id DESC LIMIT ?7 OFFSET ?8",
+                AUDIT_COLUMNS, AUDIT_FILTER
+            ))?;
+            let entries = stmt
+                .query_map(args.as_slice(), row_to_audit_entry)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((entries, total))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::admins::AdminRole;
+    use serde_json::json;
+
+    #[test]
+    fn diff_lists_changed_paths_and_redacts_secrets() {
+        let before = json!({ "name": "a", "limits": { "rpm": 10, "tpm": 5 }, "password": "x" });
+        let after = json!({ "name": "a", "limits": { "rpm": 20, "tpm": 5 }, "password": "y" });
+        let changes = diff(Some(&before), Some(&after)).unwrap();
+        assert_eq!(
+            changes,
+            json!({
+                "limits.rpm": { "before": 10, "after": 20 },
+                "password": { "before": "[redacted]", "after": "[redacted]" },
+            })
+        );
+
+        let created = diff(None, Some(&json!({ "name": "a", "secret": "s" }))).unwrap();
+        assert_eq!(created["name"], json!({ "before": null, "after": "a" }));
+        assert_eq!(created["secret"]["after"], "[redacted]");
+        assert!(diff(Some(&before), Some(&before)).is_none());
+    }
+
+    #[test]
+    fn audit_log_is_filtered_and_paginated() {
+        let db = Database::new_in_memory().unwrap();
+        let admin = db.create_admin("root", "hash", AdminRole::Owner).unwrap();
+        for id in 1..=3 {
+            let event = AuditEvent::new("user.create").target("user", id).after(&json!({ "id": id }));
+            db.record_audit(Some(&admin), Some("203.0.113.9"), &event).unwrap();
+        }
+        db.record_audit(None, None, &AuditEvent::new("proxy.stop")).unwrap();
+
+        let (all, total) = db.list_audit_log(&AuditFilter::default(), 2, 0).unwrap();
+        assert_eq!((all.len(), total), (2, 4));
+        assert_eq!(all[0].action, "proxy.stop");
+        assert_eq!(all[0].actor, "anonymous");
+
+        let filter = AuditFilter {
+            action: Some("user".to_string()),
+            target_id: Some("2".to_string()),
+            ..AuditFilter::default()
+        };
+        let (entries, total) = db.list_audit_log(&filter, 10, 0).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(entries[0].actor_id, Some(admin.id));
+        assert_eq!(entries[0].changes.as_ref().unwrap()["id"]["after"], 2);
+
+        let future = AuditFilter {
+            since: Some("2999-01-01".to_string()),
+            ..AuditFilter::default()
+        };
+        assert_eq!(db.list_audit_log(&future, 10, 0).unwrap().1, 0);
+    }
+}
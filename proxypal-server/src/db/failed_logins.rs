@@ -0,0 +1,73 @@
+use anyhow::Result;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::Database;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedLogin {
+    pub id: i64,
+    pub ip: String,
+    /// As submitted; may not name a real admin.
+    pub username: Option<String>,
+    /// `invalid_credentials`, `invalid_totp_code` or `throttled`.
+    pub reason: String,
+    pub created_at: String,
+}
+
+impl Database {
+    pub fn record_failed_login(&self, ip: &str, username: Option<&str>, reason: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO failed_logins (ip, username, reason) VALUES (?1, ?2, ?3)",
+                params![ip, username, reason],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Most recent first, optionally for one address.
+    pub fn list_failed_logins(&self, ip: Option<&str>, limit: i64) -> Result<Vec<FailedLogin>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, ip, username, reason, created_at FROM failed_logins
+                 WHERE ?1 IS NULL OR ip = ?1
+                 ORDER BY id DESC LIMIT ?2",
+            )?;
+            let rows = stmt
+                .query_map(params![ip, limit], |row| {
+                    Ok(FailedLogin {
+                        id: row.get(0)?,
+                        ip: row.get(1)?,
+                        username: row.get(2)?,
+                        reason: row.get(3)?,
+                        created_at: row.get(4)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_logins_are_listed_newest_first() {
+        let db = Database::new_in_memory().unwrap();
+        db.record_failed_login("203.0.113.7", Some("admin"), "invalid_credentials").unwrap();
+        db.record_failed_login("198.51.100.2", None, "invalid_totp_code").unwrap();
+        db.record_failed_login("203.0.113.7", Some("root"), "throttled").unwrap();
+
+        let all = db.list_failed_logins(None, 10).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].reason, "throttled");
+
+        let one_ip = db.list_failed_logins(Some("203.0.113.7"), 1).unwrap();
+        assert_eq!(one_ip.len(), 1);
+        assert_eq!(one_ip[0].username.as_deref(), Some("root"));
+    }
+}
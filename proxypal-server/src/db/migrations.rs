@@ -87,8 +87,256 @@ pub fn run(db: &Database) -> Result<()> {
             );
 
             CREATE INDEX IF NOT EXISTS idx_oauth_states_expires_at ON oauth_states(expires_at);
+
+            -- Finished quota periods (written when used_tokens rolls over)
+            CREATE TABLE IF NOT EXISTS quota_periods (
+                id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id         INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                period          TEXT NOT NULL,
+                started_at      TEXT,
+                ended_at        TEXT NOT NULL,
+                used_tokens     INTEGER NOT NULL,
+                quota_tokens    INTEGER,
+                created_at      TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_quota_periods_user_id ON quota_periods(user_id);
+
+            -- API keys (a user may hold several, e.g. while rotating)
+            CREATE TABLE IF NOT EXISTS api_keys (
+                id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id         INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                name            TEXT NOT NULL,
+                key_prefix      TEXT NOT NULL,
+                key_hash        TEXT NOT NULL,
+                created_at      TEXT NOT NULL DEFAULT (datetime('now')),
+                expires_at      TEXT,
+                revoked_at      TEXT,
+                last_used_at    TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_api_keys_user_id ON api_keys(user_id);
+            CREATE INDEX IF NOT EXISTS idx_api_keys_key_prefix ON api_keys(key_prefix);
+
+            -- Teams share a token pool and default rate limits
+            CREATE TABLE IF NOT EXISTS teams (
+                id                      INTEGER PRIMARY KEY AUTOINCREMENT,
+                name                    TEXT NOT NULL UNIQUE,
+                quota_tokens            INTEGER,
+                used_tokens             INTEGER NOT NULL DEFAULT 0,
+                requests_per_minute     INTEGER,
+                tokens_per_minute       INTEGER,
+                max_concurrent_requests INTEGER,
+                created_at              TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            -- Admin accounts ('owner', 'admin' or 'viewer')
+            CREATE TABLE IF NOT EXISTS admins (
+                id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                username        TEXT NOT NULL UNIQUE,
+                password_hash   TEXT NOT NULL,
+                role            TEXT NOT NULL DEFAULT 'viewer',
+                created_at      TEXT NOT NULL DEFAULT (datetime('now')),
+                last_login_at   TEXT
+            );
+
+            -- One-time TOTP recovery codes (SHA-256 of the normalized code)
+            CREATE TABLE IF NOT EXISTS admin_recovery_codes (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                admin_id    INTEGER NOT NULL REFERENCES admins(id) ON DELETE CASCADE,
+                code_hash   TEXT NOT NULL,
+                used_at     TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_recovery_codes_admin_id ON admin_recovery_codes(admin_id);
+
+            -- Failed admin login attempts, for review and lockout diagnosis
+            CREATE TABLE IF NOT EXISTS failed_logins (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                ip          TEXT NOT NULL,
+                username    TEXT,
+                reason      TEXT NOT NULL,
+                created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_failed_logins_created_at ON failed_logins(created_at);
+            CREATE INDEX IF NOT EXISTS idx_failed_logins_ip ON failed_logins(ip);
+
+            -- Password accepted, second factor pending
+            CREATE TABLE IF NOT EXISTS login_challenges (
+                token       TEXT PRIMARY KEY,
+                admin_id    INTEGER NOT NULL REFERENCES admins(id) ON DELETE CASCADE,
+                attempts    INTEGER NOT NULL DEFAULT 0,
+                expires_at  TEXT NOT NULL
+            );
+
+            -- Admin actions. The actor's name is copied so entries outlive the account.
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                actor_id    INTEGER,
+                actor       TEXT NOT NULL,
+                action      TEXT NOT NULL,
+                target_type TEXT,
+                target_id   TEXT,
+                changes     TEXT,
+                ip          TEXT,
+                created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at);
+            CREATE INDEX IF NOT EXISTS idx_audit_log_actor ON audit_log(actor);
+            CREATE INDEX IF NOT EXISTS idx_audit_log_action ON audit_log(action);
+            CREATE INDEX IF NOT EXISTS idx_audit_log_target ON audit_log(target_type, target_id);
+
+            -- Long-lived bearer tokens for scripts driving the admin API
+            CREATE TABLE IF NOT EXISTS admin_tokens (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                admin_id    INTEGER NOT NULL REFERENCES admins(id) ON DELETE CASCADE,
+                name        TEXT NOT NULL,
+                public_id   TEXT NOT NULL UNIQUE,
+                token_hash  TEXT NOT NULL,
+                role        TEXT NOT NULL,
+                created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+                expires_at  TEXT,
+                last_used_at TEXT,
+                revoked_at  TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_admin_tokens_admin_id ON admin_tokens(admin_id);
             "#,
         )?;
+
+        // Columns added after the initial schema
+        add_column_if_missing(conn, "users", "requests_per_minute", "INTEGER")?;
+        add_column_if_missing(conn, "users", "tokens_per_minute", "INTEGER")?;
+        add_column_if_missing(conn, "users", "max_concurrent_requests", "INTEGER")?;
+        add_column_if_missing(conn, "users", "quota_period", "TEXT NOT NULL DEFAULT 'none'")?;
+        add_column_if_missing(conn, "users", "quota_timezone", "TEXT NOT NULL DEFAULT 'UTC'")?;
+        add_column_if_missing(conn, "users", "quota_period_started_at", "TEXT")?;
+
+        // Opaque `sk-pp-<public_id>-<secret>` keys; NULL for legacy name-prefixed keys
+        add_column_if_missing(conn, "api_keys", "public_id", "TEXT")?;
+        conn.execute_batch(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_api_keys_public_id ON api_keys(public_id);",
+        )?;
+
+        // Endpoint/provider/model scopes as JSON; NULL means unrestricted
+        add_column_if_missing(conn, "users", "scopes", "TEXT")?;
+        add_column_if_missing(conn, "api_keys", "scopes", "TEXT")?;
+
+        // Team membership, and the team each request was billed to
+        add_column_if_missing(conn, "users", "team_id", "INTEGER")?;
+        add_column_if_missing(conn, "usage_logs", "team_id", "INTEGER")?;
+        add_column_if_missing(conn, "teams", "quota_period", "TEXT NOT NULL DEFAULT 'none'")?;
+        add_column_if_missing(conn, "teams", "quota_timezone", "TEXT NOT NULL DEFAULT 'UTC'")?;
+        add_column_if_missing(conn, "teams", "quota_period_started_at", "TEXT")?;
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_users_team_id ON users(team_id);
+             CREATE INDEX IF NOT EXISTS idx_usage_team_id ON usage_logs(team_id);",
+        )?;
+
+        // The admin who logged in; sessions without one are not accepted
+        add_column_if_missing(conn, "sessions", "admin_id", "INTEGER")?;
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_sessions_admin_id ON sessions(admin_id);",
+        )?;
+
+        // Where each session was opened from, and a handle to list and revoke
+        // it by that is not the cookie secret
+        add_column_if_missing(conn, "sessions", "public_id", "TEXT")?;
+        add_column_if_missing(conn, "sessions", "ip", "TEXT")?;
+        add_column_if_missing(conn, "sessions", "user_agent", "TEXT")?;
+        conn.execute_batch(
+            "UPDATE sessions SET public_id = lower(hex(randomblob(8))) WHERE public_id IS NULL;
+             CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_public_id ON sessions(public_id);
+             CREATE INDEX IF NOT EXISTS idx_sessions_last_accessed ON sessions(last_accessed);",
+        )?;
+
+        // TOTP second factor: encrypted secret, and the last time step accepted
+        // so a code cannot be replayed
+        add_column_if_missing(conn, "admins", "totp_secret", "TEXT")?;
+        add_column_if_missing(conn, "admins", "totp_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(conn, "admins", "totp_last_step", "INTEGER")?;
+
+        // OIDC single sign-on: login states carry the nonce and PKCE verifier,
+        // and SSO admins are matched by the IdP's subject rather than password
+        add_column_if_missing(conn, "oauth_states", "nonce", "TEXT")?;
+        add_column_if_missing(conn, "oauth_states", "code_verifier", "TEXT")?;
+        add_column_if_missing(conn, "admins", "oidc_subject", "TEXT")?;
+        conn.execute_batch(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_admins_oidc_subject ON admins(oidc_subject);",
+        )?;
+
+        // Per-user model aliases as a JSON object, and the model a request
+        // named before aliasing
+        add_column_if_missing(conn, "users", "model_aliases", "TEXT")?;
+        add_column_if_missing(conn, "usage_logs", "requested_model", "TEXT")?;
+
+        // Turn the single bootstrap password into an owner account, once.
+        // Sessions opened with it carry over to that account.
+        let admins_migrated: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM settings WHERE key = 'admins_migrated')",
+            [],
+            |row| row.get(0),
+        )?;
+        if !admins_migrated {
+            conn.execute_batch(
+                "INSERT INTO admins (username, password_hash, role)
+                 SELECT 'admin', value, 'owner' FROM settings WHERE key = 'admin_password_hash';
+                 UPDATE sessions SET admin_id = (SELECT id FROM admins WHERE username = 'admin')
+                 WHERE admin_id IS NULL;
+                 DELETE FROM settings WHERE key = 'admin_password_hash';
+                 INSERT INTO settings (key, value) VALUES ('admins_migrated', '1');",
+            )?;
+        }
+
+        // Move each user's original single key into api_keys, once
+        let keys_migrated: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM settings WHERE key = 'api_keys_migrated')",
+            [],
+            |row| row.get(0),
+        )?;
+        if !keys_migrated {
+            conn.execute_batch(
+                "INSERT INTO api_keys (user_id, name, key_prefix, key_hash, created_at)
+                 SELECT id, 'default', api_key_prefix, api_key_hash, created_at FROM users
+                 WHERE api_key_hash != '';
+                 INSERT INTO settings (key, value) VALUES ('api_keys_migrated', '1');",
+            )?;
+        }
+
+        // Per-user allowed models became part of the user's scopes
+        if has_column(conn, "users", "allowed_models")? {
+            super::scopes::fold_allowed_models_into_scopes(conn)?;
+            conn.execute_batch("ALTER TABLE users DROP COLUMN allowed_models;")?;
+        }
+
         Ok(())
     })
 }
+
+pub(super) fn has_column(conn: &rusqlite::Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+    Ok(exists)
+}
+
+/// SQLite has no `ADD COLUMN IF NOT EXISTS`, so check `table_info` first.
+fn add_column_if_missing(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
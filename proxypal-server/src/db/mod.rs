@@ -3,11 +3,20 @@ use r2d2_sqlite::SqliteConnectionManager;
 use std::path::PathBuf;
 use anyhow::Result;
 
+pub mod admin_tokens;
+pub mod admins;
+pub mod api_keys;
+pub mod audit;
+pub mod failed_logins;
 mod migrations;
 pub mod oauth_state;
 pub mod providers;
+pub mod quota;
+pub mod scopes;
 pub mod sessions;
 pub mod settings;
+pub mod teams;
+pub mod totp;
 pub mod usage;
 pub mod users;
 
@@ -12,6 +12,25 @@ pub struct OAuthState {
     pub redirect_url: Option<String>,
     pub created_at: String,
     pub expires_at: String,
+    /// OIDC login only: the ID token nonce and PKCE code verifier.
+    pub nonce: Option<String>,
+    pub code_verifier: Option<String>,
+}
+
+const OAUTH_STATE_COLUMNS: &str =
+    "state, provider, admin_session_id, redirect_url, created_at, expires_at, nonce, code_verifier";
+
+fn row_to_oauth_state(row: &rusqlite::Row) -> rusqlite::Result<OAuthState> {
+    Ok(OAuthState {
+        state: row.get(0)?,
+        provider: row.get(1)?,
+        admin_session_id: row.get(2)?,
+        redirect_url: row.get(3)?,
+        created_at: row.get(4)?,
+        expires_at: row.get(5)?,
+        nonce: row.get(6)?,
+        code_verifier: row.get(7)?,
+    })
 }
 
 impl Database {
@@ -37,25 +56,41 @@ impl Database {
         })
     }
 
+    /// Create a state for an admin SSO login, which has no session yet.
+    /// The nonce and PKCE verifier come back from [`Self::consume_oauth_state`].
+    pub fn create_login_oauth_state(
+        &self,
+        provider: &str,
+        nonce: &str,
+        code_verifier: &str,
+        redirect_url: Option<&str>,
+        ttl_minutes: i64,
+    ) -> Result<String> {
+        let state = uuid::Uuid::new_v4().to_string();
+
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO oauth_states (state, provider, admin_session_id, redirect_url, created_at,
+                 expires_at, nonce, code_verifier)
+                 VALUES (?1, ?2, '', ?3, datetime('now'), datetime('now', ?4 || ' minutes'), ?5, ?6)",
+                params![state, provider, redirect_url, ttl_minutes, nonce, code_verifier],
+            )?;
+            Ok(state)
+        })
+    }
+
     /// Consume (retrieve and delete) an OAuth state.
     /// Returns None if state doesn't exist or is expired.
     pub fn consume_oauth_state(&self, state: &str) -> Result<Option<OAuthState>> {
         self.with_conn(|conn| {
             let oauth_state: Option<OAuthState> = conn.query_row(
-                "SELECT state, provider, admin_session_id, redirect_url, created_at, expires_at
-                 FROM oauth_states
-                 WHERE state = ?1 AND datetime(expires_at) > datetime('now')",
+                &format!(
+                    "SELECT {} FROM oauth_states
+                     WHERE state = ?1 AND datetime(expires_at) > datetime('now')",
+                    OAUTH_STATE_COLUMNS
+                ),
                 params![state],
-                |row| {
-                    Ok(OAuthState {
-                        state: row.get(0)?,
-                        provider: row.get(1)?,
-                        admin_session_id: row.get(2)?,
-                        redirect_url: row.get(3)?,
-                        created_at: row.get(4)?,
-                        expires_at: row.get(5)?,
-                    })
-                },
+                row_to_oauth_state,
             ).optional()?;
 
             if oauth_state.is_some() {
@@ -70,20 +105,13 @@ impl Database {
     pub fn get_oauth_state(&self, state: &str) -> Result<Option<OAuthState>> {
         self.with_conn(|conn| {
             let oauth_state = conn.query_row(
-                "SELECT state, provider, admin_session_id, redirect_url, created_at, expires_at
-                 FROM oauth_states
-                 WHERE state = ?1 AND datetime(expires_at) > datetime('now')",
+                &format!(
+                    "SELECT {} FROM oauth_states
+                     WHERE state = ?1 AND datetime(expires_at) > datetime('now')",
+                    OAUTH_STATE_COLUMNS
+                ),
                 params![state],
-                |row| {
-                    Ok(OAuthState {
-                        state: row.get(0)?,
-                        provider: row.get(1)?,
-                        admin_session_id: row.get(2)?,
-                        redirect_url: row.get(3)?,
-                        created_at: row.get(4)?,
-                        expires_at: row.get(5)?,
-                    })
-                },
+                row_to_oauth_state,
             ).optional()?;
             Ok(oauth_state)
         })
@@ -177,4 +205,20 @@ mod tests {
         let valid = db.get_oauth_state(&valid_state).unwrap();
         assert!(valid.is_some());
     }
+
+    #[test]
+    fn login_state_carries_nonce_and_verifier() {
+        let db = Database::new_in_memory().unwrap();
+
+        let state = db
+            .create_login_oauth_state("oidc", "nonce-1", "verifier-1", Some("/users"), 10)
+            .unwrap();
+
+        let consumed = db.consume_oauth_state(&state).unwrap().unwrap();
+        assert_eq!(consumed.provider, "oidc");
+        assert_eq!(consumed.nonce.as_deref(), Some("nonce-1"));
+        assert_eq!(consumed.code_verifier.as_deref(), Some("verifier-1"));
+        assert_eq!(consumed.redirect_url.as_deref(), Some("/users"));
+        assert!(consumed.admin_session_id.is_empty());
+    }
 }
@@ -0,0 +1,277 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Days, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::users::{User, UserChanges};
+use super::Database;
+
+/// SQLite `datetime('now')` format, used for all stored period boundaries.
+const DB_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// How often a user's or team's `used_tokens` starts over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaPeriod {
+    #[default]
+    None,
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl QuotaPeriod {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "none" => Some(Self::None),
+            "daily" => Some(Self::Daily),
+            "weekly" => Some(Self::Weekly),
+            "monthly" => Some(Self::Monthly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+            Self::Monthly => "monthly",
+        }
+    }
+
+    /// Start of the period containing `now`, measured in local time of `tz`
+    /// (days and months begin at local midnight, weeks on Monday).
+    pub fn period_start(&self, now: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let today = now.with_timezone(&tz).date_naive();
+        let start_date = match self {
+            Self::None => return None,
+            Self::Daily => today,
+            Self::Weekly => today - Days::new(today.weekday().num_days_from_monday() as u64),
+            Self::Monthly => today.with_day(1)?,
+        };
+
+        let midnight = start_date.and_hms_opt(0, 0, 0)?;
+        // Midnight can fall into a DST gap; the first valid instant then wins.
+        let local = tz
+            .from_local_datetime(&midnight)
+            .earliest()
+            .unwrap_or_else(|| tz.from_utc_datetime(&midnight));
+        Some(local.with_timezone(&Utc))
+    }
+}
+
+/// Parses an IANA timezone name, e.g. `Europe/Berlin`.
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.parse().ok()
+}
+
+pub fn format_db_time(time: DateTime<Utc>) -> String {
+    time.format(DB_TIME_FORMAT).to_string()
+}
+
+pub fn parse_db_time(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, DB_TIME_FORMAT)
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// A finished quota period, kept when `used_tokens` rolls over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaPeriodRecord {
+    pub id: i64,
+    pub user_id: i64,
+    pub period: QuotaPeriod,
+    pub started_at: Option<String>,
+    pub ended_at: String,
+    pub used_tokens: i64,
+    pub quota_tokens: Option<i64>,
+}
+
+/// When the current `period` in `timezone` began, i.e. its latest boundary.
+pub(super) fn current_period_start(period: QuotaPeriod, timezone: &str) -> Result<Option<String>> {
+    let tz = parse_timezone(timezone)
+        .ok_or_else(|| anyhow::anyhow!("Unknown timezone: {}", timezone))?;
+    Ok(period.period_start(Utc::now(), tz).map(format_db_time))
+}
+
+/// Start of the period containing `now`, if the one that began at
+/// `started_at` has ended by then and `used_tokens` should roll over.
+pub(super) fn rollover_start(
+    period: &str,
+    timezone: &str,
+    started_at: Option<&str>,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let period = QuotaPeriod::parse(period)?;
+    let tz = parse_timezone(timezone).unwrap_or(Tz::UTC);
+    let current_start = period.period_start(now, tz)?;
+    let previous_start = started_at.and_then(parse_db_time);
+    if previous_start.is_some_and(|start| start >= current_start) {
+        return None;
+    }
+    Some(format_db_time(current_start))
+}
+
+impl Database {
+    /// Changes the user's quota period. The current period is taken to have
+    /// begun at its latest boundary, so usage so far counts toward it.
+    pub fn set_user_quota_period(
+        &self,
+        id: i64,
+        period: QuotaPeriod,
+        timezone: &str,
+    ) -> Result<Option<User>> {
+        self.apply_user_changes(
+            id,
+            &UserChanges {
+                quota_period: Some((period, timezone)),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Closes every quota period that ended before `now`: records it in the
+    /// history and resets `used_tokens`. Returns how many users rolled over.
+    pub fn roll_over_quotas(&self, now: DateTime<Utc>) -> Result<usize> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, quota_period, quota_timezone, quota_period_started_at
+                 FROM users WHERE quota_period != 'none'",
+            )?;
+            let users = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut rolled = 0;
+            for (user_id, period, timezone, started_at) in users {
+                let Some(current_start) =
+                    rollover_start(&period, &timezone, started_at.as_deref(), now)
+                else {
+                    continue;
+                };
+
+                let tx = conn.unchecked_transaction()?;
+                if started_at.is_some() {
+                    tx.execute(
+                        "INSERT INTO quota_periods (user_id, period, started_at, ended_at, used_tokens, quota_tokens)
+                         SELECT id, quota_period, quota_period_started_at, ?1, used_tokens, quota_tokens
+                         FROM users WHERE id = ?2",
+                        params![current_start, user_id],
+                    )?;
+                }
+                tx.execute(
+                    "UPDATE users SET used_tokens = 0, quota_period_started_at = ?1 WHERE id = ?2",
+                    params![current_start, user_id],
+                )?;
+                tx.commit()?;
+                rolled += 1;
+            }
+
+            Ok(rolled)
+        })
+    }
+
+    pub fn list_quota_history(&self, user_id: i64, limit: u32) -> Result<Vec<QuotaPeriodRecord>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, user_id, period, started_at, ended_at, used_tokens, quota_tokens
+                 FROM quota_periods WHERE user_id = ?1
+                 ORDER BY ended_at DESC, id DESC LIMIT ?2",
+            )?;
+            let records = stmt
+                .query_map(params![user_id, limit], |row| {
+                    Ok(QuotaPeriodRecord {
+                        id: row.get(0)?,
+                        user_id: row.get(1)?,
+                        period: QuotaPeriod::parse(&row.get::<_, String>(2)?).unwrap_or_default(),
+                        started_at: row.get(3)?,
+                        ended_at: row.get(4)?,
+                        used_tokens: row.get(5)?,
+                        quota_tokens: row.get(6)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(records)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        parse_db_time(value).unwrap()
+    }
+
+    #[test]
+    fn period_start_respects_timezone() {
+        let now = utc("2025-03-05 02:30:00"); // Wednesday
+        let berlin = parse_timezone("Europe/Berlin").unwrap();
+        let la = parse_timezone("America/Los_Angeles").unwrap();
+
+        assert_eq!(QuotaPeriod::None.period_start(now, Tz::UTC), None);
+        assert_eq!(
+            QuotaPeriod::Daily.period_start(now, Tz::UTC),
+            Some(utc("2025-03-05 00:00:00"))
+        );
+        assert_eq!(
+            QuotaPeriod::Daily.period_start(now, berlin),
+            Some(utc("2025-03-04 23:00:00"))
+        );
+        // Still Tuesday evening in Los Angeles
+        assert_eq!(
+            QuotaPeriod::Daily.period_start(now, la),
+            Some(utc("2025-03-04 08:00:00"))
+        );
+        assert_eq!(
+            QuotaPeriod::Weekly.period_start(now, Tz::UTC),
+            Some(utc("2025-03-03 00:00:00"))
+        );
+        assert_eq!(
+            QuotaPeriod::Monthly.period_start(now, berlin),
+            Some(utc("2025-02-28 23:00:00"))
+        );
+    }
+
+    #[test]
+    fn roll_over_resets_usage_and_records_history() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, _) = db.create_user("alice", Some(1000)).unwrap();
+        let (other, _) = db.create_user("bob", None).unwrap();
+        db.set_user_quota_period(user.id, QuotaPeriod::Daily, "UTC").unwrap();
+        db.log_usage(user.id, "openai", "gpt-4o", 100, 50, 10, "success").unwrap();
+        db.log_usage(other.id, "openai", "gpt-4o", 100, 50, 10, "success").unwrap();
+
+        let today = QuotaPeriod::Daily.period_start(Utc::now(), Tz::UTC).unwrap();
+        assert_eq!(db.roll_over_quotas(Utc::now()).unwrap(), 0);
+
+        let tomorrow = today + chrono::Duration::days(1) + chrono::Duration::minutes(1);
+        assert_eq!(db.roll_over_quotas(tomorrow).unwrap(), 1);
+        assert_eq!(db.roll_over_quotas(tomorrow).unwrap(), 0);
+
+        assert_eq!(db.get_user_by_id(user.id).unwrap().unwrap().used_tokens, 0);
+        assert_eq!(db.get_user_by_id(other.id).unwrap().unwrap().used_tokens, 150);
+
+        let history = db.list_quota_history(user.id, 10).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].period, QuotaPeriod::Daily);
+        assert_eq!(history[0].used_tokens, 150);
+        assert_eq!(history[0].quota_tokens, Some(1000));
+        assert_eq!(history[0].started_at.as_deref(), Some(format_db_time(today).as_str()));
+        assert_eq!(
+            history[0].ended_at,
+            format_db_time(today + chrono::Duration::days(1))
+        );
+    }
+}
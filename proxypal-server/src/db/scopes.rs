@@ -0,0 +1,314 @@
+use anyhow::Result;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::api_keys::ApiKey;
+use super::users::{User, UserChanges};
+use super::Database;
+
+/// Groups of `/v1` endpoints a scope can grant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndpointFamily {
+    Models,
+    Chat,
+    Completions,
+    Embeddings,
+    Messages,
+    Responses,
+    Gemini,
+}
+
+impl EndpointFamily {
+    /// Family of a proxied path such as `/v1/chat/completions` or
+    /// `/v1beta/models/gemini-2.5-pro:generateContent`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let path = path.split('?').next().unwrap_or(path);
+        if path.starts_with("/v1beta/models/") {
+            return Some(Self::Gemini);
+        }
+        match path.strip_prefix("/v1")? {
+            "/models" => Some(Self::Models),
+            "/chat/completions" => Some(Self::Chat),
+            "/completions" => Some(Self::Completions),
+            "/embeddings" => Some(Self::Embeddings),
+            "/messages" | "/messages/count_tokens" => Some(Self::Messages),
+            "/responses" => Some(Self::Responses),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Models => "models",
+            Self::Chat => "chat",
+            Self::Completions => "completions",
+            Self::Embeddings => "embeddings",
+            Self::Messages => "messages",
+            Self::Responses => "responses",
+            Self::Gemini => "gemini",
+        }
+    }
+}
+
+/// Matches a model name against a pattern where `*` matches any run of
+/// characters (e.g. `claude-*`).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let first = parts[0];
+    let last = parts[parts.len() - 1];
+    if text.len() < first.len() + last.len() || !text.starts_with(first) || !text.ends_with(last) {
+        return false;
+    }
+
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// What a user or key may call. `None` leaves that dimension unrestricted;
+/// model entries are glob patterns like `text-embedding-*`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scopes {
+    #[serde(default)]
+    pub endpoints: Option<Vec<EndpointFamily>>,
+    #[serde(default)]
+    pub providers: Option<Vec<String>>,
+    #[serde(default)]
+    pub models: Option<Vec<String>>,
+}
+
+impl Scopes {
+    pub fn is_unrestricted(&self) -> bool {
+        self.endpoints.is_none() && self.providers.is_none() && self.models.is_none()
+    }
+
+    pub fn allows_model(&self, model: &str) -> bool {
+        match &self.models {
+            None => true,
+            Some(patterns) => patterns.iter().any(|p| glob_match(p, model)),
+        }
+    }
+
+    /// Narrows `models` to what both it and `patterns` allow. Two pattern
+    /// lists cannot always be intersected exactly, so a pattern is kept only
+    /// when the other list covers all of it; anything in doubt is dropped.
+    pub fn restrict_models(&mut self, patterns: Vec<String>) {
+        self.models = Some(match self.models.take() {
+            None => patterns,
+            Some(current) => {
+                // Matching a pattern as literal text against another means
+                // every name it matches is matched by the other too
+                let covered = |pattern: &String, by: &[String]| by.iter().any(|b| glob_match(b, pattern));
+                let mut merged: Vec<String> = current
+                    .iter()
+                    .filter(|p| covered(p, &patterns))
+                    .chain(patterns.iter().filter(|p| covered(p, &current)))
+                    .cloned()
+                    .collect();
+                merged.sort();
+                merged.dedup();
+                merged
+            }
+        });
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let entries = [("providers", &self.providers), ("models", &self.models)];
+        for (field, values) in entries {
+            if values.iter().flatten().any(|v| v.trim().is_empty()) {
+                return Err(format!("scopes.{} must not contain empty entries", field));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stored as JSON; NULL means unrestricted.
+pub(crate) fn scopes_to_column(scopes: &Scopes) -> Result<Option<String>> {
+    if scopes.is_unrestricted() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(scopes)?))
+}
+
+pub(crate) fn parse_scopes(value: Option<String>) -> Scopes {
+    value
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Users once had a separate `allowed_models` list, checked on top of their
+/// scopes. Folds each into `scopes.models`, after which the migration drops
+/// the old column, so there is a single model allow-list.
+pub(crate) fn fold_allowed_models_into_scopes(conn: &rusqlite::Connection) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT id, allowed_models, scopes FROM users WHERE allowed_models IS NOT NULL",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (id, allowed_models, scopes) in rows {
+        let mut scopes = parse_scopes(scopes);
+        match serde_json::from_str::<Vec<String>>(&allowed_models) {
+            Ok(patterns) => scopes.restrict_models(patterns),
+            // An unreadable list never allowed anything
+            Err(_) => scopes.models = Some(Vec::new()),
+        }
+        conn.execute(
+            "UPDATE users SET scopes = ?1 WHERE id = ?2",
+            params![scopes_to_column(&scopes)?, id],
+        )?;
+    }
+    Ok(())
+}
+
+impl Database {
+    pub fn set_user_scopes(&self, id: i64, scopes: &Scopes) -> Result<Option<User>> {
+        self.apply_user_changes(
+            id,
+            &UserChanges {
+                scopes: Some(scopes),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn set_api_key_scopes(
+        &self,
+        user_id: i64,
+        key_id: i64,
+        scopes: &Scopes,
+    ) -> Result<Option<ApiKey>> {
+        let value = scopes_to_column(scopes)?;
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE api_keys SET scopes = ?1 WHERE id = ?2 AND user_id = ?3",
+                params![value, key_id, user_id],
+            )?;
+            Ok(())
+        })?;
+        self.get_api_key(user_id, key_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_family_from_path() {
+        assert_eq!(EndpointFamily::from_path("/v1/chat/completions"), Some(EndpointFamily::Chat));
+        assert_eq!(EndpointFamily::from_path("/v1/completions"), Some(EndpointFamily::Completions));
+        assert_eq!(
+            EndpointFamily::from_path("/v1/messages/count_tokens"),
+            Some(EndpointFamily::Messages)
+        );
+        assert_eq!(
+            EndpointFamily::from_path("/v1beta/models/gemini-2.5-pro:streamGenerateContent?alt=sse"),
+            Some(EndpointFamily::Gemini)
+        );
+        assert_eq!(EndpointFamily::from_path("/v1/unknown"), None);
+    }
+
+    #[test]
+    fn scopes_round_trip_and_unrestricted_is_null() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, _) = db.create_user("alice", None).unwrap();
+        assert!(user.scopes.is_unrestricted());
+
+        let scopes = Scopes {
+            endpoints: Some(vec![EndpointFamily::Embeddings]),
+            providers: None,
+            models: Some(vec!["text-embedding-*".to_string()]),
+        };
+        let updated = db.set_user_scopes(user.id, &scopes).unwrap().unwrap();
+        assert_eq!(updated.scopes, scopes);
+
+        let key = db.list_api_keys(user.id).unwrap().remove(0);
+        let key = db.set_api_key_scopes(user.id, key.id, &scopes).unwrap().unwrap();
+        assert_eq!(key.scopes, scopes);
+
+        let cleared = db.set_user_scopes(user.id, &Scopes::default()).unwrap().unwrap();
+        assert!(cleared.scopes.is_unrestricted());
+        let stored: Option<String> = db
+            .with_conn(|conn| {
+                Ok(conn.query_row("SELECT scopes FROM users WHERE id = ?1", [user.id], |row| {
+                    row.get(0)
+                })?)
+            })
+            .unwrap();
+        assert!(stored.is_none());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("gpt-4o", "gpt-4o"));
+        assert!(!glob_match("gpt-4o", "gpt-4o-mini"));
+        assert!(glob_match("gpt-4o*", "gpt-4o-mini"));
+        assert!(glob_match("claude-*", "claude-sonnet-4"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("claude-*-4*", "claude-opus-4-1"));
+        assert!(!glob_match("claude-*-4*", "claude-3-opus"));
+        assert!(!glob_match("a*a", "a"));
+    }
+
+    #[test]
+    fn allowed_models_fold_into_the_narrower_scope() {
+        let db = Database::new_in_memory().unwrap();
+        let (alice, _) = db.create_user("alice", None).unwrap();
+        let (bob, _) = db.create_user("bob", None).unwrap();
+        let scoped = Scopes {
+            models: Some(vec!["gpt-*".to_string(), "claude-opus-4-1".to_string()]),
+            ..Scopes::default()
+        };
+        db.set_user_scopes(bob.id, &scoped).unwrap();
+        // A database from before the fold still has the old column
+        db.with_conn(|conn| {
+            conn.execute_batch("ALTER TABLE users ADD COLUMN allowed_models TEXT;")?;
+            conn.execute(
+                "UPDATE users SET allowed_models = ?1 WHERE id = ?2",
+                params![r#"["claude-*"]"#, alice.id],
+            )?;
+            conn.execute(
+                "UPDATE users SET allowed_models = ?1 WHERE id = ?2",
+                params![r#"["claude-*","gpt-4o"]"#, bob.id],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+        super::super::migrations::run(&db).unwrap();
+
+        let alice = db.get_user_by_id(alice.id).unwrap().unwrap();
+        assert_eq!(alice.scopes.models, Some(vec!["claude-*".to_string()]));
+        let bob = db.get_user_by_id(bob.id).unwrap().unwrap();
+        assert_eq!(
+            bob.scopes.models,
+            Some(vec!["claude-opus-4-1".to_string(), "gpt-4o".to_string()])
+        );
+        assert!(bob.scopes.allows_model("gpt-4o"));
+        assert!(!bob.scopes.allows_model("gpt-4o-mini"));
+
+        let still_there = db
+            .with_conn(|conn| super::super::migrations::has_column(conn, "users", "allowed_models"))
+            .unwrap();
+        assert!(!still_there);
+    }
+}
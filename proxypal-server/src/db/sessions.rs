@@ -11,19 +11,57 @@ pub struct Session {
     pub expires_at: String,
     pub created_at: String,
     pub last_accessed: String,
+    pub admin_id: Option<i64>,
+}
+
+/// Where a session was opened from.
+#[derive(Debug, Clone, Default)]
+pub struct SessionClient {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// A session as shown to admins. `id` is a public handle, not the cookie value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub id: String,
+    pub admin_id: i64,
+    pub username: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: String,
+    pub last_accessed: String,
+    pub expires_at: String,
+    /// The session making the request.
+    pub current: bool,
 }
 
 impl Database {
-    pub fn create_session(&self, id: &str, csrf_token: &str, ttl_days: i64) -> Result<Session> {
+    pub fn create_session(&self, id: &str, csrf_token: &str, admin_id: i64, ttl_days: i64) -> Result<Session> {
+        self.create_session_for(id, csrf_token, admin_id, ttl_days, &SessionClient::default())
+    }
+
+    pub fn create_session_for(
+        &self,
+        id: &str,
+        csrf_token: &str,
+        admin_id: i64,
+        ttl_days: i64,
+        client: &SessionClient,
+    ) -> Result<Session> {
+        let public_id = hex::encode(rand::random::<[u8; 8]>());
         self.with_conn(|conn| {
             conn.execute(
-                "INSERT INTO sessions (id, csrf_token, expires_at, created_at, last_accessed)
-                 VALUES (?1, ?2, datetime('now', ?3 || ' days'), datetime('now'), datetime('now'))",
-                params![id, csrf_token, ttl_days],
+                "INSERT INTO sessions (id, csrf_token, expires_at, created_at, last_accessed, admin_id,
+                                       public_id, ip, user_agent)
+                 VALUES (?1, ?2, datetime('now', ?3 || ' days'), datetime('now'), datetime('now'), ?4,
+                         ?5, ?6, ?7)",
+                params![id, csrf_token, ttl_days, admin_id, public_id, client.ip, client.user_agent],
             )?;
             
             let mut stmt = conn.prepare(
-                "SELECT id, csrf_token, expires_at, created_at, last_accessed FROM sessions WHERE id = ?1"
+                "SELECT id, csrf_token, expires_at, created_at, last_accessed, admin_id FROM sessions WHERE id = ?1"
             )?;
             let session = stmt.query_row(params![id], |row| {
                 Ok(Session {
@@ -32,6 +70,7 @@ impl Database {
                     expires_at: row.get(2)?,
                     created_at: row.get(3)?,
                     last_accessed: row.get(4)?,
+                    admin_id: row.get(5)?,
                 })
             })?;
             Ok(session)
@@ -41,7 +80,7 @@ impl Database {
     pub fn get_session(&self, id: &str) -> Result<Option<Session>> {
         self.with_conn(|conn| {
             let mut stmt = conn.prepare(
-                "SELECT id, csrf_token, expires_at, created_at, last_accessed 
+                "SELECT id, csrf_token, expires_at, created_at, last_accessed, admin_id
                  FROM sessions 
                  WHERE id = ?1 AND datetime(expires_at) > datetime('now')"
             )?;
@@ -52,6 +91,7 @@ impl Database {
                     expires_at: row.get(2)?,
                     created_at: row.get(3)?,
                     last_accessed: row.get(4)?,
+                    admin_id: row.get(5)?,
                 })
             }).optional()?;
             Ok(session)
@@ -75,6 +115,71 @@ impl Database {
         })
     }
 
+    /// Unexpired sessions, most recently used first; only `admin_id`'s if given.
+    /// `current_id` is the requesting session's cookie value.
+    pub fn list_sessions(&self, admin_id: Option<i64>, current_id: &str) -> Result<Vec<SessionInfo>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT s.public_id, s.admin_id, a.username, s.ip, s.user_agent,
+                        s.created_at, s.last_accessed, s.expires_at, s.id = ?2
+                 FROM sessions s JOIN admins a ON a.id = s.admin_id
+                 WHERE datetime(s.expires_at) > datetime('now')
+                   AND (?1 IS NULL OR s.admin_id = ?1)
+                 ORDER BY s.last_accessed DESC, s.created_at DESC",
+            )?;
+            let sessions = stmt
+                .query_map(params![admin_id, current_id], |row| {
+                    Ok(SessionInfo {
+                        id: row.get(0)?,
+                        admin_id: row.get(1)?,
+                        username: row.get(2)?,
+                        ip: row.get(3)?,
+                        user_agent: row.get(4)?,
+                        created_at: row.get(5)?,
+                        last_accessed: row.get(6)?,
+                        expires_at: row.get(7)?,
+                        current: row.get(8)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(sessions)
+        })
+    }
+
+    /// Ends a session by its public id; only `admin_id`'s if given.
+    pub fn revoke_session(&self, public_id: &str, admin_id: Option<i64>) -> Result<bool> {
+        self.with_conn(|conn| {
+            let rows = conn.execute(
+                "DELETE FROM sessions WHERE public_id = ?1 AND (?2 IS NULL OR admin_id = ?2)",
+                params![public_id, admin_id],
+            )?;
+            Ok(rows > 0)
+        })
+    }
+
+    /// Ends all of an admin's sessions except `keep_id` (a cookie value).
+    pub fn revoke_other_sessions(&self, admin_id: i64, keep_id: &str) -> Result<u64> {
+        self.with_conn(|conn| {
+            let rows = conn.execute(
+                "DELETE FROM sessions WHERE admin_id = ?1 AND id != ?2",
+                params![admin_id, keep_id],
+            )?;
+            Ok(rows as u64)
+        })
+    }
+
+    /// Deletes sessions unused for `idle_minutes` or longer.
+    pub fn cleanup_idle_sessions(&self, idle_minutes: i64) -> Result<u64> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute(
+                "DELETE FROM sessions
+                 WHERE datetime(last_accessed) <= datetime('now', ?1 || ' minutes')",
+                params![-idle_minutes],
+            )?;
+            Ok(deleted as u64)
+        })
+    }
+
     pub fn cleanup_expired_sessions(&self) -> Result<u64> {
         self.with_conn(|conn| {
             let deleted = conn.execute(
@@ -85,3 +190,59 @@ impl Database {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::admins::AdminRole;
+
+    #[test]
+    fn sessions_are_listed_and_revoked_by_public_id() {
+        let db = Database::new_in_memory().unwrap();
+        let owner = db.create_admin("root", "hash", AdminRole::Owner).unwrap();
+        let viewer = db.create_admin("auditor", "hash", AdminRole::Viewer).unwrap();
+        let client = SessionClient {
+            ip: Some("203.0.113.9".to_string()),
+            user_agent: Some("curl/8.0".to_string()),
+        };
+        db.create_session_for("laptop", "csrf", owner.id, 7, &client).unwrap();
+        db.create_session("phone", "csrf", owner.id, 7).unwrap();
+        db.create_session("other", "csrf", viewer.id, 7).unwrap();
+        db.create_session("expired", "csrf", owner.id, -1).unwrap();
+
+        let mine = db.list_sessions(Some(owner.id), "laptop").unwrap();
+        assert_eq!(mine.len(), 2);
+        let laptop = mine.iter().find(|s| s.current).unwrap();
+        assert_eq!(laptop.ip.as_deref(), Some("203.0.113.9"));
+        assert_ne!(laptop.id, "laptop");
+        assert_eq!(db.list_sessions(None, "laptop").unwrap().len(), 3);
+
+        let other = db.list_sessions(Some(viewer.id), "").unwrap().remove(0);
+        assert!(!db.revoke_session(&other.id, Some(owner.id)).unwrap());
+        assert!(db.revoke_session(&other.id, None).unwrap());
+        assert!(db.get_session("other").unwrap().is_none());
+
+        assert_eq!(db.revoke_other_sessions(owner.id, "laptop").unwrap(), 2);
+        assert!(db.get_session("laptop").unwrap().is_some());
+    }
+
+    #[test]
+    fn idle_sessions_are_cleaned_up() {
+        let db = Database::new_in_memory().unwrap();
+        let owner = db.create_admin("root", "hash", AdminRole::Owner).unwrap();
+        db.create_session("idle", "csrf", owner.id, 7).unwrap();
+        db.create_session("active", "csrf", owner.id, 7).unwrap();
+        db.with_conn(|conn| {
+            conn.execute(
+                "UPDATE sessions SET last_accessed = datetime('now', '-3 hours') WHERE id = 'idle'",
+                [],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(db.cleanup_idle_sessions(120).unwrap(), 1);
+        assert!(db.get_session("idle").unwrap().is_none());
+        assert!(db.get_session("active").unwrap().is_some());
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+use super::quota::{current_period_start, rollover_start, QuotaPeriod};
+use super::users::{User, UserChanges, UserLimits};
+use super::Database;
+
+/// A group of users sharing one token pool. Members' own quotas still apply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Team {
+    pub id: i64,
+    pub name: String,
+    pub quota_tokens: Option<i64>,
+    pub used_tokens: i64,
+    /// How often `used_tokens` starts over; unlike a user's, a team's
+    /// finished periods are not kept in a history.
+    pub quota_period: QuotaPeriod,
+    /// IANA timezone that period boundaries are computed in.
+    pub quota_timezone: String,
+    pub quota_period_started_at: Option<String>,
+    /// Defaults for members without their own override.
+    #[serde(flatten)]
+    pub limits: UserLimits,
+    pub member_count: i64,
+    pub created_at: String,
+}
+
+impl Team {
+    pub fn quota_exhausted(&self) -> bool {
+        self.quota_tokens.is_some_and(|quota| self.used_tokens >= quota)
+    }
+}
+
+const TEAM_COLUMNS: &str = "id, name, quota_tokens, used_tokens, requests_per_minute, tokens_per_minute, \
+     max_concurrent_requests, (SELECT COUNT(*) FROM users WHERE users.team_id = teams.id), created_at, \
+     quota_period, quota_timezone, quota_period_started_at";
+
+fn row_to_team(row: &rusqlite::Row) -> rusqlite::Result<Team> {
+    Ok(Team {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        quota_tokens: row.get(2)?,
+        used_tokens: row.get(3)?,
+        limits: UserLimits {
+            requests_per_minute: row.get(4)?,
+            tokens_per_minute: row.get(5)?,
+            max_concurrent_requests: row.get(6)?,
+        },
+        member_count: row.get(7)?,
+        created_at: row.get(8)?,
+        quota_period: QuotaPeriod::parse(&row.get::<_, String>(9)?).unwrap_or_default(),
+        quota_timezone: row.get(10)?,
+        quota_period_started_at: row.get(11)?,
+    })
+}
+
+fn conflict_or(name: &str, err: rusqlite::Error) -> anyhow::Error {
+    match err {
+        rusqlite::Error::SqliteFailure(e, _) if e.code == rusqlite::ErrorCode::ConstraintViolation => {
+            anyhow!("Team with name '{}' already exists", name)
+        }
+        e => e.into(),
+    }
+}
+
+impl Database {
+    pub fn list_teams(&self) -> Result<Vec<Team>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare(&format!("SELECT {} FROM teams ORDER BY id", TEAM_COLUMNS))?;
+            let teams = stmt
+                .query_map([], row_to_team)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(teams)
+        })
+    }
+
+    pub fn create_team(
+        &self,
+        name: &str,
+        quota_tokens: Option<i64>,
+        limits: &UserLimits,
+    ) -> Result<Team> {
+        self.create_team_with(name, quota_tokens, limits, None)
+    }
+
+    /// Creates a team whose quota rolls over every `quota_period`, given as
+    /// the period and the IANA timezone its boundaries are computed in.
+    pub fn create_team_with(
+        &self,
+        name: &str,
+        quota_tokens: Option<i64>,
+        limits: &UserLimits,
+        quota_period: Option<(QuotaPeriod, &str)>,
+    ) -> Result<Team> {
+        let (period, timezone) = quota_period.unwrap_or((QuotaPeriod::None, "UTC"));
+        let started_at = current_period_start(period, timezone)?;
+        let id = self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO teams (name, quota_tokens, requests_per_minute, tokens_per_minute,
+                 max_concurrent_requests, quota_period, quota_timezone, quota_period_started_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    name,
+                    quota_tokens,
+                    limits.requests_per_minute,
+                    limits.tokens_per_minute,
+                    limits.max_concurrent_requests,
+                    period.as_str(),
+                    timezone,
+                    started_at
+                ],
+            )
+            .map_err(|e| conflict_or(name, e))?;
+            Ok(conn.last_insert_rowid())
+        })?;
+        self.get_team(id)?
+            .ok_or_else(|| anyhow!("Team {} vanished after insert", id))
+    }
+
+    pub fn get_team(&self, id: i64) -> Result<Option<Team>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare(&format!("SELECT {} FROM teams WHERE id = ?1", TEAM_COLUMNS))?;
+            Ok(stmt.query_row([id], row_to_team).optional()?)
+        })
+    }
+
+    pub fn update_team(
+        &self,
+        id: i64,
+        name: Option<&str>,
+        quota_tokens: Option<Option<i64>>,
+        limits: Option<&UserLimits>,
+        quota_period: Option<(QuotaPeriod, &str)>,
+    ) -> Result<Option<Team>> {
+        let mut updates = Vec::new();
+        let mut values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(n) = name {
+            updates.push("name = ?");
+            values.push(Box::new(n.to_string()));
+        }
+        if let Some(qt) = quota_tokens {
+            updates.push("quota_tokens = ?");
+            values.push(Box::new(qt));
+        }
+        if let Some(l) = limits {
+            updates.push("requests_per_minute = ?");
+            values.push(Box::new(l.requests_per_minute));
+            updates.push("tokens_per_minute = ?");
+            values.push(Box::new(l.tokens_per_minute));
+            updates.push("max_concurrent_requests = ?");
+            values.push(Box::new(l.max_concurrent_requests));
+        }
+        // As for users, usage so far counts toward the new period
+        if let Some((period, timezone)) = quota_period {
+            updates.push("quota_period = ?");
+            values.push(Box::new(period.as_str()));
+            updates.push("quota_timezone = ?");
+            values.push(Box::new(timezone.to_string()));
+            updates.push("quota_period_started_at = ?");
+            values.push(Box::new(current_period_start(period, timezone)?));
+        }
+
+        if updates.is_empty() {
+            return self.get_team(id);
+        }
+
+        values.push(Box::new(id));
+        let sql = format!("UPDATE teams SET {} WHERE id = ?", updates.join(", "));
+        let rows_affected = self.with_conn(|conn| {
+            let value_refs: Vec<&dyn rusqlite::ToSql> = values.iter().map(|v| v.as_ref()).collect();
+            conn.execute(&sql, value_refs.as_slice())
+                .map_err(|e| conflict_or(name.unwrap_or_default(), e))
+        })?;
+
+        if rows_affected == 0 {
+            return Ok(None);
+        }
+        self.get_team(id)
+    }
+
+    /// Deletes the team and leaves its members without one. Past usage keeps
+    /// its team attribution.
+    pub fn delete_team(&self, id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            tx.execute("UPDATE users SET team_id = NULL WHERE team_id = ?1", [id])?;
+            let rows_affected = tx.execute("DELETE FROM teams WHERE id = ?1", [id])?;
+            tx.commit()?;
+            Ok(rows_affected > 0)
+        })
+    }
+
+    pub fn set_user_team(&self, user_id: i64, team_id: Option<i64>) -> Result<Option<User>> {
+        self.apply_user_changes(
+            user_id,
+            &UserChanges {
+                team_id: Some(team_id),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Zeroes the team's pool and returns what it held. Both happen in one
+    /// write transaction, so usage logged in between is not lost.
+    pub fn reset_team_used_tokens(&self, id: i64) -> Result<Option<i64>> {
+        self.with_conn(|conn| {
+            let tx = rusqlite::Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+            let prev: Option<i64> = tx
+                .query_row("SELECT used_tokens FROM teams WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })
+                .optional()?;
+
+            if prev.is_some() {
+                tx.execute("UPDATE teams SET used_tokens = 0 WHERE id = ?1", [id])?;
+            }
+            tx.commit()?;
+            Ok(prev)
+        })
+    }
+
+    /// Resets `used_tokens` of every team whose quota period ended before
+    /// `now`. Returns how many teams rolled over.
+    pub fn roll_over_team_quotas(&self, now: DateTime<Utc>) -> Result<usize> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, quota_period, quota_timezone, quota_period_started_at
+                 FROM teams WHERE quota_period != 'none'",
+            )?;
+            let teams = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut rolled = 0;
+            for (team_id, period, timezone, started_at) in teams {
+                let Some(current_start) =
+                    rollover_start(&period, &timezone, started_at.as_deref(), now)
+                else {
+                    continue;
+                };
+                conn.execute(
+                    "UPDATE teams SET used_tokens = 0, quota_period_started_at = ?1 WHERE id = ?2",
+                    params![current_start, team_id],
+                )?;
+                rolled += 1;
+            }
+            Ok(rolled)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn team_usage_is_pooled_across_members() {
+        let db = Database::new_in_memory().unwrap();
+        let team = db.create_team("research", Some(1000), &UserLimits::default()).unwrap();
+        let (alice, _) = db.create_user("alice", None).unwrap();
+        let (bob, _) = db.create_user("bob", None).unwrap();
+        let (carol, _) = db.create_user("carol", None).unwrap();
+        db.set_user_team(alice.id, Some(team.id)).unwrap();
+        let bob = db.set_user_team(bob.id, Some(team.id)).unwrap().unwrap();
+        assert_eq!(bob.team_id, Some(team.id));
+
+        db.log_usage(alice.id, "openai", "gpt-4o", 300, 100, 10, "success").unwrap();
+        db.log_usage(bob.id, "claude", "claude-sonnet-4", 400, 200, 10, "success").unwrap();
+        db.log_usage(carol.id, "openai", "gpt-4o", 50, 50, 10, "success").unwrap();
+
+        let team = db.get_team(team.id).unwrap().unwrap();
+        assert_eq!(team.used_tokens, 1000);
+        assert_eq!(team.member_count, 2);
+        assert!(team.quota_exhausted());
+
+        assert_eq!(db.reset_team_used_tokens(team.id).unwrap(), Some(1000));
+        assert_eq!(db.reset_team_used_tokens(team.id + 1).unwrap(), None);
+        assert!(!db.get_team(team.id).unwrap().unwrap().quota_exhausted());
+    }
+
+    #[test]
+    fn team_pool_rolls_over_with_its_period() {
+        let db = Database::new_in_memory().unwrap();
+        let daily = db
+            .create_team_with(
+                "research",
+                Some(1000),
+                &UserLimits::default(),
+                Some((QuotaPeriod::Daily, "UTC")),
+            )
+            .unwrap();
+        let lifetime = db.create_team("ops", Some(1000), &UserLimits::default()).unwrap();
+        let (alice, _) = db.create_user("alice", None).unwrap();
+        let (bob, _) = db.create_user("bob", None).unwrap();
+        db.set_user_team(alice.id, Some(daily.id)).unwrap();
+        db.set_user_team(bob.id, Some(lifetime.id)).unwrap();
+        db.log_usage(alice.id, "openai", "gpt-4o", 100, 50, 10, "success").unwrap();
+        db.log_usage(bob.id, "openai", "gpt-4o", 100, 50, 10, "success").unwrap();
+
+        let today = QuotaPeriod::Daily.period_start(Utc::now(), chrono_tz::Tz::UTC).unwrap();
+        assert_eq!(db.roll_over_team_quotas(Utc::now()).unwrap(), 0);
+
+        let tomorrow = today + chrono::Duration::days(1) + chrono::Duration::minutes(1);
+        assert_eq!(db.roll_over_team_quotas(tomorrow).unwrap(), 1);
+        assert_eq!(db.roll_over_team_quotas(tomorrow).unwrap(), 0);
+
+        let daily = db.get_team(daily.id).unwrap().unwrap();
+        assert_eq!(daily.used_tokens, 0);
+        assert_eq!(
+            daily.quota_period_started_at,
+            Some(crate::db::quota::format_db_time(today + chrono::Duration::days(1)))
+        );
+        assert_eq!(db.get_team(lifetime.id).unwrap().unwrap().used_tokens, 150);
+    }
+
+    #[test]
+    fn duplicate_names_conflict_and_delete_unassigns_members() {
+        let db = Database::new_in_memory().unwrap();
+        let team = db.create_team("research", None, &UserLimits::default()).unwrap();
+        let err = db.create_team("research", None, &UserLimits::default()).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+
+        let limits = UserLimits {
+            requests_per_minute: Some(10),
+            ..Default::default()
+        };
+        let updated = db
+            .update_team(team.id, Some("ml"), Some(Some(500)), Some(&limits), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!((updated.name.as_str(), updated.quota_tokens), ("ml", Some(500)));
+        assert_eq!(updated.limits, limits);
+
+        let (alice, _) = db.create_user("alice", None).unwrap();
+        db.set_user_team(alice.id, Some(team.id)).unwrap();
+        assert!(db.delete_team(team.id).unwrap());
+        assert!(db.get_team(team.id).unwrap().is_none());
+        assert_eq!(db.get_user_by_id(alice.id).unwrap().unwrap().team_id, None);
+        assert!(!db.delete_team(team.id).unwrap());
+    }
+}
@@ -0,0 +1,250 @@
+use anyhow::Result;
+use rand::Rng;
+use rusqlite::{params, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use super::Database;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// Wrong codes allowed against one login challenge before it is discarded.
+pub const MAX_CHALLENGE_ATTEMPTS: i64 = 5;
+
+/// An admin's TOTP state. `secret` is encrypted with `crypto::encrypt_tokens`.
+#[derive(Debug, Clone)]
+pub struct AdminTotp {
+    pub secret: Option<String>,
+    pub enabled: bool,
+    pub last_step: Option<i64>,
+}
+
+/// Fresh recovery codes in `xxxxx-xxxxx` form. Only their hashes are stored.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code = hex::encode(rng.gen::<[u8; 5]>());
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// Hashes a recovery code, ignoring case, dashes and spaces as typed.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+fn insert_recovery_codes(conn: &rusqlite::Connection, admin_id: i64, code_hashes: &[String]) -> Result<()> {
+    conn.execute("DELETE FROM admin_recovery_codes WHERE admin_id = ?1", [admin_id])?;
+    let mut stmt =
+        conn.prepare("INSERT INTO admin_recovery_codes (admin_id, code_hash) VALUES (?1, ?2)")?;
+    for hash in code_hashes {
+        stmt.execute(params![admin_id, hash])?;
+    }
+    Ok(())
+}
+
+impl Database {
+    pub fn get_admin_totp(&self, admin_id: i64) -> Result<Option<AdminTotp>> {
+        self.with_conn(|conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT totp_secret, totp_enabled, totp_last_step FROM admins WHERE id = ?1",
+                    [admin_id],
+                    |row| {
+                        Ok(AdminTotp {
+                            secret: row.get(0)?,
+                            enabled: row.get(1)?,
+                            last_step: row.get(2)?,
+                        })
+                    },
+                )
+                .optional()?)
+        })
+    }
+
+    /// Stores a pending secret; it only takes effect once [`Self::enable_admin_totp`]
+    /// confirms a code generated from it.
+    pub fn set_admin_totp_secret(&self, admin_id: i64, encrypted_secret: &str) -> Result<bool> {
+        self.with_conn(|conn| {
+            let rows = conn.execute(
+                "UPDATE admins SET totp_secret = ?1, totp_last_step = NULL
+                 WHERE id = ?2 AND totp_enabled = 0",
+                params![encrypted_secret, admin_id],
+            )?;
+            Ok(rows > 0)
+        })
+    }
+
+    /// Turns the pending secret on, with `step` as the first code used.
+    pub fn enable_admin_totp(&self, admin_id: i64, step: i64, code_hashes: &[String]) -> Result<()> {
+        self.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            tx.execute(
+                "UPDATE admins SET totp_enabled = 1, totp_last_step = ?1 WHERE id = ?2",
+                params![step, admin_id],
+            )?;
+            insert_recovery_codes(&tx, admin_id, code_hashes)?;
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    pub fn disable_admin_totp(&self, admin_id: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            let rows = tx.execute(
+                "UPDATE admins SET totp_secret = NULL, totp_enabled = 0, totp_last_step = NULL
+                 WHERE id = ?1",
+                [admin_id],
+            )?;
+            tx.execute("DELETE FROM admin_recovery_codes WHERE admin_id = ?1", [admin_id])?;
+            tx.execute("DELETE FROM login_challenges WHERE admin_id = ?1", [admin_id])?;
+            tx.commit()?;
+            Ok(rows > 0)
+        })
+    }
+
+    /// Records `step` as used. Returns false if it (or a later step) already
+    /// was, so each code works once.
+    pub fn record_totp_step(&self, admin_id: i64, step: i64) -> Result<bool> {
+        self.with_conn(|conn| {
+            let rows = conn.execute(
+                "UPDATE admins SET totp_last_step = ?1
+                 WHERE id = ?2 AND (totp_last_step IS NULL OR totp_last_step < ?1)",
+                params![step, admin_id],
+            )?;
+            Ok(rows > 0)
+        })
+    }
+
+    pub fn replace_recovery_codes(&self, admin_id: i64, code_hashes: &[String]) -> Result<()> {
+        self.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            insert_recovery_codes(&tx, admin_id, code_hashes)?;
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    /// Marks a matching unused recovery code as used. Returns false if none matched.
+    pub fn use_recovery_code(&self, admin_id: i64, code_hash: &str) -> Result<bool> {
+        self.with_conn(|conn| {
+            let rows = conn.execute(
+                "UPDATE admin_recovery_codes SET used_at = datetime('now')
+                 WHERE id = (SELECT id FROM admin_recovery_codes
+                             WHERE admin_id = ?1 AND code_hash = ?2 AND used_at IS NULL LIMIT 1)",
+                params![admin_id, code_hash],
+            )?;
+            Ok(rows > 0)
+        })
+    }
+
+    pub fn count_unused_recovery_codes(&self, admin_id: i64) -> Result<i64> {
+        self.with_conn(|conn| {
+            Ok(conn.query_row(
+                "SELECT COUNT(*) FROM admin_recovery_codes WHERE admin_id = ?1 AND used_at IS NULL",
+                [admin_id],
+                |row| row.get(0),
+            )?)
+        })
+    }
+
+    /// Starts the second login step for an admin whose password checked out.
+    pub fn create_login_challenge(&self, admin_id: i64, ttl_minutes: i64) -> Result<String> {
+        let token = uuid::Uuid::new_v4().to_string();
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO login_challenges (token, admin_id, expires_at)
+                 VALUES (?1, ?2, datetime('now', ?3 || ' minutes'))",
+                params![token, admin_id, ttl_minutes],
+            )?;
+            Ok(token)
+        })
+    }
+
+    /// The admin an unexpired challenge belongs to, if it has attempts left.
+    pub fn get_login_challenge(&self, token: &str) -> Result<Option<i64>> {
+        self.with_conn(|conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT admin_id FROM login_challenges
+                     WHERE token = ?1 AND datetime(expires_at) > datetime('now') AND attempts < ?2",
+                    params![token, MAX_CHALLENGE_ATTEMPTS],
+                    |row| row.get(0),
+                )
+                .optional()?)
+        })
+    }
+
+    pub fn fail_login_challenge(&self, token: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE login_challenges SET attempts = attempts + 1 WHERE token = ?1",
+                [token],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn delete_login_challenge(&self, token: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM login_challenges WHERE token = ?1", [token])?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::admins::AdminRole;
+
+    #[test]
+    fn totp_steps_and_recovery_codes_are_single_use() {
+        let db = Database::new_in_memory().unwrap();
+        let admin = db.create_admin("root", "hash", AdminRole::Owner).unwrap();
+        assert!(!admin.totp_enabled);
+
+        assert!(db.set_admin_totp_secret(admin.id, "encrypted").unwrap());
+        let codes = generate_recovery_codes();
+        let hashes: Vec<_> = codes.iter().map(|c| hash_recovery_code(c)).collect();
+        db.enable_admin_totp(admin.id, 100, &hashes).unwrap();
+        assert!(db.get_admin(admin.id).unwrap().unwrap().totp_enabled);
+        // Enabled secrets are not replaced by a new setup
+        assert!(!db.set_admin_totp_secret(admin.id, "other").unwrap());
+
+        assert!(!db.record_totp_step(admin.id, 100).unwrap());
+        assert!(db.record_totp_step(admin.id, 101).unwrap());
+
+        let typed = codes[0].to_uppercase().replace('-', " ");
+        assert!(db.use_recovery_code(admin.id, &hash_recovery_code(&typed)).unwrap());
+        assert!(!db.use_recovery_code(admin.id, &hashes[0]).unwrap());
+        assert_eq!(db.count_unused_recovery_codes(admin.id).unwrap(), 9);
+
+        assert!(db.disable_admin_totp(admin.id).unwrap());
+        let totp = db.get_admin_totp(admin.id).unwrap().unwrap();
+        assert!(!totp.enabled && totp.secret.is_none());
+        assert_eq!(db.count_unused_recovery_codes(admin.id).unwrap(), 0);
+    }
+
+    #[test]
+    fn login_challenges_run_out_of_attempts() {
+        let db = Database::new_in_memory().unwrap();
+        let admin = db.create_admin("root", "hash", AdminRole::Owner).unwrap();
+        let token = db.create_login_challenge(admin.id, 5).unwrap();
+        assert_eq!(db.get_login_challenge(&token).unwrap(), Some(admin.id));
+
+        for _ in 0..MAX_CHALLENGE_ATTEMPTS {
+            db.fail_login_challenge(&token).unwrap();
+        }
+        assert_eq!(db.get_login_challenge(&token).unwrap(), None);
+
+        let expired = db.create_login_challenge(admin.id, -1).unwrap();
+        assert_eq!(db.get_login_challenge(&expired).unwrap(), None);
+    }
+}
@@ -10,6 +10,8 @@ pub struct UsageLog {
     pub user_id: i64,
     pub provider: String,
     pub model: String,
+    /// The model the client named, when known; `model` is what served it.
+    pub requested_model: Option<String>,
     pub tokens_input: i64,
     pub tokens_output: i64,
     pub request_time_ms: i64,
@@ -17,6 +19,19 @@ pub struct UsageLog {
     pub timestamp: String,
 }
 
+/// One proxied request to record with [`Database::insert_usage_log`].
+#[derive(Debug, Clone, Default)]
+pub struct NewUsageLog<'a> {
+    pub user_id: i64,
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub requested_model: Option<&'a str>,
+    pub tokens_input: i64,
+    pub tokens_output: i64,
+    pub request_time_ms: i64,
+    pub status: &'a str,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UsageStats {
@@ -34,6 +49,28 @@ pub struct ProviderUsage {
     pub tokens_output: i64,
 }
 
+/// Usage billed to one team; `team_id` is `None` for users without a team.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamUsage {
+    pub team_id: Option<i64>,
+    pub team_name: Option<String>,
+    pub requests: i64,
+    pub tokens_input: i64,
+    pub tokens_output: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyTeamUsage {
+    pub date: String,
+    pub team_id: Option<i64>,
+    pub team_name: Option<String>,
+    pub requests: i64,
+    pub tokens_input: i64,
+    pub tokens_output: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DailyUsage {
@@ -45,31 +82,67 @@ pub struct DailyUsage {
 
 impl Database {
     pub fn log_usage(&self, user_id: i64, provider: &str, model: &str, tokens_input: i64, tokens_output: i64, request_time_ms: i64, status: &str) -> Result<()> {
+        self.insert_usage_log(&NewUsageLog {
+            user_id,
+            provider,
+            model,
+            requested_model: None,
+            tokens_input,
+            tokens_output,
+            request_time_ms,
+            status,
+        })
+    }
+
+    pub fn insert_usage_log(&self, log: &NewUsageLog) -> Result<()> {
+        let tokens = log.tokens_input + log.tokens_output;
         self.with_conn(|conn| {
-            conn.execute(
-                "INSERT INTO usage_logs (user_id, provider, model, tokens_input, tokens_output, request_time_ms, status) VALUES (?, ?, ?, ?, ?, ?, ?)",
-                rusqlite::params![user_id, provider, model, tokens_input, tokens_output, request_time_ms, status],
+            let tx = conn.unchecked_transaction()?;
+            // The request is billed to the team the user belongs to right now
+            tx.execute(
+                "INSERT INTO usage_logs (user_id, provider, model, requested_model, tokens_input, tokens_output, request_time_ms, status, team_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, (SELECT team_id FROM users WHERE id = ?1))",
+                rusqlite::params![log.user_id, log.provider, log.model, log.requested_model, log.tokens_input, log.tokens_output, log.request_time_ms, log.status],
             )?;
             
             // Update user's used_tokens
-            conn.execute(
+            tx.execute(
                 "UPDATE users SET used_tokens = used_tokens + ?, last_used_at = datetime('now') WHERE id = ?",
-                rusqlite::params![tokens_input + tokens_output, user_id],
+                rusqlite::params![tokens, log.user_id],
             )?;
-            
+
+            // And the team's shared pool
+            tx.execute(
+                "UPDATE teams SET used_tokens = used_tokens + ?1 WHERE id = (SELECT team_id FROM users WHERE id = ?2)",
+                rusqlite::params![tokens, log.user_id],
+            )?;
+
+            tx.commit()?;
             Ok(())
         })
     }
 
-    pub fn get_usage_stats(&self, period: &str) -> Result<UsageStats> {
+    /// Tokens the user consumed since `since` (a `datetime('now')`-format UTC time).
+    pub fn get_user_tokens_since(&self, user_id: i64, since: &str) -> Result<i64> {
         self.with_conn(|conn| {
-            let date_filter = Self::period_to_date_filter(period);
+            let tokens = conn.query_row(
+                "SELECT COALESCE(SUM(tokens_input + tokens_output), 0) FROM usage_logs
+                 WHERE user_id = ?1 AND timestamp >= ?2",
+                rusqlite::params![user_id, since],
+                |row| row.get(0),
+            )?;
+            Ok(tokens)
+        })
+    }
+
+    pub fn get_usage_stats(&self, period: &str, team_id: Option<i64>) -> Result<UsageStats> {
+        self.with_conn(|conn| {
+            let where_clause = Self::period_and_team_filter(period, team_id);
             let sql = format!(
                 "SELECT COUNT(*) as total_requests, COALESCE(SUM(tokens_input), 0) as total_tokens_input, COALESCE(SUM(tokens_output), 0) as total_tokens_output FROM usage_logs{}",
-                date_filter
+                where_clause
             );
             let mut stmt = conn.prepare(&sql)?;
-            let stats = stmt.query_row([], |row| {
+            let stats = stmt.query_row(rusqlite::params_from_iter(team_id), |row| {
                 Ok(UsageStats {
                     total_requests: row.get(0)?,
                     total_tokens_input: row.get(1)?,
@@ -104,15 +177,15 @@ impl Database {
         })
     }
 
-    pub fn get_usage_by_provider(&self, period: &str) -> Result<Vec<ProviderUsage>> {
+    pub fn get_usage_by_provider(&self, period: &str, team_id: Option<i64>) -> Result<Vec<ProviderUsage>> {
         self.with_conn(|conn| {
-            let date_filter = Self::period_to_date_filter(period);
+            let where_clause = Self::period_and_team_filter(period, team_id);
             let sql = format!(
                 "SELECT provider, COUNT(*) as requests, COALESCE(SUM(tokens_input), 0) as tokens_input, COALESCE(SUM(tokens_output), 0) as tokens_output FROM usage_logs{} GROUP BY provider ORDER BY requests DESC",
-                date_filter
+                where_clause
             );
             let mut stmt = conn.prepare(&sql)?;
-            let rows = stmt.query_map([], |row| {
+            let rows = stmt.query_map(rusqlite::params_from_iter(team_id), |row| {
                 Ok(ProviderUsage {
                     provider: row.get(0)?,
                     requests: row.get(1)?,
@@ -128,28 +201,33 @@ impl Database {
         })
     }
 
-    pub fn get_daily_usage(&self, days: u32, user_id: Option<i64>, provider: Option<&str>) -> Result<Vec<DailyUsage>> {
+    pub fn get_usage_by_team(&self, period: &str) -> Result<Vec<TeamUsage>> {
         self.with_conn(|conn| {
-            let mut conditions = vec![format!("timestamp >= datetime('now', '-{} days')", days)];
-            let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-            
-            if let Some(uid) = user_id {
-                conditions.push("user_id = ?".to_string());
-                params.push(Box::new(uid));
-            }
-            if let Some(prov) = provider {
-                conditions.push("provider = ?".to_string());
-                params.push(Box::new(prov.to_string()));
-            }
-            
-            let where_clause = if conditions.is_empty() {
-                String::new()
-            } else {
-                format!(" WHERE {}", conditions.join(" AND "))
-            };
+            let date_filter = Self::period_to_date_filter(period);
+            let sql = format!(
+                "SELECT ul.team_id, t.name, COUNT(*) as requests, COALESCE(SUM(ul.tokens_input), 0) as tokens_input, COALESCE(SUM(ul.tokens_output), 0) as tokens_output FROM usage_logs ul LEFT JOIN teams t ON t.id = ul.team_id{} GROUP BY ul.team_id ORDER BY requests DESC",
+                date_filter
+            );
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map([], |row| {
+                Ok(TeamUsage {
+                    team_id: row.get(0)?,
+                    team_name: row.get(1)?,
+                    requests: row.get(2)?,
+                    tokens_input: row.get(3)?,
+                    tokens_output: row.get(4)?,
+                })
+            })?;
+            Ok(rows.collect::<Result<Vec<_>, _>>()?)
+        })
+    }
+
+    pub fn get_daily_usage(&self, days: u32, user_id: Option<i64>, provider: Option<&str>, team_id: Option<i64>) -> Result<Vec<DailyUsage>> {
+        self.with_conn(|conn| {
+            let (where_clause, params) = Self::daily_filter(days, user_id, provider, team_id);
             
             let sql = format!(
-                "SELECT date(timestamp) as date, COUNT(*) as requests, COALESCE(SUM(tokens_input), 0) as tokens_input, COALESCE(SUM(tokens_output), 0) as tokens_output FROM usage_logs{} GROUP BY date(timestamp) ORDER BY date DESC",
+                "SELECT date(timestamp) as date, COUNT(*) as requests, COALESCE(SUM(tokens_input), 0) as tokens_input, COALESCE(SUM(tokens_output), 0) as tokens_output FROM usage_logs ul{} GROUP BY date(timestamp) ORDER BY date DESC",
                 where_clause
             );
             
@@ -171,6 +249,54 @@ impl Database {
         })
     }
 
+    /// Per-day usage split by the team each request was billed to.
+    pub fn get_daily_usage_by_team(&self, days: u32, user_id: Option<i64>, provider: Option<&str>, team_id: Option<i64>) -> Result<Vec<DailyTeamUsage>> {
+        self.with_conn(|conn| {
+            let (where_clause, params) = Self::daily_filter(days, user_id, provider, team_id);
+
+            let sql = format!(
+                "SELECT date(ul.timestamp) as date, ul.team_id, t.name, COUNT(*) as requests, COALESCE(SUM(ul.tokens_input), 0) as tokens_input, COALESCE(SUM(ul.tokens_output), 0) as tokens_output FROM usage_logs ul LEFT JOIN teams t ON t.id = ul.team_id{} GROUP BY date(ul.timestamp), ul.team_id ORDER BY date DESC, requests DESC",
+                where_clause
+            );
+
+            let mut stmt = conn.prepare(&sql)?;
+            let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+            let rows = stmt.query_map(param_refs.as_slice(), |row| {
+                Ok(DailyTeamUsage {
+                    date: row.get(0)?,
+                    team_id: row.get(1)?,
+                    team_name: row.get(2)?,
+                    requests: row.get(3)?,
+                    tokens_input: row.get(4)?,
+                    tokens_output: row.get(5)?,
+                })
+            })?;
+            Ok(rows.collect::<Result<Vec<_>, _>>()?)
+        })
+    }
+
+    /// WHERE clause shared by the daily usage queries; columns are qualified
+    /// with `ul` so it also works when `teams` is joined in.
+    fn daily_filter(days: u32, user_id: Option<i64>, provider: Option<&str>, team_id: Option<i64>) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
+        let mut conditions = vec![format!("ul.timestamp >= datetime('now', '-{} days')", days)];
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(uid) = user_id {
+            conditions.push("ul.user_id = ?".to_string());
+            params.push(Box::new(uid));
+        }
+        if let Some(prov) = provider {
+            conditions.push("ul.provider = ?".to_string());
+            params.push(Box::new(prov.to_string()));
+        }
+        if let Some(tid) = team_id {
+            conditions.push("ul.team_id = ?".to_string());
+            params.push(Box::new(tid));
+        }
+
+        (format!(" WHERE {}", conditions.join(" AND ")), params)
+    }
+
     pub fn get_usage_logs_paginated(&self, limit: u32, offset: u32, user_id: Option<i64>, provider: Option<&str>) -> Result<(Vec<UsageLog>, u64)> {
         self.with_conn(|conn| {
             let mut conditions: Vec<String> = Vec::new();
@@ -199,7 +325,7 @@ impl Database {
             
             // Get paginated results
             let sql = format!(
-                "SELECT id, user_id, provider, model, tokens_input, tokens_output, request_time_ms, COALESCE(status, 'success') as status, timestamp FROM usage_logs{} ORDER BY timestamp DESC LIMIT ? OFFSET ?",
+                "SELECT id, user_id, provider, model, tokens_input, tokens_output, request_time_ms, COALESCE(status, 'success') as status, timestamp, requested_model FROM usage_logs{} ORDER BY timestamp DESC LIMIT ? OFFSET ?",
                 where_clause
             );
             
@@ -220,6 +346,7 @@ impl Database {
                     request_time_ms: row.get(6)?,
                     status: row.get(7)?,
                     timestamp: row.get(8)?,
+                    requested_model: row.get(9)?,
                 })
             })?;
             let mut results = Vec::new();
@@ -268,7 +395,8 @@ impl Database {
 
             let query_sql = format!(
                 "SELECT ul.id, ul.timestamp, ul.user_id, u.name, ul.provider, ul.model, 
-                        ul.tokens_input, ul.tokens_output, ul.request_time_ms, COALESCE(ul.status, 'success') as status
+                        ul.tokens_input, ul.tokens_output, ul.request_time_ms, COALESCE(ul.status, 'success') as status,
+                        ul.requested_model
                  FROM usage_logs ul
                  LEFT JOIN users u ON ul.user_id = u.id
                  {}
@@ -298,6 +426,7 @@ impl Database {
                     tokens_output: row.get(7)?,
                     duration_ms: row.get(8)?,
                     status: row.get(9)?,
+                    requested_model: row.get(10)?,
                 })
             })?;
             let mut results = Vec::new();
@@ -317,6 +446,17 @@ impl Database {
         }
     }
 
+    /// `period_to_date_filter`, narrowed to one team when `team_id` is set;
+    /// bind `team_id` as the only parameter.
+    fn period_and_team_filter(period: &str, team_id: Option<i64>) -> String {
+        let date_filter = Self::period_to_date_filter(period);
+        match team_id {
+            None => date_filter,
+            Some(_) if date_filter.is_empty() => " WHERE team_id = ?".to_string(),
+            Some(_) => format!("{} AND team_id = ?", date_filter),
+        }
+    }
+
     pub fn get_total_requests(&self) -> Result<i64> {
         self.with_conn(|conn| {
             let count: i64 = conn.query_row(
@@ -1,12 +1,11 @@
 use anyhow::{anyhow, Result};
-use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
-    Argon2,
-};
-use rand::Rng;
 use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
+use super::api_keys::insert_api_key;
+use super::quota::{current_period_start, QuotaPeriod};
+use super::scopes::{parse_scopes, scopes_to_column, Scopes};
 use super::Database;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,30 +19,63 @@ pub struct User {
     pub enabled: bool,
     pub created_at: String,
     pub last_used_at: Option<String>,
+    #[serde(flatten)]
+    pub limits: UserLimits,
+    pub quota_period: QuotaPeriod,
+    /// IANA timezone that period boundaries are computed in.
+    pub quota_timezone: String,
+    pub quota_period_started_at: Option<String>,
+    /// Endpoint, provider and model restrictions applied to all the user's keys.
+    pub scopes: Scopes,
+    pub team_id: Option<i64>,
+    /// Model names (`*` wildcards) rewritten for this user before the
+    /// server-wide `model_mappings`.
+    pub model_aliases: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone)]
-pub struct UserWithHash {
-    pub user: User,
-    pub api_key_hash: String,
+/// Per-user overrides of the server-wide rate limits. `None` falls back to
+/// the global default; `Some(0)` means unlimited for that user.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserLimits {
+    pub requests_per_minute: Option<i64>,
+    pub tokens_per_minute: Option<i64>,
+    pub max_concurrent_requests: Option<i64>,
 }
 
-fn generate_api_key(name: &str) -> (String, String, String) {
-    let random_bytes: [u8; 16] = rand::thread_rng().gen();
-    let random_hex = hex::encode(random_bytes);
-    let prefix = format!("sk-{}", name);
-    let full_key = format!("{}-{}", prefix, random_hex);
-
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let hash = argon2
-        .hash_password(full_key.as_bytes(), &salt)
-        .expect("Failed to hash API key")
-        .to_string();
+impl UserLimits {
+    /// Fills each limit this leaves unset from `defaults`.
+    pub fn or(&self, defaults: &UserLimits) -> UserLimits {
+        UserLimits {
+            requests_per_minute: self.requests_per_minute.or(defaults.requests_per_minute),
+            tokens_per_minute: self.tokens_per_minute.or(defaults.tokens_per_minute),
+            max_concurrent_requests: self
+                .max_concurrent_requests
+                .or(defaults.max_concurrent_requests),
+        }
+    }
+}
 
-    (full_key, prefix, hash)
+/// Fields written together, in one transaction, by `create_user_with` and
+/// `apply_user_changes`. `None` leaves a field as it is.
+#[derive(Debug, Default)]
+pub struct UserChanges<'a> {
+    pub name: Option<&'a str>,
+    pub quota_tokens: Option<Option<i64>>,
+    pub enabled: Option<bool>,
+    pub limits: Option<UserLimits>,
+    /// The new period and the IANA timezone its boundaries are computed in.
+    pub quota_period: Option<(QuotaPeriod, &'a str)>,
+    pub scopes: Option<&'a Scopes>,
+    pub team_id: Option<Option<i64>>,
+    /// An empty map removes the user's aliases.
+    pub model_aliases: Option<&'a HashMap<String, String>>,
 }
 
+const USER_COLUMNS: &str = "id, name, api_key_prefix, quota_tokens, used_tokens, enabled, created_at, \
+     last_used_at, requests_per_minute, tokens_per_minute, max_concurrent_requests, \
+     quota_period, quota_timezone, quota_period_started_at, scopes, team_id, model_aliases";
+
 fn row_to_user(row: &rusqlite::Row) -> rusqlite::Result<User> {
     Ok(User {
         id: row.get(0)?,
@@ -54,15 +86,94 @@ fn row_to_user(row: &rusqlite::Row) -> rusqlite::Result<User> {
         enabled: row.get::<_, i32>(5)? != 0,
         created_at: row.get(6)?,
         last_used_at: row.get(7)?,
+        limits: UserLimits {
+            requests_per_minute: row.get(8)?,
+            tokens_per_minute: row.get(9)?,
+            max_concurrent_requests: row.get(10)?,
+        },
+        quota_period: QuotaPeriod::parse(&row.get::<_, String>(11)?).unwrap_or_default(),
+        quota_timezone: row.get(12)?,
+        quota_period_started_at: row.get(13)?,
+        scopes: parse_scopes(row.get(14)?),
+        team_id: row.get(15)?,
+        model_aliases: row
+            .get::<_, Option<String>>(16)?
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
     })
 }
 
+fn select_user(conn: &rusqlite::Connection, id: i64) -> Result<Option<User>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM users WHERE id = ?1", USER_COLUMNS))?;
+    Ok(stmt.query_row([id], |row| row_to_user(row)).optional()?)
+}
+
+fn write_user_changes(
+    conn: &rusqlite::Connection,
+    id: i64,
+    changes: &UserChanges,
+) -> Result<Option<User>> {
+    let mut updates = Vec::new();
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if let Some(n) = changes.name {
+        updates.push("name = ?");
+        params.push(Box::new(n.to_string()));
+    }
+    if let Some(qt) = changes.quota_tokens {
+        updates.push("quota_tokens = ?");
+        params.push(Box::new(qt));
+    }
+    if let Some(e) = changes.enabled {
+        updates.push("enabled = ?");
+        params.push(Box::new(e as i32));
+    }
+    if let Some(limits) = &changes.limits {
+        updates.push("requests_per_minute = ?");
+        params.push(Box::new(limits.requests_per_minute));
+        updates.push("tokens_per_minute = ?");
+        params.push(Box::new(limits.tokens_per_minute));
+        updates.push("max_concurrent_requests = ?");
+        params.push(Box::new(limits.max_concurrent_requests));
+    }
+    if let Some((period, timezone)) = changes.quota_period {
+        updates.push("quota_period = ?");
+        params.push(Box::new(period.as_str()));
+        updates.push("quota_timezone = ?");
+        params.push(Box::new(timezone.to_string()));
+        updates.push("quota_period_started_at = ?");
+        params.push(Box::new(current_period_start(period, timezone)?));
+    }
+    if let Some(scopes) = changes.scopes {
+        updates.push("scopes = ?");
+        params.push(Box::new(scopes_to_column(scopes)?));
+    }
+    if let Some(team_id) = changes.team_id {
+        updates.push("team_id = ?");
+        params.push(Box::new(team_id));
+    }
+    if let Some(aliases) = changes.model_aliases {
+        updates.push("model_aliases = ?");
+        params.push(Box::new(
+            (!aliases.is_empty())
+                .then(|| serde_json::to_string(aliases))
+                .transpose()?,
+        ));
+    }
+
+    if !updates.is_empty() {
+        params.push(Box::new(id));
+        let sql = format!("UPDATE users SET {} WHERE id = ?", updates.join(", "));
+        let params_ref: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        conn.execute(&sql, params_ref.as_slice())?;
+    }
+    select_user(conn, id)
+}
+
 impl Database {
     pub fn list_users(&self) -> Result<Vec<User>> {
         self.with_conn(|conn| {
-            let mut stmt = conn.prepare(
-                "SELECT id, name, api_key_prefix, quota_tokens, used_tokens, enabled, created_at, last_used_at FROM users",
-            )?;
+            let mut stmt = conn.prepare(&format!("SELECT {} FROM users", USER_COLUMNS))?;
             let users = stmt
                 .query_map([], |row| row_to_user(row))?
                 .collect::<Result<Vec<_>, _>>()?;
@@ -71,22 +182,36 @@ impl Database {
     }
 
     pub fn create_user(&self, name: &str, quota_tokens: Option<i64>) -> Result<(User, String)> {
-        let (full_key, prefix, hash) = generate_api_key(name);
+        self.create_user_with(name, quota_tokens, &UserChanges::default())
+    }
 
+    /// Creates the user and applies `changes` in the same transaction, so a
+    /// failure part way leaves no half-configured user behind.
+    pub fn create_user_with(
+        &self,
+        name: &str,
+        quota_tokens: Option<i64>,
+        changes: &UserChanges,
+    ) -> Result<(User, String)> {
         self.with_conn(|conn| {
-            let result = conn.execute(
+            let tx = conn.unchecked_transaction()?;
+            let result = tx.execute(
                 "INSERT INTO users (name, api_key_prefix, api_key_hash, quota_tokens, used_tokens, enabled, created_at)
-                 VALUES (?1, ?2, ?3, ?4, 0, 1, datetime('now'))",
-                rusqlite::params![name, prefix, hash, quota_tokens],
+                 VALUES (?1, '', '', ?2, 0, 1, datetime('now'))",
+                rusqlite::params![name, quota_tokens],
             );
 
             match result {
                 Ok(_) => {
-                    let id = conn.last_insert_rowid();
-                    let mut stmt = conn.prepare(
-                        "SELECT id, name, api_key_prefix, quota_tokens, used_tokens, enabled, created_at, last_used_at FROM users WHERE id = ?1",
+                    let id = tx.last_insert_rowid();
+                    let (key, full_key) = insert_api_key(&tx, id, "default", None)?;
+                    tx.execute(
+                        "UPDATE users SET api_key_prefix = ?1 WHERE id = ?2",
+                        rusqlite::params![key.key_prefix, id],
                     )?;
-                    let user = stmt.query_row([id], |row| row_to_user(row))?;
+                    let user = write_user_changes(&tx, id, changes)?
+                        .ok_or_else(|| anyhow!("User {} vanished during creation", id))?;
+                    tx.commit()?;
                     Ok((user, full_key))
                 }
                 Err(rusqlite::Error::SqliteFailure(err, _))
@@ -101,41 +226,15 @@ impl Database {
 
     pub fn get_user_by_id(&self, id: i64) -> Result<Option<User>> {
         self.with_conn(|conn| {
-            let mut stmt = conn.prepare(
-                "SELECT id, name, api_key_prefix, quota_tokens, used_tokens, enabled, created_at, last_used_at FROM users WHERE id = ?1",
-            )?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM users WHERE id = ?1",
+                USER_COLUMNS
+            ))?;
             let user = stmt.query_row([id], |row| row_to_user(row)).optional()?;
             Ok(user)
         })
     }
 
-    pub fn get_user_by_api_key_prefix(&self, prefix: &str) -> Result<Option<UserWithHash>> {
-        self.with_conn(|conn| {
-            let mut stmt = conn.prepare(
-                "SELECT id, name, api_key_prefix, quota_tokens, used_tokens, enabled, created_at, last_used_at, api_key_hash 
-                 FROM users WHERE api_key_prefix = ?1",
-            )?;
-            let result = stmt
-                .query_row([prefix], |row| {
-                    Ok(UserWithHash {
-                        user: User {
-                            id: row.get(0)?,
-                            name: row.get(1)?,
-                            api_key_prefix: row.get(2)?,
-                            quota_tokens: row.get(3)?,
-                            used_tokens: row.get(4)?,
-                            enabled: row.get::<_, i32>(5)? != 0,
-                            created_at: row.get(6)?,
-                            last_used_at: row.get(7)?,
-                        },
-                        api_key_hash: row.get(8)?,
-                    })
-                })
-                .optional()?;
-            Ok(result)
-        })
-    }
-
     pub fn update_user(
         &self,
         id: i64,
@@ -143,79 +242,98 @@ impl Database {
         quota_tokens: Option<Option<i64>>,
         enabled: Option<bool>,
     ) -> Result<Option<User>> {
-        self.with_conn(|conn| {
-            let mut updates = Vec::new();
-            let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-            if let Some(n) = name {
-                updates.push("name = ?");
-                params.push(Box::new(n.to_string()));
-            }
-            if let Some(qt) = quota_tokens {
-                updates.push("quota_tokens = ?");
-                params.push(Box::new(qt));
-            }
-            if let Some(e) = enabled {
-                updates.push("enabled = ?");
-                params.push(Box::new(e as i32));
-            }
-
-            if updates.is_empty() {
-                return self.get_user_by_id(id);
-            }
-
-            params.push(Box::new(id));
-            let sql = format!(
-                "UPDATE users SET {} WHERE id = ?",
-                updates.join(", ")
-            );
-
-            let params_ref: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-            let rows_affected = conn.execute(&sql, params_ref.as_slice())?;
-
-            if rows_affected == 0 {
-                return Ok(None);
-            }
+        self.apply_user_changes(
+            id,
+            &UserChanges {
+                name,
+                quota_tokens,
+                enabled,
+                ..Default::default()
+            },
+        )
+    }
 
-            let mut stmt = conn.prepare(
-                "SELECT id, name, api_key_prefix, quota_tokens, used_tokens, enabled, created_at, last_used_at FROM users WHERE id = ?1",
-            )?;
-            let user = stmt.query_row([id], |row| row_to_user(row)).optional()?;
+    /// Applies all of `changes` in one transaction. `None` if the user
+    /// doesn't exist.
+    pub fn apply_user_changes(&self, id: i64, changes: &UserChanges) -> Result<Option<User>> {
+        self.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            let user = write_user_changes(&tx, id, changes)?;
+            tx.commit()?;
             Ok(user)
         })
     }
 
     pub fn delete_user(&self, id: i64) -> Result<bool> {
         self.with_conn(|conn| {
-            let rows_affected = conn.execute("DELETE FROM users WHERE id = ?1", [id])?;
+            let tx = conn.unchecked_transaction()?;
+            tx.execute("DELETE FROM api_keys WHERE user_id = ?1", [id])?;
+            tx.execute("DELETE FROM quota_periods WHERE user_id = ?1", [id])?;
+            let rows_affected = tx.execute("DELETE FROM users WHERE id = ?1", [id])?;
+            tx.commit()?;
             Ok(rows_affected > 0)
         })
     }
 
+    /// Revokes all of the user's keys and issues a fresh `default` key.
+    /// Use `create_api_key` instead to rotate without downtime.
     pub fn regenerate_api_key(&self, id: i64) -> Result<Option<(User, String)>> {
         self.with_conn(|conn| {
-            let mut stmt = conn.prepare("SELECT name FROM users WHERE id = ?1")?;
-            let name: Option<String> = stmt.query_row([id], |row| row.get(0)).optional()?;
-
-            let Some(user_name) = name else {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM users WHERE id = ?1)",
+                [id],
+                |row| row.get(0),
+            )?;
+            if !exists {
                 return Ok(None);
-            };
-
-            let (full_key, prefix, hash) = generate_api_key(&user_name);
+            }
 
-            conn.execute(
-                "UPDATE users SET api_key_prefix = ?1, api_key_hash = ?2 WHERE id = ?3",
-                rusqlite::params![prefix, hash, id],
+            let tx = conn.unchecked_transaction()?;
+            tx.execute(
+                "UPDATE api_keys SET revoked_at = datetime('now') WHERE user_id = ?1 AND revoked_at IS NULL",
+                [id],
             )?;
-
-            let mut stmt = conn.prepare(
-                "SELECT id, name, api_key_prefix, quota_tokens, used_tokens, enabled, created_at, last_used_at FROM users WHERE id = ?1",
+            let (key, full_key) = insert_api_key(&tx, id, "default", None)?;
+            tx.execute(
+                "UPDATE users SET api_key_prefix = ?1 WHERE id = ?2",
+                rusqlite::params![key.key_prefix, id],
             )?;
+            tx.commit()?;
+
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM users WHERE id = ?1",
+                USER_COLUMNS
+            ))?;
             let user = stmt.query_row([id], |row| row_to_user(row))?;
             Ok(Some((user, full_key)))
         })
     }
 
+    /// Replaces the user's model aliases; an empty map removes them.
+    pub fn set_user_model_aliases(
+        &self,
+        id: i64,
+        aliases: &HashMap<String, String>,
+    ) -> Result<Option<User>> {
+        self.apply_user_changes(
+            id,
+            &UserChanges {
+                model_aliases: Some(aliases),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn set_user_limits(&self, id: i64, limits: &UserLimits) -> Result<Option<User>> {
+        self.apply_user_changes(
+            id,
+            &UserChanges {
+                limits: Some(limits.clone()),
+                ..Default::default()
+            },
+        )
+    }
+
     pub fn reset_used_tokens(&self, id: i64) -> Result<Option<i64>> {
         self.with_conn(|conn| {
             let mut stmt = conn.prepare("SELECT used_tokens FROM users WHERE id = ?1")?;
@@ -230,15 +348,28 @@ impl Database {
         })
     }
 
+    pub fn list_team_members(&self, team_id: i64) -> Result<Vec<User>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM users WHERE team_id = ?1 ORDER BY id",
+                USER_COLUMNS
+            ))?;
+            let users = stmt
+                .query_map([team_id], row_to_user)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(users)
+        })
+    }
+
     pub fn list_users_paginated(&self, page: u32, limit: u32) -> Result<(Vec<User>, u64)> {
         self.with_conn(|conn| {
             let total: u64 = conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
 
             let offset = (page.saturating_sub(1)) * limit;
-            let mut stmt = conn.prepare(
-                "SELECT id, name, api_key_prefix, quota_tokens, used_tokens, enabled, created_at, last_used_at 
-                 FROM users ORDER BY id LIMIT ?1 OFFSET ?2",
-            )?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM users ORDER BY id LIMIT ?1 OFFSET ?2",
+                USER_COLUMNS
+            ))?;
             let users = stmt
                 .query_map(rusqlite::params![limit, offset], |row| row_to_user(row))?
                 .collect::<Result<Vec<_>, _>>()?;
@@ -247,3 +378,27 @@ impl Database {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_changes_leave_the_user_untouched() {
+        let db = Database::new_in_memory().unwrap();
+        let bad_period = UserChanges {
+            name: Some("renamed"),
+            quota_period: Some((QuotaPeriod::Daily, "Mars/Olympus_Mons")),
+            ..Default::default()
+        };
+
+        assert!(db.create_user_with("alice", None, &bad_period).is_err());
+        assert!(db.list_users().unwrap().is_empty());
+
+        let (user, _) = db.create_user("alice", None).unwrap();
+        assert!(db.apply_user_changes(user.id, &bad_period).is_err());
+        let unchanged = db.get_user_by_id(user.id).unwrap().unwrap();
+        assert_eq!(unchanged.name, "alice");
+        assert_eq!(unchanged.quota_period, QuotaPeriod::None);
+    }
+}
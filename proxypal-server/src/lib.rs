@@ -4,16 +4,25 @@ pub mod cliproxy;
 pub mod crypto;
 pub mod db;
 pub mod middleware;
+pub mod oidc;
 pub mod routes;
+pub mod tasks;
 
-use cliproxy::{ProxyManagementClient, ProxyProcessManager};
+use cliproxy::{ProxyManagementClient, ProxyProbe, ProxyProcessManager};
 use db::Database;
+use middleware::key_cache::ApiKeyCache;
+use middleware::login_guard::LoginGuard;
 use middleware::rate_limit::RateLimiter;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Database,
     pub rate_limiter: Arc<RateLimiter>,
+    pub key_cache: Arc<ApiKeyCache>,
+    pub login_guard: Arc<LoginGuard>,
     pub proxy_client: Arc<dyn ProxyManagementClient>,
     pub proxy_manager: Arc<dyn ProxyProcessManager>,
+    pub proxy_probe: Arc<ProxyProbe>,
+    /// Set when OIDC single sign-on is configured.
+    pub oidc: Option<Arc<oidc::OidcClient>>,
 }
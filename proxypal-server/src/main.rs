@@ -7,24 +7,33 @@ use serde::Serialize;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::services::ServeDir;
-use tracing::info;
+use tracing::{info, warn};
 
 mod cliproxy;
 mod crypto;
 mod db;
 mod middleware;
+mod oidc;
 mod routes;
+mod tasks;
 
-use cliproxy::{ProxyManagementClient, ProxyProcessManager};
+use cliproxy::{ProxyManagementClient, ProxyProbe, ProxyProcessManager};
 use db::Database;
-use middleware::rate_limit::RateLimiter;
+use middleware::key_cache::{ApiKeyCache, KeyCacheStats};
+use middleware::login_guard::LoginGuard;
+use middleware::rate_limit::{rate_limited, RateLimiter};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Database,
     pub rate_limiter: Arc<RateLimiter>,
+    pub key_cache: Arc<ApiKeyCache>,
+    pub login_guard: Arc<LoginGuard>,
     pub proxy_client: Arc<dyn ProxyManagementClient>,
     pub proxy_manager: Arc<dyn ProxyProcessManager>,
+    pub proxy_probe: Arc<ProxyProbe>,
+    /// Set when OIDC single sign-on is configured.
+    pub oidc: Option<Arc<oidc::OidcClient>>,
 }
 
 #[derive(Serialize)]
@@ -35,6 +44,11 @@ struct HealthResponse {
     proxy_pid: Option<u32>,
     uptime_seconds: Option<u64>,
     database_connected: bool,
+    proxy_client: String,
+    proxy_reachable: bool,
+    proxy_probe_error: Option<String>,
+    proxy_probed_at: String,
+    api_key_cache: KeyCacheStats,
 }
 
 async fn health_check(
@@ -60,6 +74,11 @@ async fn health_check(
         proxy_pid,
         uptime_seconds,
         database_connected,
+        proxy_client: state.proxy_probe.backend.clone(),
+        proxy_reachable: state.proxy_probe.reachable,
+        proxy_probe_error: state.proxy_probe.error.clone(),
+        proxy_probed_at: state.proxy_probe.checked_at.clone(),
+        api_key_cache: state.key_cache.stats(),
     })
 }
 
@@ -81,37 +100,65 @@ async fn main() -> anyhow::Result<()> {
     info!("Database initialized");
 
     // Bootstrap admin password if not set
-    bootstrap_admin_password(&db)?;
+    bootstrap_admin(&db)?;
+
+    // Rate limit follows the saved server config; config updates adjust it live
+    let rate_limits = cliproxy::load_server_config(&db)
+        .map(|config| config.rate_limits)
+        .unwrap_or_default();
+
+    let rate_limiter = Arc::new(RateLimiter::new(rate_limits.requests_per_minute));
+    rate_limiter.set_concurrency_limit(rate_limits.max_concurrent_requests);
+    rate_limiter.set_token_limit(rate_limits.tokens_per_minute);
+    info!(
+        "Rate limiter configured: {} requests per minute, {} tokens per minute, {} concurrent requests",
+        rate_limits.requests_per_minute,
+        rate_limits.tokens_per_minute,
+        rate_limits.max_concurrent_requests
+    );
+
+    let proxy_backend = cliproxy::ProxyBackend::from_env()?;
+    let proxy_client = proxy_backend.build_client()?;
+    let proxy_manager: Arc<dyn ProxyProcessManager> = Arc::new(cliproxy::LocalProxyProcessManager::from_env());
 
-    // Get rate limit from settings or use default
-    let rate_limit_rpm: u64 = db
-        .get_setting("rate_limit_rpm")
-        .ok()
-        .flatten()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(60);
-    
-    let rate_limiter = Arc::new(RateLimiter::new(rate_limit_rpm));
-    info!("Rate limiter configured: {} requests per minute", rate_limit_rpm);
+    let proxy_probe = ProxyProbe::run(proxy_backend, proxy_client.as_ref()).await;
+    match &proxy_probe.error {
+        None => info!("Proxy client ({}) reached CLIProxyAPI", proxy_probe.backend),
+        Some(e) => warn!("Proxy client ({}) could not reach CLIProxyAPI: {}", proxy_probe.backend, e),
+    }
+    let proxy_probe = Arc::new(proxy_probe);
 
-    let proxy_client: Arc<dyn ProxyManagementClient> = Arc::new(cliproxy::MockProxyManagementClient::default());
-    let proxy_manager: Arc<dyn ProxyProcessManager> = Arc::new(cliproxy::LocalProxyProcessManager::from_env());
+    tasks::spawn_quota_rollover(db.clone());
+    tasks::spawn_session_cleanup(db.clone(), middleware::admin_auth::session_idle_timeout());
+
+    let key_cache = Arc::new(ApiKeyCache::from_env());
+    let login_guard = Arc::new(LoginGuard::from_env());
 
-    let app_state = AppState { db, rate_limiter, proxy_client, proxy_manager };
+    let oidc = oidc::OidcConfig::from_env()?.map(|config| {
+        info!("OIDC single sign-on enabled for issuer {}", config.issuer_url);
+        Arc::new(oidc::OidcClient::new(config))
+    });
+
+    let app_state = AppState { db, rate_limiter, key_cache, login_guard, proxy_client, proxy_manager, proxy_probe, oidc };
 
     // Build admin API routes (require session auth)
     let admin_api = Router::new()
         .nest("/auth", routes::auth::router())
+        .nest("/me", routes::me::router())
+        .nest("/admins", routes::admins::router())
         .nest("/users", routes::users::router())
+        .nest("/teams", routes::teams::router())
         .nest("/usage", routes::usage::router())
         .nest("/providers", routes::providers::router())
         .nest("/proxy", routes::proxy::router())
         .nest("/config", routes::config::router())
-        .nest("/logs", routes::logs::router());
+        .nest("/logs", routes::logs::router())
+        .nest("/audit", routes::audit::router())
+        .layer(axum::middleware::from_fn(middleware::csrf::csrf_protection));
 
-    // Build v1 proxy routes with API key auth (no rate limiting middleware here - 
-    // rate limiting is handled by checking user quota in ApiKeyAuth extractor)
-    let v1_proxy_routes = routes::v1_proxy::router();
+    // Build v1 proxy routes with API key auth and per-user rate limiting
+    let v1_proxy_routes = rate_limited(routes::v1_proxy::router(), &app_state);
+    let gemini_routes = rate_limited(routes::v1_proxy::gemini_router(), &app_state);
 
     // Build router
     let app = Router::new()
@@ -119,7 +166,8 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/health", get(health_check))
         .nest("/api", admin_api)
         .nest("/oauth", routes::providers::oauth_callback_router())
-        .nest("/v1", v1_proxy_routes)
+        .nest("/v1", v1_proxy_routes.merge(routes::me::v1_router()))
+        .nest("/v1beta", gemini_routes)
         .fallback_service(ServeDir::new("dist").append_index_html_on_directories(true))
         .with_state(app_state);
 
@@ -133,33 +181,26 @@ async fn main() -> anyhow::Result<()> {
     info!("Listening on http://{}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
 
-fn bootstrap_admin_password(db: &Database) -> anyhow::Result<()> {
-    use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
-    use rand::rngs::OsRng;
-
-    // Check if admin password is already set
-    if db.get_setting("admin_password_hash")?.is_some() {
-        info!("Admin password already configured");
+fn bootstrap_admin(db: &Database) -> anyhow::Result<()> {
+    if db.count_admins()? > 0 {
+        info!("Admin accounts already configured");
         return Ok(());
     }
 
-    // First run: hash password from env var
+    // First run: create the owner account from env vars
     let password = std::env::var("ADMIN_PASSWORD")
         .map_err(|_| anyhow::anyhow!("ADMIN_PASSWORD env var required on first run"))?;
+    let username = std::env::var("ADMIN_USERNAME")
+        .unwrap_or_else(|_| routes::auth::DEFAULT_ADMIN_USERNAME.to_string());
 
-    let salt = SaltString::generate(&mut OsRng);
-    let hash = Argon2::default()
-        .hash_password(password.as_bytes(), &salt)
-        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?
-        .to_string();
-
-    db.set_setting("admin_password_hash", &hash)?;
-    info!("Admin password initialized from ADMIN_PASSWORD env var");
+    let hash = db::admins::hash_password(&password)?;
+    db.create_admin(&username, &hash, db::admins::AdminRole::Owner)?;
+    info!("Owner account '{}' created from ADMIN_PASSWORD env var", username);
 
     Ok(())
 }
@@ -168,7 +209,7 @@ fn bootstrap_admin_password(db: &Database) -> anyhow::Result<()> {
 mod tests {
     use super::*;
     use axum::{body::Body, http::Request};
-    use cliproxy::{MockProxyManagementClient, MockProxyProcessManager};
+    use cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend};
     use tower::ServiceExt;
 
     fn create_test_app(proxy_running: bool) -> (Router, Arc<MockProxyProcessManager>) {
@@ -185,8 +226,12 @@ mod tests {
         let state = AppState {
             db,
             rate_limiter,
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(LoginGuard::default()),
             proxy_client,
             proxy_manager,
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         };
 
         let app = Router::new()
@@ -255,4 +300,26 @@ mod tests {
 
         assert_eq!(health["version"], env!("CARGO_PKG_VERSION"));
     }
+
+    #[tokio::test]
+    async fn test_health_check_reports_startup_probe() {
+        let (app, _) = create_test_app(true);
+
+        let response = app
+            .oneshot(Request::builder().uri("/healthz").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let health: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(health["proxy_client"], "mock");
+        assert_eq!(health["proxy_reachable"], false);
+        assert_eq!(health["proxy_probe_error"], "Not probed");
+        assert!(health["proxy_probed_at"].as_str().is_some());
+        assert_eq!(health["api_key_cache"]["hits"], 0);
+        assert!(health["api_key_cache"]["hit_rate"].is_number());
+    }
 }
@@ -1,22 +1,123 @@
 use axum::{
     async_trait,
     extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use axum::extract::FromRef;
 use axum_extra::extract::cookie::CookieJar;
+use chrono::{DateTime, Utc};
 use serde::Serialize;
+use std::ops::Deref;
+use std::sync::OnceLock;
 
-use crate::{db::sessions::Session, AppState};
+use crate::{
+    db::admin_tokens::{is_admin_token, AdminToken},
+    db::admins::{Admin, AdminRole},
+    db::quota::parse_db_time,
+    db::sessions::Session,
+    db::Database,
+    AppState,
+};
+
+const DEFAULT_IDLE_TIMEOUT_MINUTES: i64 = 120;
+
+/// How long a session may go unused before it ends, on top of its fixed
+/// lifetime. `SESSION_IDLE_TIMEOUT_MINUTES`, default 120; 0 turns it off.
+pub fn session_idle_timeout() -> Option<chrono::Duration> {
+    static TIMEOUT: OnceLock<Option<chrono::Duration>> = OnceLock::new();
+    *TIMEOUT.get_or_init(|| {
+        let minutes = std::env::var("SESSION_IDLE_TIMEOUT_MINUTES")
+            .ok()
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .unwrap_or(DEFAULT_IDLE_TIMEOUT_MINUTES);
+        (minutes > 0).then(|| chrono::Duration::minutes(minutes))
+    })
+}
+
+fn is_idle(last_accessed: &str, timeout: chrono::Duration, now: DateTime<Utc>) -> bool {
+    parse_db_time(last_accessed).is_some_and(|at| now - at >= timeout)
+}
+
+/// Whether a session's `last_accessed` is past the idle timeout.
+pub fn session_is_idle(last_accessed: &str) -> bool {
+    session_idle_timeout().is_some_and(|timeout| is_idle(last_accessed, timeout, Utc::now()))
+}
+
+/// The session and its admin, unless it expired or sat idle too long. Idle
+/// sessions are deleted on sight.
+pub fn load_session(db: &Database, session_id: &str) -> anyhow::Result<Option<(Session, Admin)>> {
+    let Some((session, admin)) = db.get_session_admin(session_id)? else {
+        return Ok(None);
+    };
+    if session_is_idle(&session.last_accessed) {
+        db.delete_session(session_id)?;
+        return Ok(None);
+    }
+    Ok(Some((session, admin)))
+}
+
+/// The admin API token in `Authorization: Bearer`, if that is what the
+/// request carries. User API keys are left alone.
+pub fn bearer_admin_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .filter(|token| is_admin_token(token))
+}
 
 pub struct AdminSession {
-    pub session: Session,
+    /// The login session; `None` when the request carried an admin API token.
+    pub session: Option<Session>,
+    /// The API token the request carried instead of a session cookie.
+    pub token: Option<AdminToken>,
+    /// Who is logged in, or owns the token; loaded fresh on every request.
+    pub admin: Admin,
+}
+
+impl AdminSession {
+    /// The admin's role, capped at the token's when authenticated by one.
+    pub fn role(&self) -> AdminRole {
+        match &self.token {
+            Some(token) => self.admin.role.min(token.role),
+            None => self.admin.role,
+        }
+    }
+
+    pub fn has_role(&self, minimum: AdminRole) -> bool {
+        self.role() >= minimum
+    }
+}
+
+/// An [`AdminSession`] whose admin has at least the `admin` role.
+pub struct RequireAdmin(pub AdminSession);
+
+/// An [`AdminSession`] whose admin is an owner.
+pub struct RequireOwner(pub AdminSession);
+
+impl Deref for RequireAdmin {
+    type Target = AdminSession;
+
+    fn deref(&self) -> &AdminSession {
+        &self.0
+    }
+}
+
+impl Deref for RequireOwner {
+    type Target = AdminSession;
+
+    fn deref(&self) -> &AdminSession {
+        &self.0
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct AuthError {
+    #[serde(skip)]
+    status: StatusCode,
     pub success: bool,
     pub error: String,
     pub code: String,
@@ -25,16 +126,26 @@ pub struct AuthError {
 impl AuthError {
     fn unauthorized() -> Self {
         Self {
+            status: StatusCode::UNAUTHORIZED,
             success: false,
             error: "Unauthorized".to_string(),
             code: "UNAUTHORIZED".to_string(),
         }
     }
+
+    fn forbidden(minimum: AdminRole) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            success: false,
+            error: format!("Requires the {} role", minimum.as_str()),
+            code: "FORBIDDEN".to_string(),
+        }
+    }
 }
 
 impl IntoResponse for AuthError {
     fn into_response(self) -> Response {
-        (StatusCode::UNAUTHORIZED, Json(self)).into_response()
+        (self.status, Json(self)).into_response()
     }
 }
 
@@ -49,6 +160,21 @@ where
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let app_state = AppState::from_ref(state);
 
+        // A bearer token is used on its own, never alongside a cookie, since
+        // requests carrying one skip the CSRF check.
+        if let Some(token) = bearer_admin_token(&parts.headers) {
+            let (token, admin) = app_state
+                .db
+                .authenticate_admin_token(token)
+                .map_err(|_| AuthError::unauthorized().into_response())?
+                .ok_or_else(|| AuthError::unauthorized().into_response())?;
+            return Ok(AdminSession {
+                session: None,
+                token: Some(token),
+                admin,
+            });
+        }
+
         let jar = CookieJar::from_request_parts(parts, state)
             .await
             .map_err(|_| AuthError::unauthorized().into_response())?;
@@ -58,14 +184,73 @@ where
             .map(|c| c.value().to_string())
             .ok_or_else(|| AuthError::unauthorized().into_response())?;
 
-        let session = app_state
-            .db
-            .get_session(&session_id)
+        let (session, admin) = load_session(&app_state.db, &session_id)
             .map_err(|_| AuthError::unauthorized().into_response())?
             .ok_or_else(|| AuthError::unauthorized().into_response())?;
 
         let _ = app_state.db.update_session_access(&session_id);
 
-        Ok(AdminSession { session })
+        Ok(AdminSession {
+            session: Some(session),
+            token: None,
+            admin,
+        })
+    }
+}
+
+async fn require_role<S>(
+    parts: &mut Parts,
+    state: &S,
+    minimum: AdminRole,
+) -> Result<AdminSession, Response>
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    let session = AdminSession::from_request_parts(parts, state).await?;
+    if !session.has_role(minimum) {
+        return Err(AuthError::forbidden(minimum).into_response());
+    }
+    Ok(session)
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequireAdmin
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        require_role(parts, state, AdminRole::Admin).await.map(RequireAdmin)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequireOwner
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        require_role(parts, state, AdminRole::Owner).await.map(RequireOwner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_idle_past_the_timeout() {
+        let now = parse_db_time("2024-01-15 12:00:00").unwrap();
+        let timeout = chrono::Duration::minutes(30);
+        assert!(!is_idle("2024-01-15 11:31:00", timeout, now));
+        assert!(is_idle("2024-01-15 11:30:00", timeout, now));
+        // Unparseable timestamps do not lock anyone out
+        assert!(!is_idle("garbage", timeout, now));
     }
 }
@@ -1,23 +1,43 @@
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use axum::{
     async_trait,
-    extract::FromRequestParts,
+    extract::{FromRequestParts, Request},
     http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
-use axum::extract::FromRef;
+use axum::extract::{FromRef, Query};
 use serde::Serialize;
-
+use std::collections::HashMap;
+
+use crate::cliproxy::load_server_config;
+use crate::db::api_keys::{parse_public_id, ApiKey, ApiKeyWithHash};
+use crate::db::Database;
+use crate::db::quota::{format_db_time, parse_timezone, QuotaPeriod};
+use crate::db::scopes::Scopes;
+use crate::db::users::{User, UserLimits};
+use chrono::Utc;
+use chrono_tz::Tz;
 use crate::AppState;
 
 #[derive(Debug, Clone)]
 pub struct UserContext {
     pub id: i64,
+    /// The key this request authenticated with.
+    pub api_key_id: i64,
     pub name: String,
     pub quota_tokens: Option<i64>,
     pub used_tokens: i64,
     pub enabled: bool,
+    /// The user's own overrides, falling back to their team's defaults.
+    pub limits: UserLimits,
+    pub team_id: Option<i64>,
+    /// The user's scopes and the key's; a request must satisfy both.
+    pub scopes: Scopes,
+    pub key_scopes: Scopes,
+    /// Checked before the server-wide `model_mappings`.
+    pub model_aliases: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -51,12 +71,49 @@ impl ApiKeyError {
             code: "QUOTA_EXCEEDED".to_string(),
         }
     }
+
+    fn team_quota_exceeded() -> Self {
+        Self {
+            success: false,
+            error: "Team quota exceeded".to_string(),
+            code: "TEAM_QUOTA_EXCEEDED".to_string(),
+        }
+    }
+
+    fn daily_limit_exceeded() -> Self {
+        Self {
+            success: false,
+            error: "Daily token limit exceeded".to_string(),
+            code: "DAILY_LIMIT_EXCEEDED".to_string(),
+        }
+    }
+}
+
+/// Tokens the user has used since local midnight in their quota timezone.
+pub(crate) fn tokens_used_today(app_state: &AppState, user: &User) -> anyhow::Result<i64> {
+    let tz = parse_timezone(&user.quota_timezone).unwrap_or(Tz::UTC);
+    let Some(day_start) = QuotaPeriod::Daily.period_start(Utc::now(), tz) else {
+        return Ok(0);
+    };
+    app_state
+        .db
+        .get_user_tokens_since(user.id, &format_db_time(day_start))
 }
 
 pub struct ApiKeyAuth {
     pub user: UserContext,
 }
 
+fn verify_key(api_key: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(api_key.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
 fn extract_prefix(api_key: &str) -> Option<&str> {
     if !api_key.starts_with("sk-") {
         return None;
@@ -66,8 +123,121 @@ fn extract_prefix(api_key: &str) -> Option<&str> {
     Some(&api_key[..3 + last_dash])
 }
 
+/// Opaque keys are found by their indexed public ID. Legacy `sk-<name>-<hex>`
+/// keys keep working by prefix until they are rotated; several may share one.
+fn find_candidate_keys(
+    db: &Database,
+    api_key: &str,
+    prefix: &str,
+) -> anyhow::Result<Vec<ApiKeyWithHash>> {
+    if let Some(public_id) = parse_public_id(api_key) {
+        if let Some(key) = db.find_active_api_key_by_public_id(public_id)? {
+            return Ok(vec![key]);
+        }
+    }
+    db.find_active_legacy_api_keys(prefix)
+}
+
+/// Reads the presented key from `Authorization: Bearer` (OpenAI style),
+/// `x-api-key` (Anthropic style), or `x-goog-api-key` / `?key=` (Gemini style).
+fn extract_api_key(parts: &Parts) -> Result<String, &'static str> {
+    if let Some(auth_header) = parts.headers.get(AUTHORIZATION) {
+        let auth_header = auth_header
+            .to_str()
+            .map_err(|_| "Invalid Authorization format")?;
+        return auth_header
+            .strip_prefix("Bearer ")
+            .map(String::from)
+            .ok_or("Invalid Authorization format");
+    }
+
+    for header in ["x-api-key", "x-goog-api-key"] {
+        if let Some(value) = parts.headers.get(header).and_then(|v| v.to_str().ok()) {
+            return Ok(value.to_string());
+        }
+    }
+
+    Query::<HashMap<String, String>>::try_from_uri(&parts.uri)
+        .ok()
+        .and_then(|Query(mut params)| params.remove("key"))
+        .ok_or("Missing API key")
+}
+
+/// Verifies the presented key and loads its user. Disabled users are
+/// rejected here; quotas are left to [`ApiKeyAuth`].
+fn authenticate(
+    parts: &Parts,
+    app_state: &AppState,
+) -> Result<(ApiKey, User), (StatusCode, Json<ApiKeyError>)> {
+    let api_key = extract_api_key(parts)
+        .map_err(|message| (StatusCode::UNAUTHORIZED, Json(ApiKeyError::unauthorized(message))))?;
+
+    let api_key = api_key.as_str();
+
+    if !api_key.starts_with("sk-") {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ApiKeyError::unauthorized("Invalid API key format")),
+        ));
+    }
+
+    let prefix = extract_prefix(api_key).ok_or_else(|| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ApiKeyError::unauthorized("Invalid API key format")),
+        )
+    })?;
+
+    let invalid_key = || (StatusCode::UNAUTHORIZED, Json(ApiKeyError::unauthorized("Invalid API key")));
+
+    let digest = app_state.key_cache.digest(api_key);
+    let key = match app_state.key_cache.get(&digest) {
+        Some(key) => key,
+        None => {
+            let generation = app_state.key_cache.generation();
+            let candidates = find_candidate_keys(&app_state.db, api_key, prefix)
+                .map_err(|_| invalid_key())?;
+            let key = candidates
+                .into_iter()
+                .find(|candidate| verify_key(api_key, &candidate.key_hash))
+                .ok_or_else(invalid_key)?
+                .key;
+            app_state.key_cache.insert(digest, key.clone(), generation);
+            key
+        }
+    };
+
+    let user = app_state
+        .db
+        .get_user_by_id(key.user_id)
+        .map_err(|_| invalid_key())?
+        .ok_or_else(invalid_key)?;
+
+    if app_state.key_cache.should_record_use(key.id) {
+        if let Err(e) = app_state.db.touch_api_key(key.id) {
+            tracing::warn!("Failed to record API key use: {}", e);
+        }
+    }
+
+    if !user.enabled {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiKeyError::forbidden("User is disabled")),
+        ));
+    }
+
+    Ok((key, user))
+}
+
+/// The calling user and key, without [`ApiKeyAuth`]'s quota checks, so a
+/// user who has run out can still look up their own usage.
+pub struct ApiKeyIdentity {
+    pub user: User,
+    pub key: ApiKey,
+}
+
 #[async_trait]
-impl<S> FromRequestParts<S> for ApiKeyAuth
+impl<S> FromRequestParts<S> for ApiKeyIdentity
 where
     AppState: FromRef<S>,
     S: Send + Sync,
@@ -75,95 +245,67 @@ where
     type Rejection = Response;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let app_state = AppState::from_ref(state);
-
-        let auth_header = parts
-            .headers
-            .get(AUTHORIZATION)
-            .and_then(|v| v.to_str().ok())
-            .ok_or_else(|| {
-                (
-                    StatusCode::UNAUTHORIZED,
-                    Json(ApiKeyError::unauthorized("Missing Authorization header")),
-                )
-                    .into_response()
-            })?;
+        let (key, user) =
+            authenticate(parts, &AppState::from_ref(state)).map_err(IntoResponse::into_response)?;
+        Ok(ApiKeyIdentity { user, key })
+    }
+}
 
-        let api_key = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(ApiKeyError::unauthorized("Invalid Authorization format")),
-            )
-                .into_response()
-        })?;
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiKeyAuth
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
 
-        if !api_key.starts_with("sk-") {
-            return Err((
-                StatusCode::UNAUTHORIZED,
-                Json(ApiKeyError::unauthorized("Invalid API key format")),
-            )
-                .into_response());
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Already authenticated by `require_api_key` earlier in the stack.
+        if let Some(user) = parts.extensions.get::<UserContext>() {
+            return Ok(ApiKeyAuth { user: user.clone() });
         }
 
-        let prefix = extract_prefix(api_key).ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(ApiKeyError::unauthorized("Invalid API key format")),
-            )
-                .into_response()
-        })?;
-
-        let user_with_hash = app_state
-            .db
-            .get_user_by_api_key_prefix(prefix)
-            .map_err(|_| {
-                (
-                    StatusCode::UNAUTHORIZED,
-                    Json(ApiKeyError::unauthorized("Invalid API key")),
-                )
-                    .into_response()
-            })?
-            .ok_or_else(|| {
-                (
-                    StatusCode::UNAUTHORIZED,
-                    Json(ApiKeyError::unauthorized("Invalid API key")),
-                )
-                    .into_response()
-            })?;
+        let app_state = AppState::from_ref(state);
+        let (key, user) = authenticate(parts, &app_state).map_err(IntoResponse::into_response)?;
+        let user = &user;
 
-        let parsed_hash = PasswordHash::new(&user_with_hash.api_key_hash).map_err(|_| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(ApiKeyError::unauthorized("Invalid API key")),
-            )
-                .into_response()
-        })?;
-
-        Argon2::default()
-            .verify_password(api_key.as_bytes(), &parsed_hash)
-            .map_err(|_| {
-                (
-                    StatusCode::UNAUTHORIZED,
-                    Json(ApiKeyError::unauthorized("Invalid API key")),
+        if let Some(quota) = user.quota_tokens {
+            if user.used_tokens >= quota {
+                return Err((
+                    StatusCode::TOO_MANY_REQUESTS,
+                    Json(ApiKeyError::quota_exceeded()),
                 )
-                    .into_response()
-            })?;
-
-        let user = &user_with_hash.user;
+                    .into_response());
+            }
+        }
 
-        if !user.enabled {
+        let team = match user.team_id {
+            Some(team_id) => app_state.db.get_team(team_id).unwrap_or_else(|e| {
+                tracing::error!("Failed to load team {}: {}", team_id, e);
+                None
+            }),
+            None => None,
+        };
+        if team.as_ref().is_some_and(|team| team.quota_exhausted()) {
             return Err((
-                StatusCode::FORBIDDEN,
-                Json(ApiKeyError::forbidden("User is disabled")),
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(ApiKeyError::team_quota_exceeded()),
             )
                 .into_response());
         }
 
-        if let Some(quota) = user.quota_tokens {
-            if user.used_tokens >= quota {
+        let tokens_per_day = load_server_config(&app_state.db)
+            .ok()
+            .and_then(|config| config.rate_limits.tokens_per_day);
+        if let Some(tokens_per_day) = tokens_per_day {
+            let used_today = tokens_used_today(&app_state, user).unwrap_or_else(|e| {
+                tracing::error!("Failed to read daily usage: {}", e);
+                0
+            });
+            if used_today >= tokens_per_day {
                 return Err((
                     StatusCode::TOO_MANY_REQUESTS,
-                    Json(ApiKeyError::quota_exceeded()),
+                    Json(ApiKeyError::daily_limit_exceeded()),
                 )
                     .into_response());
             }
@@ -172,15 +314,32 @@ where
         Ok(ApiKeyAuth {
             user: UserContext {
                 id: user.id,
+                api_key_id: key.id,
                 name: user.name.clone(),
                 quota_tokens: user.quota_tokens,
                 used_tokens: user.used_tokens,
                 enabled: user.enabled,
+                limits: match &team {
+                    Some(team) => user.limits.or(&team.limits),
+                    None => user.limits.clone(),
+                },
+                team_id: user.team_id,
+                scopes: user.scopes.clone(),
+                key_scopes: key.scopes,
+                model_aliases: user.model_aliases.clone(),
             },
         })
     }
 }
 
+/// Middleware form of [`ApiKeyAuth`]: rejects unauthenticated requests and
+/// stores the caller's [`UserContext`] in the request extensions, where the
+/// rate limiter and the handler's own `ApiKeyAuth` extractor pick it up.
+pub async fn require_api_key(ApiKeyAuth { user }: ApiKeyAuth, mut req: Request, next: Next) -> Response {
+    req.extensions_mut().insert(user);
+    next.run(req).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -193,19 +352,27 @@ mod tests {
     use tower::ServiceExt;
 
     fn create_test_app(db: crate::db::Database) -> Router {
+        Router::new()
+            .route("/protected", get(protected_handler))
+            .with_state(create_test_app_state(db))
+    }
+
+    fn create_test_app_state(db: crate::db::Database) -> AppState {
         use std::sync::Arc;
+        use crate::middleware::key_cache::ApiKeyCache;
         use crate::middleware::rate_limit::RateLimiter;
-        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager};
+        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
         
-        let state = AppState { 
+        AppState { 
             db, 
             rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
             proxy_client: Arc::new(MockProxyManagementClient::default()),
             proxy_manager: Arc::new(MockProxyProcessManager::default()),
-        };
-        Router::new()
-            .route("/protected", get(protected_handler))
-            .with_state(state)
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
+        }
     }
 
     async fn protected_handler(auth: ApiKeyAuth) -> impl IntoResponse {
@@ -275,7 +442,8 @@ mod tests {
         let (_, api_key) = db.create_user("testuser", None).unwrap();
         let app = create_test_app(db);
 
-        let wrong_key = format!("sk-testuser-wrongwrongwrongwrongwrong");
+        let public_id = parse_public_id(&api_key).unwrap();
+        let wrong_key = format!("sk-pp-{}-wrongwrongwrongwrongwrong", public_id);
 
         let response = app
             .oneshot(
@@ -289,7 +457,6 @@ mod tests {
             .unwrap();
 
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
-        let _ = api_key;
     }
 
     #[tokio::test]
@@ -316,6 +483,96 @@ mod tests {
         assert_eq!(json["name"], "testuser");
     }
 
+    #[tokio::test]
+    async fn test_legacy_name_prefixed_key_still_allows_access() {
+        use argon2::password_hash::{rand_core::OsRng, PasswordHasher, SaltString};
+
+        let db = crate::db::Database::new_in_memory().unwrap();
+        let (user, _) = db.create_user("testuser", None).unwrap();
+        let legacy_key = "sk-testuser-0123456789abcdef0123456789abcdef";
+        let hash = Argon2::default()
+            .hash_password(legacy_key.as_bytes(), &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string();
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO api_keys (user_id, name, key_prefix, key_hash) VALUES (?1, 'old', 'sk-testuser', ?2)",
+                rusqlite::params![user.id, hash],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+        assert!(db.list_api_keys(user.id).unwrap().iter().any(|k| k.legacy));
+        let app = create_test_app(db);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/protected")
+                    .header("Authorization", format!("Bearer {}", legacy_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_x_api_key_header_allows_access() {
+        let db = crate::db::Database::new_in_memory().unwrap();
+        let (user, api_key) = db.create_user("testuser", None).unwrap();
+        let app = create_test_app(db);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/protected")
+                    .header("x-api-key", api_key)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["user_id"], user.id);
+    }
+
+    #[tokio::test]
+    async fn test_gemini_key_sources_allow_access() {
+        let db = crate::db::Database::new_in_memory().unwrap();
+        let (_, api_key) = db.create_user("testuser", None).unwrap();
+        let app = create_test_app(db);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/protected")
+                    .header("x-goog-api-key", api_key.clone())
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/protected?alt=sse&key={}", api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
     #[tokio::test]
     async fn test_disabled_user_returns_403() {
         let db = crate::db::Database::new_in_memory().unwrap();
@@ -367,4 +624,80 @@ mod tests {
         let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
         assert_eq!(json["code"], "QUOTA_EXCEEDED");
     }
+
+    #[tokio::test]
+    async fn test_team_quota_is_enforced_and_team_limits_apply() {
+        let db = crate::db::Database::new_in_memory().unwrap();
+        let team_limits = UserLimits {
+            requests_per_minute: Some(5),
+            tokens_per_minute: Some(500),
+            max_concurrent_requests: None,
+        };
+        let team = db.create_team("research", Some(100), &team_limits).unwrap();
+        let (user, api_key) = db.create_user("testuser", None).unwrap();
+        db.set_user_team(user.id, Some(team.id)).unwrap();
+        db.set_user_limits(user.id, &UserLimits { requests_per_minute: Some(2), ..Default::default() })
+            .unwrap();
+
+        let app = Router::new()
+            .route(
+                "/limits",
+                get(|auth: ApiKeyAuth| async move { Json(auth.user.limits.clone()) }),
+            )
+            .with_state(create_test_app_state(db.clone()));
+        let request = || {
+            Request::builder()
+                .uri("/limits")
+                .header("Authorization", format!("Bearer {}", api_key))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let limits: UserLimits = serde_json::from_slice(&body).unwrap();
+        assert_eq!(limits.requests_per_minute, Some(2));
+        assert_eq!(limits.tokens_per_minute, Some(500));
+
+        // A teammate exhausts the shared pool; this user's own quota is untouched
+        let (teammate, _) = db.create_user("teammate", None).unwrap();
+        db.set_user_team(teammate.id, Some(team.id)).unwrap();
+        db.log_usage(teammate.id, "openai", "gpt-4o", 80, 20, 10, "success").unwrap();
+
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "TEAM_QUOTA_EXCEEDED");
+    }
+
+    #[tokio::test]
+    async fn test_daily_token_limit_returns_429() {
+        let db = crate::db::Database::new_in_memory().unwrap();
+        let (user, api_key) = db.create_user("testuser", None).unwrap();
+        let mut config = load_server_config(&db).unwrap();
+        config.rate_limits.tokens_per_day = Some(1000);
+        crate::cliproxy::save_server_config(&db, &config).unwrap();
+        let app = create_test_app(db.clone());
+
+        let request = || {
+            Request::builder()
+                .uri("/protected")
+                .header("Authorization", format!("Bearer {}", api_key))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        db.log_usage(user.id, "openai", "gpt-4o", 600, 300, 10, "success").unwrap();
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        db.log_usage(user.id, "openai", "gpt-4o", 100, 0, 10, "success").unwrap();
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "DAILY_LIMIT_EXCEEDED");
+    }
 }
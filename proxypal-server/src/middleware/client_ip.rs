@@ -0,0 +1,89 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+
+/// Recorded when the peer address is not known (e.g. in tests).
+pub const UNKNOWN_IP: &str = "unknown";
+
+/// Whether to believe `X-Forwarded-For`. Only safe behind a reverse proxy
+/// that appends the real peer address, such as Render's.
+fn trust_proxy_headers() -> bool {
+    static TRUST: OnceLock<bool> = OnceLock::new();
+    *TRUST.get_or_init(|| {
+        std::env::var("TRUST_PROXY_HEADERS")
+            .map(|v| matches!(v.trim(), "1" | "true" | "yes"))
+            .unwrap_or(false)
+    })
+}
+
+/// The address a request came from. With `TRUST_PROXY_HEADERS` set this is
+/// the last `X-Forwarded-For` entry, the one our proxy added; earlier
+/// entries are client-supplied and could be forged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIp(pub String);
+
+impl ClientIp {
+    fn from_parts(parts: &Parts, trust_proxy: bool) -> Self {
+        if trust_proxy {
+            let forwarded = parts
+                .headers
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.rsplit(',').next())
+                .map(str::trim)
+                .filter(|ip| !ip.is_empty());
+            if let Some(ip) = forwarded {
+                return Self(ip.to_string());
+            }
+        }
+
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        Self(peer.unwrap_or_else(|| UNKNOWN_IP.to_string()))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_parts(parts, trust_proxy_headers()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    fn parts(forwarded: Option<&str>, peer: Option<&str>) -> Parts {
+        let mut builder = Request::builder();
+        if let Some(value) = forwarded {
+            builder = builder.header("X-Forwarded-For", value);
+        }
+        let (mut parts, _) = builder.body(()).unwrap().into_parts();
+        if let Some(addr) = peer {
+            parts.extensions.insert(ConnectInfo(addr.parse::<SocketAddr>().unwrap()));
+        }
+        parts
+    }
+
+    #[test]
+    fn forwarded_header_is_only_used_when_trusted() {
+        let p = parts(Some("6.6.6.6, 203.0.113.7"), Some("10.0.0.2:5000"));
+        assert_eq!(ClientIp::from_parts(&p, true).0, "203.0.113.7");
+        assert_eq!(ClientIp::from_parts(&p, false).0, "10.0.0.2");
+        assert_eq!(ClientIp::from_parts(&parts(None, None), true).0, UNKNOWN_IP);
+    }
+}
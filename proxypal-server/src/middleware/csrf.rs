@@ -8,6 +8,8 @@ use axum::{
 use axum_extra::extract::cookie::CookieJar;
 use serde::Serialize;
 
+use super::admin_auth::bearer_admin_token;
+
 #[derive(Debug, Serialize)]
 struct CsrfError {
     success: bool,
@@ -34,6 +36,18 @@ pub async fn csrf_protection(
         return Ok(next.run(req).await);
     }
 
+    // Browsers never attach an `Authorization` header on their own, and
+    // `AdminSession` ignores the cookie when one is present.
+    if bearer_admin_token(req.headers()).is_some() {
+        return Ok(next.run(req).await);
+    }
+
+    // Without a session cookie there is no ambient credential to forge a
+    // request with (login, API-key calls), so there is nothing to protect.
+    if jar.get("session").is_none() {
+        return Ok(next.run(req).await);
+    }
+
     let cookie_token = jar
         .get("csrf_token")
         .map(|c| c.value().to_string());
@@ -49,3 +63,58 @@ pub async fn csrf_protection(
         _ => Err((StatusCode::FORBIDDEN, Json(CsrfError::forbidden())).into_response()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", post(|| async { "ok" }))
+            .layer(middleware::from_fn(csrf_protection))
+    }
+
+    async fn status(authorization: Option<&str>) -> StatusCode {
+        let mut builder = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header("Cookie", "session=abc; csrf_token=xyz");
+        if let Some(value) = authorization {
+            builder = builder.header("Authorization", value);
+        }
+        app()
+            .oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn admin_tokens_skip_the_csrf_check() {
+        assert_eq!(status(None).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(Some("Bearer sk-pp-0123456789abcdef-secret")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(Some("Bearer ppa-0123456789abcdef-secret")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn requests_need_a_matching_token_only_with_a_session_cookie() {
+        let send = |cookie: &'static str, token: Option<&'static str>| async move {
+            let mut builder = Request::builder().method("POST").uri("/").header("Cookie", cookie);
+            if let Some(token) = token {
+                builder = builder.header("X-CSRF-Token", token);
+            }
+            app()
+                .oneshot(builder.body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+                .status()
+        };
+
+        assert_eq!(send("csrf_token=xyz", None).await, StatusCode::OK);
+        assert_eq!(send("session=abc; csrf_token=xyz", None).await, StatusCode::FORBIDDEN);
+        assert_eq!(send("session=abc; csrf_token=xyz", Some("nope")).await, StatusCode::FORBIDDEN);
+        assert_eq!(send("session=abc; csrf_token=xyz", Some("xyz")).await, StatusCode::OK);
+    }
+}
@@ -0,0 +1,304 @@
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::Serialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::db::api_keys::ApiKey;
+use crate::db::quota::format_db_time;
+
+const DEFAULT_CAPACITY: usize = 10_000;
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+/// How often a key's `last_used_at` is written while it is in use.
+const RECORD_USE_INTERVAL: Duration = Duration::from_secs(60);
+
+type Digest = [u8; 32];
+
+struct CachedKey {
+    key: ApiKey,
+    cached_at: Instant,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyCacheStats {
+    pub entries: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+}
+
+/// Remembers keys that recently passed Argon2 verification so repeat
+/// requests skip the hash. Entries are keyed by an HMAC of the presented
+/// key under a per-process secret, so plaintext keys are never held.
+pub struct ApiKeyCache {
+    secret: [u8; 32],
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<HashMap<Digest, CachedKey>>,
+    /// Bumped on every invalidation; lookups that started before one must
+    /// not repopulate the cache with what they read.
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    /// When each key's use was last written to the database.
+    uses_recorded: Mutex<HashMap<i64, Instant>>,
+}
+
+impl Default for ApiKeyCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, DEFAULT_TTL)
+    }
+}
+
+impl ApiKeyCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            secret: rand::thread_rng().gen(),
+            capacity,
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            uses_recorded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads `API_KEY_CACHE_SIZE` and `API_KEY_CACHE_TTL_SECS`; a size of 0
+    /// disables caching.
+    pub fn from_env() -> Self {
+        let capacity = std::env::var("API_KEY_CACHE_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CAPACITY);
+        let ttl = std::env::var("API_KEY_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL);
+        Self::new(capacity, ttl)
+    }
+
+    pub fn digest(&self, api_key: &str) -> Digest {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .expect("HMAC accepts any key length");
+        mac.update(api_key.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Returns the cached key unless its entry or the key itself expired.
+    pub fn get(&self, digest: &Digest) -> Option<ApiKey> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = format_db_time(chrono::Utc::now());
+        let fresh = entries.get(digest).and_then(|entry| {
+            let key_expired = entry.key.expires_at.as_deref().is_some_and(|t| t <= now.as_str());
+            (entry.cached_at.elapsed() < self.ttl && !key_expired).then(|| entry.key.clone())
+        });
+
+        if fresh.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            entries.remove(digest);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        fresh
+    }
+
+    /// Caches a verified key, unless something was invalidated since
+    /// `generation` was read.
+    pub fn insert(&self, digest: Digest, key: ApiKey, generation: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if self.generation() != generation {
+            return;
+        }
+
+        if entries.len() >= self.capacity && !entries.contains_key(&digest) {
+            entries.retain(|_, entry| entry.cached_at.elapsed() < self.ttl);
+            if entries.len() >= self.capacity {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.cached_at)
+                    .map(|(digest, _)| *digest);
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+
+        entries.insert(
+            digest,
+            CachedKey {
+                key,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn invalidate_key(&self, key_id: i64) {
+        self.invalidate(|key| key.id == key_id);
+    }
+
+    pub fn invalidate_user(&self, user_id: i64) {
+        self.invalidate(|key| key.user_id == user_id);
+    }
+
+    fn invalidate(&self, matches: impl Fn(&ApiKey) -> bool) {
+        let mut entries = self.entries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        entries.retain(|_, entry| !matches(&entry.key));
+    }
+
+    /// Whether this use of `key_id` should be written to `last_used_at`:
+    /// at most once per `RECORD_USE_INTERVAL`, so requests do not each cost
+    /// a database write.
+    pub fn should_record_use(&self, key_id: i64) -> bool {
+        let mut recorded = self.uses_recorded.lock().unwrap();
+        if recorded
+            .get(&key_id)
+            .is_some_and(|at| at.elapsed() < RECORD_USE_INTERVAL)
+        {
+            return false;
+        }
+
+        if recorded.len() >= self.capacity {
+            recorded.retain(|_, at| at.elapsed() < RECORD_USE_INTERVAL);
+        }
+        recorded.insert(key_id, Instant::now());
+        true
+    }
+
+    pub fn stats(&self) -> KeyCacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
+        KeyCacheStats {
+            entries: self.entries.lock().unwrap().len(),
+            capacity: self.capacity,
+            hits,
+            misses,
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                hits as f64 / lookups as f64
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: i64, user_id: i64, expires_at: Option<&str>) -> ApiKey {
+        ApiKey {
+            id,
+            user_id,
+            name: "default".to_string(),
+            key_prefix: format!("sk-pp-{:016x}", id),
+            created_at: "2025-01-01 00:00:00".to_string(),
+            expires_at: expires_at.map(String::from),
+            revoked_at: None,
+            last_used_at: None,
+            active: true,
+            legacy: false,
+            scopes: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_hit_and_miss_are_counted() {
+        let cache = ApiKeyCache::default();
+        let digest = cache.digest("sk-pp-a");
+        assert_ne!(digest, cache.digest("sk-pp-b"));
+
+        assert!(cache.get(&digest).is_none());
+        cache.insert(digest, key(1, 1, None), cache.generation());
+        assert_eq!(cache.get(&digest).unwrap().id, 1);
+        assert_eq!(cache.get(&digest).unwrap().id, 1);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 1, 1));
+        assert!((stats.hit_rate - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_entries_expire_with_ttl_and_key_expiry() {
+        let cache = ApiKeyCache::new(10, Duration::ZERO);
+        let digest = cache.digest("sk-pp-a");
+        cache.insert(digest, key(1, 1, None), cache.generation());
+        assert!(cache.get(&digest).is_none());
+
+        let cache = ApiKeyCache::default();
+        cache.insert(digest, key(1, 1, Some("2000-01-01 00:00:00")), cache.generation());
+        assert!(cache.get(&digest).is_none());
+    }
+
+    #[test]
+    fn test_invalidation_by_key_and_user() {
+        let cache = ApiKeyCache::default();
+        let (a, b, c) = (cache.digest("a"), cache.digest("b"), cache.digest("c"));
+        cache.insert(a, key(1, 1, None), cache.generation());
+        cache.insert(b, key(2, 1, None), cache.generation());
+        cache.insert(c, key(3, 2, None), cache.generation());
+
+        cache.invalidate_key(2);
+        assert!(cache.get(&a).is_some());
+        assert!(cache.get(&b).is_none());
+
+        cache.invalidate_user(1);
+        assert!(cache.get(&a).is_none());
+        assert!(cache.get(&c).is_some());
+    }
+
+    #[test]
+    fn test_stale_lookup_is_not_cached_after_invalidation() {
+        let cache = ApiKeyCache::default();
+        let digest = cache.digest("a");
+        let generation = cache.generation();
+        cache.invalidate_user(1);
+        cache.insert(digest, key(1, 1, None), generation);
+        assert!(cache.get(&digest).is_none());
+    }
+
+    #[test]
+    fn test_key_use_is_recorded_once_per_interval() {
+        let cache = ApiKeyCache::default();
+        assert!(cache.should_record_use(1));
+        assert!(!cache.should_record_use(1));
+        assert!(cache.should_record_use(2));
+
+        cache
+            .uses_recorded
+            .lock()
+            .unwrap()
+            .insert(1, Instant::now() - RECORD_USE_INTERVAL);
+        assert!(cache.should_record_use(1));
+    }
+
+    #[test]
+    fn test_capacity_is_bounded() {
+        let cache = ApiKeyCache::new(2, DEFAULT_TTL);
+        for id in 1..=3 {
+            cache.insert(cache.digest(&id.to_string()), key(id, id, None), cache.generation());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(cache.stats().entries, 2);
+        assert!(cache.get(&cache.digest("1")).is_none());
+        assert!(cache.get(&cache.digest("3")).is_some());
+
+        let disabled = ApiKeyCache::new(0, DEFAULT_TTL);
+        disabled.insert(disabled.digest("a"), key(1, 1, None), disabled.generation());
+        assert_eq!(disabled.stats().entries, 0);
+    }
+}
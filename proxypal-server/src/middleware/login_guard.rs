@@ -0,0 +1,347 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::db::quota::format_db_time;
+
+/// Stop tracking an address after this long without a failure.
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+/// Bound on tracked addresses; stale ones are pruned past this.
+const MAX_TRACKED_IPS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct LoginGuardConfig {
+    /// Failures from one address before each further attempt is delayed.
+    pub free_attempts: u32,
+    /// First delay; doubles with every further failure.
+    pub base_delay: Duration,
+    /// Failures from one address that lock it out.
+    pub lockout_after: u32,
+    pub lockout: Duration,
+    /// Failures from all addresses within `global_window` that lock out
+    /// password login for everyone, against attacks spread over many IPs.
+    pub global_max_failures: u32,
+    pub global_window: Duration,
+    pub global_lockout: Duration,
+}
+
+impl Default for LoginGuardConfig {
+    fn default() -> Self {
+        Self {
+            free_attempts: 3,
+            base_delay: Duration::from_secs(1),
+            lockout_after: 10,
+            lockout: Duration::from_secs(15 * 60),
+            global_max_failures: 100,
+            global_window: Duration::from_secs(5 * 60),
+            global_lockout: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// Why a login attempt was turned away before the password was checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoginBlock {
+    /// This address must wait out its backoff delay.
+    Backoff,
+    /// This address failed too often.
+    Locked,
+    /// Too many failures overall.
+    Global,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoginThrottled {
+    pub block: LoginBlock,
+    pub retry_after: Duration,
+}
+
+struct IpState {
+    failures: u32,
+    last_failure: Instant,
+    blocked_until: Option<Instant>,
+}
+
+#[derive(Default)]
+struct GlobalState {
+    failures: VecDeque<Instant>,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpLockout {
+    pub ip: String,
+    pub failures: u32,
+    pub block: LoginBlock,
+    pub blocked_until: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginGuardStatus {
+    /// Failures from all addresses within the global window.
+    pub recent_failures: usize,
+    pub global_max_failures: u32,
+    /// Set while password login is locked for everyone.
+    pub global_locked_until: Option<String>,
+    /// Addresses currently delayed or locked out.
+    pub blocked_ips: Vec<IpLockout>,
+}
+
+/// Throttles password login by client address and overall: exponential
+/// backoff after a few failures, then a temporary lockout. In memory, so
+/// a restart clears it; the `failed_logins` table keeps the history.
+pub struct LoginGuard {
+    config: LoginGuardConfig,
+    ips: Mutex<HashMap<String, IpState>>,
+    global: Mutex<GlobalState>,
+}
+
+impl Default for LoginGuard {
+    fn default() -> Self {
+        Self::new(LoginGuardConfig::default())
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok().and_then(|v| v.parse().ok())
+}
+
+fn wall_clock(at: Instant, now: Instant) -> String {
+    let remaining = at.saturating_duration_since(now);
+    format_db_time(chrono::Utc::now() + chrono::Duration::from_std(remaining).unwrap_or_default())
+}
+
+impl LoginGuard {
+    pub fn new(config: LoginGuardConfig) -> Self {
+        Self {
+            config,
+            ips: Mutex::new(HashMap::new()),
+            global: Mutex::new(GlobalState::default()),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let mut config = LoginGuardConfig::default();
+        if let Some(n) = env_u64("LOGIN_MAX_FAILURES") {
+            config.lockout_after = n as u32;
+        }
+        if let Some(minutes) = env_u64("LOGIN_LOCKOUT_MINUTES") {
+            config.lockout = Duration::from_secs(minutes * 60);
+        }
+        if let Some(n) = env_u64("LOGIN_GLOBAL_MAX_FAILURES") {
+            config.global_max_failures = n as u32;
+        }
+        Self::new(config)
+    }
+
+    /// Call before checking credentials.
+    pub fn check(&self, ip: &str) -> Result<(), LoginThrottled> {
+        self.check_at(ip, Instant::now())
+    }
+
+    pub fn record_failure(&self, ip: &str) {
+        self.record_failure_at(ip, Instant::now())
+    }
+
+    /// A completed login clears the address's failures.
+    pub fn record_success(&self, ip: &str) {
+        self.ips.lock().unwrap().remove(ip);
+    }
+
+    pub fn status(&self) -> LoginGuardStatus {
+        self.status_at(Instant::now())
+    }
+
+    fn check_at(&self, ip: &str, now: Instant) -> Result<(), LoginThrottled> {
+        if let Some(until) = self.global.lock().unwrap().locked_until.filter(|u| *u > now) {
+            return Err(LoginThrottled {
+                block: LoginBlock::Global,
+                retry_after: until - now,
+            });
+        }
+
+        let ips = self.ips.lock().unwrap();
+        match ips.get(ip).and_then(|s| s.blocked_until.filter(|u| *u > now).map(|u| (s, u))) {
+            Some((state, until)) => Err(LoginThrottled {
+                block: self.block_for(state.failures),
+                retry_after: until - now,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn block_for(&self, failures: u32) -> LoginBlock {
+        if failures >= self.config.lockout_after {
+            LoginBlock::Locked
+        } else {
+            LoginBlock::Backoff
+        }
+    }
+
+    fn delay_after(&self, failures: u32) -> Option<Duration> {
+        if failures >= self.config.lockout_after {
+            return Some(self.config.lockout);
+        }
+        let excess = failures.checked_sub(self.config.free_attempts).filter(|n| *n > 0)?;
+        let delay = self.config.base_delay.saturating_mul(1 << (excess - 1).min(20));
+        Some(delay.min(self.config.lockout))
+    }
+
+    fn record_failure_at(&self, ip: &str, now: Instant) {
+        {
+            let mut ips = self.ips.lock().unwrap();
+            if ips.len() >= MAX_TRACKED_IPS {
+                ips.retain(|_, s| {
+                    s.blocked_until.is_some_and(|u| u > now)
+                        || now.duration_since(s.last_failure) < FORGET_AFTER
+                });
+            }
+            let state = ips.entry(ip.to_string()).or_insert(IpState {
+                failures: 0,
+                last_failure: now,
+                blocked_until: None,
+            });
+            if now.duration_since(state.last_failure) >= FORGET_AFTER {
+                state.failures = 0;
+            }
+            // A lockout that ran out starts the address over
+            if state.failures >= self.config.lockout_after {
+                state.failures = 0;
+            }
+            state.failures += 1;
+            state.last_failure = now;
+            state.blocked_until = self.delay_after(state.failures).map(|d| now + d);
+        }
+
+        let mut global = self.global.lock().unwrap();
+        while global
+            .failures
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= self.config.global_window)
+        {
+            global.failures.pop_front();
+        }
+        global.failures.push_back(now);
+        if global.failures.len() >= self.config.global_max_failures as usize {
+            tracing::warn!(
+                "{} failed admin logins within {:?}; locking password login",
+                global.failures.len(),
+                self.config.global_window
+            );
+            global.locked_until = Some(now + self.config.global_lockout);
+            global.failures.clear();
+        }
+    }
+
+    fn status_at(&self, now: Instant) -> LoginGuardStatus {
+        let global = self.global.lock().unwrap();
+        let recent_failures = global
+            .failures
+            .iter()
+            .filter(|t| now.duration_since(**t) < self.config.global_window)
+            .count();
+        let global_locked_until = global
+            .locked_until
+            .filter(|u| *u > now)
+            .map(|u| wall_clock(u, now));
+        drop(global);
+
+        let mut blocked_ips: Vec<IpLockout> = self
+            .ips
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(ip, state)| {
+                let until = state.blocked_until.filter(|u| *u > now)?;
+                Some(IpLockout {
+                    ip: ip.clone(),
+                    failures: state.failures,
+                    block: self.block_for(state.failures),
+                    blocked_until: wall_clock(until, now),
+                })
+            })
+            .collect();
+        blocked_ips.sort_by(|a, b| b.failures.cmp(&a.failures).then_with(|| a.ip.cmp(&b.ip)));
+
+        LoginGuardStatus {
+            recent_failures,
+            global_max_failures: self.config.global_max_failures,
+            global_locked_until,
+            blocked_ips,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> LoginGuard {
+        LoginGuard::new(LoginGuardConfig {
+            free_attempts: 2,
+            base_delay: Duration::from_secs(1),
+            lockout_after: 5,
+            lockout: Duration::from_secs(600),
+            global_max_failures: 8,
+            global_window: Duration::from_secs(60),
+            global_lockout: Duration::from_secs(120),
+        })
+    }
+
+    #[test]
+    fn backoff_doubles_then_locks_out() {
+        let guard = guard();
+        let start = Instant::now();
+        let fail = |n: u64| guard.record_failure_at("1.2.3.4", start + Duration::from_secs(n));
+
+        fail(0);
+        fail(0);
+        assert!(guard.check_at("1.2.3.4", start).is_ok());
+
+        fail(0);
+        let throttled = guard.check_at("1.2.3.4", start).unwrap_err();
+        assert_eq!((throttled.block, throttled.retry_after), (LoginBlock::Backoff, Duration::from_secs(1)));
+        fail(1);
+        assert_eq!(guard.check_at("1.2.3.4", start + Duration::from_secs(1)).unwrap_err().retry_after, Duration::from_secs(2));
+
+        // Other addresses are unaffected
+        assert!(guard.check_at("5.6.7.8", start).is_ok());
+
+        fail(3);
+        let locked = guard.check_at("1.2.3.4", start + Duration::from_secs(3)).unwrap_err();
+        assert_eq!((locked.block, locked.retry_after), (LoginBlock::Locked, Duration::from_secs(600)));
+        assert!(guard.check_at("1.2.3.4", start + Duration::from_secs(603)).is_ok());
+
+        guard.record_success("1.2.3.4");
+        assert!(guard.status_at(start).blocked_ips.is_empty());
+    }
+
+    #[test]
+    fn failures_across_addresses_lock_everyone_out() {
+        let guard = guard();
+        let start = Instant::now();
+        for i in 0..7 {
+            guard.record_failure_at(&format!("10.0.0.{}", i), start);
+        }
+        assert!(guard.check_at("192.0.2.1", start).is_ok());
+        assert_eq!(guard.status_at(start).recent_failures, 7);
+
+        guard.record_failure_at("10.0.0.99", start);
+        let throttled = guard.check_at("192.0.2.1", start).unwrap_err();
+        assert_eq!((throttled.block, throttled.retry_after), (LoginBlock::Global, Duration::from_secs(120)));
+        assert!(guard.status_at(start).global_locked_until.is_some());
+        assert!(guard.check_at("192.0.2.1", start + Duration::from_secs(120)).is_ok());
+
+        // Old failures fall out of the window
+        let later = start + Duration::from_secs(300);
+        for i in 0..7 {
+            guard.record_failure_at(&format!("10.0.1.{}", i), later - Duration::from_secs(90));
+        }
+        guard.record_failure_at("10.0.2.1", later);
+        assert!(guard.check_at("192.0.2.1", later).is_ok());
+    }
+}
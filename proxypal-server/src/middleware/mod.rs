@@ -1,4 +1,7 @@
 pub mod admin_auth;
 pub mod api_key_auth;
+pub mod client_ip;
 pub mod csrf;
+pub mod key_cache;
+pub mod login_guard;
 pub mod rate_limit;
@@ -1,18 +1,21 @@
 use axum::{
     body::Body,
     extract::State,
-    http::{Request, StatusCode},
-    middleware::Next,
+    http::{header::RETRY_AFTER, Request, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
-    Extension, Json,
+    Extension, Json, Router,
 };
+use futures_util::StreamExt;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
-use super::api_key_auth::UserContext;
+use super::api_key_auth::{require_api_key, UserContext};
+use crate::AppState;
 
 #[derive(Debug, Serialize)]
 struct RateLimitError {
@@ -29,24 +32,159 @@ impl RateLimitError {
             code: "RATE_LIMITED".to_string(),
         }
     }
+
+    fn concurrency_limited() -> Self {
+        Self {
+            success: false,
+            error: "Too many concurrent requests".to_string(),
+            code: "CONCURRENCY_LIMITED".to_string(),
+        }
+    }
 }
 
 pub struct RateLimiter {
     state: Arc<Mutex<HashMap<i64, (Instant, u64)>>>,
-    limit: u64,
+    in_flight: std::sync::Mutex<HashMap<i64, u64>>,
+    token_buckets: std::sync::Mutex<HashMap<i64, TokenBucket>>,
+    limit: AtomicU64,
+    concurrency_limit: AtomicU64,
+    token_limit: AtomicU64,
     window: Duration,
 }
 
+/// Per-user token bucket: holds up to one minute of tokens and refills
+/// continuously. The balance may go negative when actual usage exceeds
+/// what was reserved, which delays the user's next request.
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, capacity: u64, window: Duration) {
+        let now = Instant::now();
+        let rate = capacity as f64 / window.as_secs_f64();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity as f64);
+        self.updated = now;
+    }
+}
+
+/// Tokens taken from a user's bucket for one request. `settle` swaps the
+/// estimate for the real usage; dropping it unsettled refunds everything.
+pub struct TokenReservation {
+    limiter: Arc<RateLimiter>,
+    user_id: i64,
+    capacity: u64,
+    reserved: u64,
+    actual: Option<u64>,
+}
+
+impl TokenReservation {
+    pub fn settle(mut self, actual_tokens: u64) {
+        self.actual = Some(actual_tokens);
+    }
+}
+
+impl Drop for TokenReservation {
+    fn drop(&mut self) {
+        let adjustment = self.reserved as f64 - self.actual.unwrap_or(0) as f64;
+        let mut buckets = self.limiter.token_buckets.lock().unwrap();
+        if let Some(bucket) = buckets.get_mut(&self.user_id) {
+            bucket.refill(self.capacity, self.limiter.window);
+            bucket.tokens = (bucket.tokens + adjustment).min(self.capacity as f64);
+        }
+    }
+}
+
+/// Holds one of a user's concurrent-request slots until dropped.
+pub struct ConcurrencyPermit {
+    limiter: Arc<RateLimiter>,
+    user_id: i64,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.limiter.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.user_id) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&self.user_id);
+            }
+        }
+    }
+}
+
 impl RateLimiter {
     pub fn new(requests_per_minute: u64) -> Self {
         Self {
             state: Arc::new(Mutex::new(HashMap::new())),
-            limit: requests_per_minute,
+            in_flight: std::sync::Mutex::new(HashMap::new()),
+            token_buckets: std::sync::Mutex::new(HashMap::new()),
+            limit: AtomicU64::new(requests_per_minute),
+            concurrency_limit: AtomicU64::new(0),
+            token_limit: AtomicU64::new(0),
             window: Duration::from_secs(60),
         }
     }
 
+    /// Current requests-per-minute limit; 0 means unlimited.
+    pub fn limit(&self) -> u64 {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// Applies a new limit without restarting; running windows keep their counts.
+    pub fn set_limit(&self, requests_per_minute: u64) {
+        self.limit.store(requests_per_minute, Ordering::Relaxed);
+    }
+
+    /// Global concurrent-request limit per user; 0 means unlimited.
+    pub fn concurrency_limit(&self) -> u64 {
+        self.concurrency_limit.load(Ordering::Relaxed)
+    }
+
+    pub fn set_concurrency_limit(&self, max_concurrent: u64) {
+        self.concurrency_limit.store(max_concurrent, Ordering::Relaxed);
+    }
+
+    /// Global tokens-per-minute limit per user; 0 means unlimited.
+    pub fn token_limit(&self) -> u64 {
+        self.token_limit.load(Ordering::Relaxed)
+    }
+
+    pub fn set_token_limit(&self, tokens_per_minute: u64) {
+        self.token_limit.store(tokens_per_minute, Ordering::Relaxed);
+    }
+
+    /// The user's own TPM override, falling back to the global limit.
+    pub fn token_limit_for(&self, user: &UserContext) -> u64 {
+        user.limits
+            .tokens_per_minute
+            .map(|tpm| tpm.max(0) as u64)
+            .unwrap_or_else(|| self.token_limit())
+    }
+
+    /// The user's own RPM override, falling back to the global limit.
+    pub fn limit_for(&self, user: &UserContext) -> u64 {
+        user.limits
+            .requests_per_minute
+            .map(|rpm| rpm.max(0) as u64)
+            .unwrap_or_else(|| self.limit())
+    }
+
+    /// The user's own concurrency override, falling back to the global limit.
+    pub fn concurrency_limit_for(&self, user: &UserContext) -> u64 {
+        user.limits
+            .max_concurrent_requests
+            .map(|max| max.max(0) as u64)
+            .unwrap_or_else(|| self.concurrency_limit())
+    }
+
     pub async fn check(&self, user_id: i64) -> (bool, u64, u64) {
+        self.check_with_limit(user_id, self.limit()).await
+    }
+
+    pub async fn check_with_limit(&self, user_id: i64, limit: u64) -> (bool, u64, u64) {
         let mut state = self.state.lock().await;
         let now = Instant::now();
 
@@ -56,19 +194,77 @@ impl RateLimiter {
         if elapsed >= self.window {
             state.insert(user_id, (now, 1));
             let reset_at = now.elapsed().as_secs() + self.window.as_secs();
-            (true, self.limit - 1, reset_at)
+            (true, limit.saturating_sub(1), reset_at)
         } else {
             let new_count = count + 1;
             let remaining_secs = (self.window - elapsed).as_secs();
-            if new_count > self.limit {
+            if new_count > limit {
                 (false, 0, remaining_secs)
             } else {
                 state.insert(user_id, (window_start, new_count));
-                (true, self.limit - new_count, remaining_secs)
+                (true, limit - new_count, remaining_secs)
             }
         }
     }
 
+    /// Takes a concurrent-request slot for the user, or `None` if all
+    /// `max_concurrent` slots are in use.
+    pub fn try_acquire(self: &Arc<Self>, user_id: i64, max_concurrent: u64) -> Option<ConcurrencyPermit> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(user_id).or_insert(0);
+        if *count >= max_concurrent {
+            return None;
+        }
+        *count += 1;
+        Some(ConcurrencyPermit {
+            limiter: self.clone(),
+            user_id,
+        })
+    }
+
+    /// Reserves `estimate` tokens from the user's bucket of `capacity`
+    /// tokens per minute. A request larger than the whole bucket is let
+    /// through once the bucket is full. On rejection, returns how many
+    /// seconds until enough tokens have refilled.
+    pub fn reserve_tokens(
+        self: &Arc<Self>,
+        user_id: i64,
+        capacity: u64,
+        estimate: u64,
+    ) -> Result<TokenReservation, u64> {
+        let mut buckets = self.token_buckets.lock().unwrap();
+        let bucket = buckets.entry(user_id).or_insert_with(|| TokenBucket {
+            tokens: capacity as f64,
+            updated: Instant::now(),
+        });
+        bucket.refill(capacity, self.window);
+
+        let needed = estimate.min(capacity) as f64;
+        if bucket.tokens < needed {
+            let rate = capacity as f64 / self.window.as_secs_f64();
+            let wait = ((needed - bucket.tokens) / rate).ceil() as u64;
+            return Err(wait.max(1));
+        }
+
+        bucket.tokens -= estimate as f64;
+        Ok(TokenReservation {
+            limiter: self.clone(),
+            user_id,
+            capacity,
+            reserved: estimate,
+            actual: None,
+        })
+    }
+
+    #[cfg(test)]
+    fn available_tokens(&self, user_id: i64) -> Option<f64> {
+        self.token_buckets
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .map(|bucket| bucket.tokens)
+    }
+
     #[cfg(test)]
     pub async fn reset(&self) {
         let mut state = self.state.lock().await;
@@ -87,33 +283,79 @@ pub async fn rate_limit_middleware(
     req: Request<Body>,
     next: Next,
 ) -> Result<Response, Response> {
-    let (allowed, remaining, reset_secs) = limiter.check(user.id).await;
+    let max_concurrent = limiter.concurrency_limit_for(&user);
+    let permit = if max_concurrent > 0 {
+        let permit = limiter.try_acquire(user.id, max_concurrent).ok_or_else(|| {
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(RateLimitError::concurrency_limited()),
+            )
+                .into_response()
+        })?;
+        Some(permit)
+    } else {
+        None
+    };
 
-    if !allowed {
-        let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(RateLimitError::rate_limited()))
-            .into_response();
+    let limit = limiter.limit_for(&user);
+    let mut rate_headers = None;
+    if limit > 0 {
+        let (allowed, remaining, reset_secs) = limiter.check_with_limit(user.id, limit).await;
 
-        let headers = response.headers_mut();
-        headers.insert("X-RateLimit-Limit", limiter.limit.into());
-        headers.insert("X-RateLimit-Remaining", 0u64.into());
-        headers.insert("X-RateLimit-Reset", reset_secs.into());
+        if !allowed {
+            let mut response =
+                (StatusCode::TOO_MANY_REQUESTS, Json(RateLimitError::rate_limited()))
+                    .into_response();
 
-        return Err(response);
+            let headers = response.headers_mut();
+            headers.insert("X-RateLimit-Limit", limit.into());
+            headers.insert("X-RateLimit-Remaining", 0u64.into());
+            headers.insert("X-RateLimit-Reset", reset_secs.into());
+            headers.insert(RETRY_AFTER, reset_secs.max(1).into());
+
+            return Err(response);
+        }
+        rate_headers = Some((remaining, reset_secs));
     }
 
     let mut response = next.run(req).await;
 
-    let headers = response.headers_mut();
-    headers.insert("X-RateLimit-Limit", limiter.limit.into());
-    headers.insert("X-RateLimit-Remaining", remaining.into());
-    headers.insert("X-RateLimit-Reset", reset_secs.into());
+    // Streamed responses are still in flight after the handler returns, so
+    // the slot is released only once the body has been sent or dropped.
+    if let Some(permit) = permit {
+        let (parts, body) = response.into_parts();
+        let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+            let _permit = &permit;
+            chunk
+        }));
+        response = Response::from_parts(parts, body);
+    }
+
+    if let Some((remaining, reset_secs)) = rate_headers {
+        let headers = response.headers_mut();
+        headers.insert("X-RateLimit-Limit", limit.into());
+        headers.insert("X-RateLimit-Remaining", remaining.into());
+        headers.insert("X-RateLimit-Reset", reset_secs.into());
+    }
 
     Ok(response)
 }
 
+/// Authenticates every route of `router` by API key and counts it against
+/// the caller's per-minute budget before the handler runs.
+pub fn rate_limited(router: Router<AppState>, state: &AppState) -> Router<AppState> {
+    router
+        .route_layer(middleware::from_fn_with_state(
+            state.rate_limiter.clone(),
+            rate_limit_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_api_key))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::users::UserLimits;
     use axum::{
         body::Body,
         http::{Request, StatusCode},
@@ -141,10 +383,16 @@ mod tests {
     fn test_user(id: i64) -> UserContext {
         UserContext {
             id,
+            api_key_id: id,
             name: format!("user{}", id),
             quota_tokens: None,
             used_tokens: 0,
             enabled: true,
+            limits: UserLimits::default(),
+            team_id: None,
+            scopes: Default::default(),
+            key_scopes: Default::default(),
+            model_aliases: Default::default(),
         }
     }
 
@@ -269,6 +517,151 @@ mod tests {
         assert_eq!(limit, 10);
     }
 
+    #[tokio::test]
+    async fn test_set_limit_applies_to_running_window() {
+        let limiter = Arc::new(RateLimiter::new(1));
+        let app = create_test_app(limiter.clone(), test_user(1));
+
+        let request = || Request::builder().uri("/test").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request()).await.unwrap().status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(RETRY_AFTER));
+
+        limiter.set_limit(3);
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("X-RateLimit-Limit").unwrap(), "3");
+        assert_eq!(response.headers().get("X-RateLimit-Remaining").unwrap(), "1");
+    }
+
+    #[tokio::test]
+    async fn test_user_rpm_override_takes_precedence() {
+        let limiter = Arc::new(RateLimiter::new(1));
+        let mut user = test_user(1);
+        user.limits.requests_per_minute = Some(3);
+        let app = create_test_app(limiter.clone(), user);
+
+        for _ in 0..3 {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri("/test").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get("X-RateLimit-Limit").unwrap(), "3");
+        }
+
+        let response = app
+            .oneshot(Request::builder().uri("/test").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_slot_held_until_body_dropped() {
+        let limiter = Arc::new(RateLimiter::new(60));
+        let mut user = test_user(1);
+        user.limits.max_concurrent_requests = Some(1);
+        let app = create_test_app(limiter.clone(), user);
+
+        let request = || Request::builder().uri("/test").body(Body::empty()).unwrap();
+        let first = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "CONCURRENCY_LIMITED");
+
+        let body = axum::body::to_bytes(first.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"ok");
+
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_global_concurrency_limit_applies_without_override() {
+        let limiter = Arc::new(RateLimiter::new(60));
+        limiter.set_concurrency_limit(2);
+
+        let first = limiter.try_acquire(1, limiter.concurrency_limit_for(&test_user(1)));
+        let second = limiter.try_acquire(1, 2);
+        assert!(first.is_some() && second.is_some());
+        assert!(limiter.try_acquire(1, 2).is_none());
+        assert!(limiter.try_acquire(2, 2).is_some());
+
+        drop(first);
+        assert!(limiter.try_acquire(1, 2).is_some());
+    }
+
+    #[test]
+    fn test_token_bucket_rejects_when_empty_and_reports_retry_after() {
+        let limiter = Arc::new(RateLimiter::new(60));
+
+        let first = limiter.reserve_tokens(1, 600, 500).unwrap();
+        first.settle(500);
+
+        // 100 tokens left, refilling at 10 per second
+        let retry_after = limiter.reserve_tokens(1, 600, 300).err().unwrap();
+        assert!((19..=20).contains(&retry_after), "retry_after = {}", retry_after);
+
+        assert!(limiter.reserve_tokens(2, 600, 300).is_ok());
+    }
+
+    #[test]
+    fn test_token_reservation_reconciles_with_actual_usage() {
+        let limiter = Arc::new(RateLimiter::new(60));
+
+        let reservation = limiter.reserve_tokens(1, 1000, 400).unwrap();
+        reservation.settle(100);
+        let available = limiter.available_tokens(1).unwrap();
+        assert!((900.0..901.0).contains(&available), "available = {}", available);
+
+        let reservation = limiter.reserve_tokens(1, 1000, 200).unwrap();
+        reservation.settle(800);
+        let available = limiter.available_tokens(1).unwrap();
+        assert!((100.0..102.0).contains(&available), "available = {}", available);
+
+        // Dropped without settling (upstream failure): refunded in full
+        drop(limiter.reserve_tokens(1, 1000, 50).unwrap());
+        let available = limiter.available_tokens(1).unwrap();
+        assert!((100.0..103.0).contains(&available), "available = {}", available);
+    }
+
+    #[test]
+    fn test_oversized_request_needs_full_bucket() {
+        let limiter = Arc::new(RateLimiter::new(60));
+
+        limiter.reserve_tokens(1, 100, 5000).unwrap().settle(5000);
+        assert!(limiter.reserve_tokens(1, 100, 1).is_err());
+        assert!(limiter.reserve_tokens(2, 100, 5000).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_zero_limit_disables_rate_limiting() {
+        let limiter = Arc::new(RateLimiter::new(0));
+        let app = create_test_app(limiter.clone(), test_user(1));
+
+        for _ in 0..5 {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri("/test").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(!response.headers().contains_key("X-RateLimit-Limit"));
+        }
+    }
+
     #[tokio::test]
     async fn test_after_window_expires_requests_succeed() {
         use std::time::Duration;
@@ -0,0 +1,413 @@
+//! OpenID Connect client for admin single sign-on: discovery, the
+//! authorization-code flow with PKCE, and ID token validation against the
+//! issuer's published keys.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{OnceCell, RwLock};
+
+use crate::db::admins::AdminRole;
+
+/// `oauth_states.provider` for SSO logins.
+pub const OIDC_PROVIDER: &str = "oidc";
+
+const DEFAULT_SCOPES: &str = "openid email profile";
+const DEFAULT_GROUPS_CLAIM: &str = "groups";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Must match the redirect URI registered with the IdP, normally
+    /// `https://<host>/api/auth/oidc/callback`.
+    pub redirect_url: String,
+    pub scopes: String,
+    /// Lowercase email domains allowed in; empty allows any the issuer vouches for.
+    pub allowed_domains: Vec<String>,
+    /// ID token claim holding the user's groups.
+    pub groups_claim: String,
+    /// IdP group to admin role. A user in several groups gets the highest role.
+    pub role_mapping: Vec<(String, AdminRole)>,
+    /// Role for users in no mapped group; `None` turns them away.
+    pub default_role: Option<AdminRole>,
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn parse_role(value: &str) -> Result<AdminRole> {
+    AdminRole::parse(value.trim())
+        .ok_or_else(|| anyhow!("unknown admin role '{}'; use viewer, admin or owner", value.trim()))
+}
+
+/// Parses `group=role` pairs separated by commas.
+pub fn parse_role_mapping(value: &str) -> Result<Vec<(String, AdminRole)>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (group, role) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected group=role, got '{}'", pair))?;
+            Ok((group.trim().to_string(), parse_role(role)?))
+        })
+        .collect()
+}
+
+impl OidcConfig {
+    /// Reads the `OIDC_*` env vars. `Ok(None)` when `OIDC_ISSUER_URL` is unset.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(issuer_url) = env_var("OIDC_ISSUER_URL") else {
+            return Ok(None);
+        };
+        let required = |name: &str| {
+            env_var(name).ok_or_else(|| anyhow!("{} is required when OIDC_ISSUER_URL is set", name))
+        };
+
+        let role_mapping = env_var("OIDC_ROLE_MAPPING")
+            .map(|v| parse_role_mapping(&v))
+            .transpose()
+            .context("Invalid OIDC_ROLE_MAPPING")?
+            .unwrap_or_default();
+        let default_role = env_var("OIDC_DEFAULT_ROLE")
+            .map(|v| parse_role(&v))
+            .transpose()
+            .context("Invalid OIDC_DEFAULT_ROLE")?;
+        if role_mapping.is_empty() && default_role.is_none() {
+            bail!("Set OIDC_ROLE_MAPPING and/or OIDC_DEFAULT_ROLE so SSO users get a role");
+        }
+
+        Ok(Some(Self {
+            issuer_url,
+            client_id: required("OIDC_CLIENT_ID")?,
+            client_secret: required("OIDC_CLIENT_SECRET")?,
+            redirect_url: required("OIDC_REDIRECT_URL")?,
+            scopes: env_var("OIDC_SCOPES").unwrap_or_else(|| DEFAULT_SCOPES.to_string()),
+            allowed_domains: env_var("OIDC_ALLOWED_DOMAINS")
+                .map(|v| {
+                    v.split(',')
+                        .map(|d| d.trim().trim_start_matches('@').to_ascii_lowercase())
+                        .filter(|d| !d.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            groups_claim: env_var("OIDC_GROUPS_CLAIM")
+                .unwrap_or_else(|| DEFAULT_GROUPS_CLAIM.to_string()),
+            role_mapping,
+            default_role,
+        }))
+    }
+
+    pub fn email_allowed(&self, email: &str) -> bool {
+        let Some((_, domain)) = email.rsplit_once('@') else {
+            return false;
+        };
+        self.allowed_domains.is_empty()
+            || self.allowed_domains.iter().any(|d| d.eq_ignore_ascii_case(domain))
+    }
+
+    pub fn role_for_groups(&self, groups: &[String]) -> Option<AdminRole> {
+        self.role_mapping
+            .iter()
+            .filter(|(group, _)| groups.contains(group))
+            .map(|(_, role)| *role)
+            .max()
+            .or(self.default_role)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl IdTokenClaims {
+    /// Group names from `claim`, which IdPs send as a list or a single string.
+    pub fn groups(&self, claim: &str) -> Vec<String> {
+        match self.extra.get(claim) {
+            Some(serde_json::Value::Array(items)) => items
+                .iter()
+                .filter_map(|g| g.as_str().map(str::to_string))
+                .collect(),
+            Some(serde_json::Value::String(group)) => vec![group.clone()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// A random URL-safe value for state, nonce or PKCE verifier.
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The S256 PKCE challenge for `verifier` (RFC 7636 section 4.2).
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    /// Discovered on first use, so startup does not depend on the IdP.
+    metadata: OnceCell<ProviderMetadata>,
+    jwks: RwLock<Option<JwkSet>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            metadata: OnceCell::new(),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    async fn metadata(&self) -> Result<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer_url.trim_end_matches('/')
+                );
+                let metadata: ProviderMetadata = self
+                    .http
+                    .get(&url)
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status())
+                    .with_context(|| format!("Failed to fetch {}", url))?
+                    .json()
+                    .await
+                    .context("Invalid discovery document")?;
+                if metadata.issuer.trim_end_matches('/') != self.config.issuer_url.trim_end_matches('/') {
+                    bail!(
+                        "Discovery document is for issuer '{}', expected '{}'",
+                        metadata.issuer,
+                        self.config.issuer_url
+                    );
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// Where to send the browser to sign in.
+    pub async fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> Result<String> {
+        let metadata = self.metadata().await?;
+        let url = reqwest::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .context("Invalid authorization_endpoint")?;
+        Ok(url.into())
+    }
+
+    /// Redeems an authorization code for the ID token. The client
+    /// authenticates with `client_secret_post`.
+    pub async fn exchange_code(&self, code: &str, code_verifier: &str) -> Result<String> {
+        let metadata = self.metadata().await?;
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await
+            .context("Token request failed")?;
+        let status = response.status();
+        let body: TokenResponse = response.json().await.context("Invalid token response")?;
+
+        if let Some(error) = body.error {
+            bail!(
+                "Token endpoint returned {}: {}",
+                error,
+                body.error_description.unwrap_or_default()
+            );
+        }
+        if !status.is_success() {
+            bail!("Token endpoint returned HTTP {}", status);
+        }
+        body.id_token
+            .ok_or_else(|| anyhow!("Token response has no id_token"))
+    }
+
+    /// Checks the ID token's signature, issuer, audience, expiry and nonce.
+    pub async fn verify_id_token(&self, id_token: &str, nonce: &str) -> Result<IdTokenClaims> {
+        let metadata = self.metadata().await?;
+        let header = jsonwebtoken::decode_header(id_token).context("Malformed ID token")?;
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            bail!("ID token must be signed with the issuer's key, not {:?}", header.alg);
+        }
+        let key = self.decoding_key(header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)
+            .context("ID token rejected")?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            bail!("ID token nonce does not match this login");
+        }
+        Ok(claims)
+    }
+
+    /// The signing key named `kid`, refetching the key set once if it is
+    /// unknown (the IdP may have rotated keys).
+    async fn decoding_key(&self, kid: Option<&str>) -> Result<DecodingKey> {
+        let find = |jwks: &JwkSet| {
+            match kid {
+                Some(kid) => jwks.find(kid),
+                None => jwks.keys.first(),
+            }
+            .map(DecodingKey::from_jwk)
+        };
+
+        if let Some(jwks) = self.jwks.read().await.as_ref() {
+            if let Some(key) = find(jwks) {
+                return Ok(key?);
+            }
+        }
+
+        let metadata = self.metadata().await?;
+        let jwks: JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Failed to fetch JWKS")?
+            .json()
+            .await
+            .context("Invalid JWKS")?;
+        let key = find(&jwks);
+        *self.jwks.write().await = Some(jwks);
+        Ok(key.ok_or_else(|| anyhow!("No signing key matches kid {:?}", kid))??)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> OidcConfig {
+        OidcConfig {
+            issuer_url: "https://idp.example.com".to_string(),
+            client_id: "proxypal".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_url: "https://proxypal.example.com/api/auth/oidc/callback".to_string(),
+            scopes: DEFAULT_SCOPES.to_string(),
+            allowed_domains: vec!["example.com".to_string()],
+            groups_claim: DEFAULT_GROUPS_CLAIM.to_string(),
+            role_mapping: parse_role_mapping("ops=admin, leads=owner,staff=viewer").unwrap(),
+            default_role: None,
+        }
+    }
+
+    #[test]
+    fn highest_mapped_group_wins() {
+        let mut config = config();
+        let groups = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(config.role_for_groups(&groups(&["staff", "ops"])), Some(AdminRole::Admin));
+        assert_eq!(config.role_for_groups(&groups(&["leads"])), Some(AdminRole::Owner));
+        assert_eq!(config.role_for_groups(&groups(&["sales"])), None);
+
+        config.default_role = Some(AdminRole::Viewer);
+        assert_eq!(config.role_for_groups(&[]), Some(AdminRole::Viewer));
+        assert!(parse_role_mapping("ops=superuser").is_err());
+        assert!(parse_role_mapping("ops").is_err());
+    }
+
+    #[test]
+    fn email_domains_are_checked_case_insensitively() {
+        let mut config = config();
+        assert!(config.email_allowed("jane@Example.com"));
+        assert!(!config.email_allowed("jane@example.com.evil.io"));
+        assert!(!config.email_allowed("not-an-email"));
+
+        config.allowed_domains.clear();
+        assert!(config.email_allowed("jane@anywhere.io"));
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc_7636_example() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert_eq!(random_token().len(), 43);
+    }
+
+    #[test]
+    fn groups_claim_accepts_list_or_string() {
+        let claims: IdTokenClaims = serde_json::from_value(serde_json::json!({
+            "sub": "u1",
+            "groups": ["ops", 7, "staff"],
+            "roles": "leads",
+        }))
+        .unwrap();
+        assert_eq!(claims.groups("groups"), vec!["ops", "staff"]);
+        assert_eq!(claims.groups("roles"), vec!["leads"]);
+        assert!(claims.groups("missing").is_empty());
+    }
+}
@@ -0,0 +1,297 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::admin_tokens::AdminToken;
+use crate::db::admins::AdminRole;
+use crate::db::audit::AuditEvent;
+use crate::db::quota::format_db_time;
+use crate::middleware::admin_auth::AdminSession;
+use crate::middleware::client_ip::ClientIp;
+use crate::routes::audit;
+use crate::AppState;
+
+#[derive(Debug)]
+pub enum TokenError {
+    NotFound,
+    LoginRequired,
+    Validation(String),
+    DatabaseError(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub error: String,
+    pub code: String,
+}
+
+impl IntoResponse for TokenError {
+    fn into_response(self) -> axum::response::Response {
+        let (status, error, code) = match self {
+            TokenError::NotFound => (
+                StatusCode::NOT_FOUND,
+                "Token not found".to_string(),
+                "NOT_FOUND".to_string(),
+            ),
+            TokenError::LoginRequired => (
+                StatusCode::FORBIDDEN,
+                "Tokens can only be created from a login session".to_string(),
+                "FORBIDDEN".to_string(),
+            ),
+            TokenError::Validation(msg) => (
+                StatusCode::BAD_REQUEST,
+                msg,
+                "VALIDATION_ERROR".to_string(),
+            ),
+            TokenError::DatabaseError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+                "INTERNAL_ERROR".to_string(),
+            ),
+        };
+
+        let body = Json(ErrorResponse {
+            success: false,
+            error,
+            code,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTokenRequest {
+    name: String,
+    /// Defaults to the caller's own role, which is also the most allowed.
+    role: Option<AdminRole>,
+    /// RFC 3339 timestamp; omitted tokens never expire.
+    expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListTokensQuery {
+    #[serde(default)]
+    all: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTokensResponse {
+    tokens: Vec<AdminToken>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTokenResponse {
+    #[serde(flatten)]
+    token: AdminToken,
+    /// The secret, shown only in this response.
+    secret: String,
+}
+
+/// The caller's tokens, or everyone's for an owner passing `all`.
+pub async fn list_tokens(
+    session: AdminSession,
+    State(state): State<AppState>,
+    Query(query): Query<ListTokensQuery>,
+) -> Result<Json<ListTokensResponse>, TokenError> {
+    let admin_id = (!(query.all && session.has_role(AdminRole::Owner))).then_some(session.admin.id);
+    let tokens = state
+        .db
+        .list_admin_tokens(admin_id)
+        .map_err(|e| TokenError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(ListTokensResponse { tokens }))
+}
+
+/// Issues a token for the caller. Tokens cannot mint further tokens, so a
+/// leaked one cannot be used to outlive its own revocation.
+pub async fn create_token(
+    session: AdminSession,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(payload): Json<CreateTokenRequest>,
+) -> Result<(StatusCode, Json<CreateTokenResponse>), TokenError> {
+    if session.token.is_some() {
+        return Err(TokenError::LoginRequired);
+    }
+
+    let name = payload.name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err(TokenError::Validation(
+            "Token name must be 1-64 characters".to_string(),
+        ));
+    }
+    let role = payload.role.unwrap_or(session.role());
+    if role > session.role() {
+        return Err(TokenError::Validation(format!(
+            "role cannot exceed your own ({})",
+            session.role().as_str()
+        )));
+    }
+    let expires_at = payload
+        .expires_at
+        .as_deref()
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|t| format_db_time(t.with_timezone(&Utc)))
+                .map_err(|_| TokenError::Validation(format!("Invalid expiresAt timestamp: {}", value)))
+        })
+        .transpose()?;
+
+    let (token, secret) = state
+        .db
+        .create_admin_token(session.admin.id, name, role, expires_at.as_deref())
+        .map_err(|e| TokenError::DatabaseError(e.to_string()))?;
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("admin_token.create").target("admin_token", token.id).after(&token),
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateTokenResponse { token, secret }),
+    ))
+}
+
+/// Revokes a token. Admins may revoke their own; owners anyone's.
+pub async fn revoke_token(
+    session: AdminSession,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<AdminToken>, TokenError> {
+    let admin_id = (!session.has_role(AdminRole::Owner)).then_some(session.admin.id);
+    let token = state
+        .db
+        .revoke_admin_token(id, admin_id)
+        .map_err(|e| TokenError::DatabaseError(e.to_string()))?
+        .ok_or(TokenError::NotFound)?;
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("admin_token.revoke").target("admin_token", id),
+    );
+    Ok(Json(token))
+}
+
+/// Mounted under `/api/auth/tokens`.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_tokens).post(create_token))
+        .route("/:id", delete(revoke_token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cliproxy::{
+        MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe,
+    };
+    use crate::db::Database;
+    use crate::middleware::key_cache::ApiKeyCache;
+    use crate::middleware::rate_limit::RateLimiter;
+    use axum::{body::Body, http::Request};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn create_app(db: Database) -> Router {
+        let state = AppState {
+            db,
+            rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
+            proxy_client: Arc::new(MockProxyManagementClient::default()),
+            proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
+        };
+        Router::new()
+            .nest("/api/auth/tokens", router())
+            .nest("/api/users", crate::routes::users::router())
+            .with_state(state)
+    }
+
+    fn request(method: &str, uri: &str, auth: (&str, String), body: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(auth.0, auth.1);
+        if body.is_some() {
+            builder = builder.header("Content-Type", "application/json");
+        }
+        builder
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or(Body::empty()))
+            .unwrap()
+    }
+
+    async fn json_body(response: axum::response::Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_token_is_scoped_to_its_role_and_revocable() {
+        let db = Database::new_in_memory().unwrap();
+        let admin = db.create_admin("ops", "hash", AdminRole::Admin).unwrap();
+        db.create_session("ops-session", "csrf", admin.id, 7).unwrap();
+        let app = create_app(db);
+        let cookie = ("Cookie", "session=ops-session".to_string());
+
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/api/auth/tokens", cookie.clone(), Some(r#"{"name":"ci","role":"owner"}"#)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/api/auth/tokens", cookie.clone(), Some(r#"{"name":"ci","role":"viewer"}"#)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created = json_body(response).await;
+        let bearer = ("Authorization", format!("Bearer {}", created["secret"].as_str().unwrap()));
+
+        let response = app
+            .clone()
+            .oneshot(request("GET", "/api/users", bearer.clone(), None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/api/users", bearer.clone(), Some(r#"{"name":"alice"}"#)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/api/auth/tokens", bearer.clone(), Some(r#"{"name":"more"}"#)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let uri = format!("/api/auth/tokens/{}", created["id"]);
+        let response = app.clone().oneshot(request("DELETE", &uri, cookie, None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.oneshot(request("GET", "/api/users", bearer, None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,453 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::admins::{hash_password, Admin, AdminRole};
+use crate::db::audit::AuditEvent;
+use crate::middleware::admin_auth::RequireOwner;
+use crate::middleware::client_ip::ClientIp;
+use crate::routes::audit;
+use crate::AppState;
+
+const MIN_PASSWORD_LEN: usize = 8;
+
+#[derive(Debug)]
+pub enum AdminError {
+    NotFound,
+    Conflict(String),
+    Validation(String),
+    LastOwner,
+    DatabaseError(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub error: String,
+    pub code: String,
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> axum::response::Response {
+        let (status, error, code) = match self {
+            AdminError::NotFound => (
+                StatusCode::NOT_FOUND,
+                "Admin not found".to_string(),
+                "NOT_FOUND".to_string(),
+            ),
+            AdminError::Conflict(msg) => (StatusCode::CONFLICT, msg, "CONFLICT".to_string()),
+            AdminError::Validation(msg) => (
+                StatusCode::BAD_REQUEST,
+                msg,
+                "VALIDATION_ERROR".to_string(),
+            ),
+            AdminError::LastOwner => (
+                StatusCode::CONFLICT,
+                "At least one owner account is required".to_string(),
+                "LAST_OWNER".to_string(),
+            ),
+            AdminError::DatabaseError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+                "INTERNAL_ERROR".to_string(),
+            ),
+        };
+
+        let body = Json(ErrorResponse {
+            success: false,
+            error,
+            code,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListAdminsResponse {
+    admins: Vec<Admin>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAdminRequest {
+    username: String,
+    password: String,
+    role: AdminRole,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateAdminRequest {
+    role: Option<AdminRole>,
+    password: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteResponse {
+    success: bool,
+}
+
+fn validate_password(password: &str) -> Result<(), AdminError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(AdminError::Validation(format!(
+            "password must be at least {} characters",
+            MIN_PASSWORD_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Rejects changes that would take the owner role away from `admin` when
+/// they are the only owner.
+fn ensure_other_owner(state: &AppState, admin: &Admin) -> Result<(), AdminError> {
+    if admin.role != AdminRole::Owner {
+        return Ok(());
+    }
+    let owners = state
+        .db
+        .count_owners()
+        .map_err(|e| AdminError::DatabaseError(e.to_string()))?;
+    if owners <= 1 {
+        return Err(AdminError::LastOwner);
+    }
+    Ok(())
+}
+
+fn load_admin(state: &AppState, id: i64) -> Result<Admin, AdminError> {
+    state
+        .db
+        .get_admin(id)
+        .map_err(|e| AdminError::DatabaseError(e.to_string()))?
+        .ok_or(AdminError::NotFound)
+}
+
+pub async fn list_admins(
+    _session: RequireOwner,
+    State(state): State<AppState>,
+) -> Result<Json<ListAdminsResponse>, AdminError> {
+    let admins = state
+        .db
+        .list_admins()
+        .map_err(|e| AdminError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(ListAdminsResponse { admins }))
+}
+
+pub async fn create_admin(
+    session: RequireOwner,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(payload): Json<CreateAdminRequest>,
+) -> Result<(StatusCode, Json<Admin>), AdminError> {
+    if payload.username.trim().is_empty() {
+        return Err(AdminError::Validation("username must not be empty".to_string()));
+    }
+    validate_password(&payload.password)?;
+
+    let hash = hash_password(&payload.password)
+        .map_err(|e| AdminError::DatabaseError(e.to_string()))?;
+    let admin = state
+        .db
+        .create_admin(&payload.username, &hash, payload.role)
+        .map_err(|e| {
+            let msg = e.to_string();
+            if msg.contains("already exists") {
+                AdminError::Conflict(msg)
+            } else {
+                AdminError::DatabaseError(msg)
+            }
+        })?;
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("admin.create").target("admin", admin.id).after(&admin),
+    );
+    Ok((StatusCode::CREATED, Json(admin)))
+}
+
+pub async fn update_admin(
+    session: RequireOwner,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(payload): Json<UpdateAdminRequest>,
+) -> Result<Json<Admin>, AdminError> {
+    let before = load_admin(&state, id)?;
+    if payload.role.is_some_and(|role| role != AdminRole::Owner) {
+        ensure_other_owner(&state, &before)?;
+    }
+    if let Some(password) = payload.password.as_deref() {
+        validate_password(password)?;
+    }
+
+    let hash = payload
+        .password
+        .as_deref()
+        .map(hash_password)
+        .transpose()
+        .map_err(|e| AdminError::DatabaseError(e.to_string()))?;
+    let admin = state
+        .db
+        .update_admin(id, payload.role, hash.as_deref())
+        .map_err(|e| AdminError::DatabaseError(e.to_string()))?
+        .ok_or(AdminError::NotFound)?;
+
+    // The hash is not part of `Admin`; log that the password changed, not what to
+    let with_password = |admin: &Admin, changed: bool| {
+        let mut value = serde_json::to_value(admin).unwrap_or_default();
+        value["passwordChanged"] = changed.into();
+        value
+    };
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("admin.update")
+            .target("admin", id)
+            .before(&with_password(&before, false))
+            .after(&with_password(&admin, hash.is_some())),
+    );
+    Ok(Json(admin))
+}
+
+pub async fn delete_admin(
+    session: RequireOwner,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<DeleteResponse>, AdminError> {
+    let admin = load_admin(&state, id)?;
+    ensure_other_owner(&state, &admin)?;
+
+    let deleted = state
+        .db
+        .delete_admin(id)
+        .map_err(|e| AdminError::DatabaseError(e.to_string()))?;
+    if !deleted {
+        return Err(AdminError::NotFound);
+    }
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("admin.delete").target("admin", id).before(&admin),
+    );
+    Ok(Json(DeleteResponse { success: true }))
+}
+
+/// Turns off TOTP for an admin who lost their authenticator and recovery codes.
+pub async fn reset_admin_totp(
+    session: RequireOwner,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Admin>, AdminError> {
+    let disabled = state
+        .db
+        .disable_admin_totp(id)
+        .map_err(|e| AdminError::DatabaseError(e.to_string()))?;
+    if !disabled {
+        return Err(AdminError::NotFound);
+    }
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("admin.totp_reset").target("admin", id),
+    );
+    Ok(Json(load_admin(&state, id)?))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_admins).post(create_admin))
+        .route("/:id", put(update_admin).delete(delete_admin))
+        .route("/:id/totp", delete(reset_admin_totp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    fn create_app(db: Database) -> Router {
+        use std::sync::Arc;
+        use crate::middleware::key_cache::ApiKeyCache;
+        use crate::middleware::rate_limit::RateLimiter;
+        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
+
+        let state = AppState {
+            db,
+            rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
+            proxy_client: Arc::new(MockProxyManagementClient::default()),
+            proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
+        };
+        Router::new()
+            .nest("/api/admins", router())
+            .nest("/api/users", crate::routes::users::router())
+            .nest("/api/config", crate::routes::config::router())
+            .with_state(state)
+    }
+
+    /// Creates an admin with `role` and a session for them.
+    fn login_as(db: &Database, username: &str, role: AdminRole) -> (Admin, String) {
+        let admin = db.create_admin(username, "", role).unwrap();
+        let session_id = format!("session-{}", username);
+        db.create_session(&session_id, "csrf-token", admin.id, 7).unwrap();
+        (admin, session_id)
+    }
+
+    fn authed_request(method: &str, uri: &str, session_id: &str, body: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Cookie", format!("session={}", session_id));
+
+        if body.is_some() {
+            builder = builder.header("Content-Type", "application/json");
+        }
+
+        builder
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or(Body::empty()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_roles_gate_routes() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, _) = db.create_user("alice", None).unwrap();
+        let (_, viewer) = login_as(&db, "auditor", AdminRole::Viewer);
+        let (_, admin) = login_as(&db, "operator", AdminRole::Admin);
+        let (_, owner) = login_as(&db, "root", AdminRole::Owner);
+        let app = create_app(db);
+
+        let cases = [
+            ("GET", "/api/users".to_string(), &viewer, None, StatusCode::OK),
+            ("DELETE", format!("/api/users/{}", user.id), &viewer, None, StatusCode::FORBIDDEN),
+            ("GET", "/api/config".to_string(), &viewer, None, StatusCode::OK),
+            ("PUT", "/api/config".to_string(), &admin, Some("{}"), StatusCode::FORBIDDEN),
+            ("GET", "/api/admins".to_string(), &admin, None, StatusCode::FORBIDDEN),
+            ("GET", "/api/admins".to_string(), &owner, None, StatusCode::OK),
+            ("DELETE", format!("/api/users/{}", user.id), &admin, None, StatusCode::OK),
+        ];
+        for (method, uri, session, body, expected) in cases {
+            let response = app
+                .clone()
+                .oneshot(authed_request(method, &uri, session, body))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), expected, "{} {} as {}", method, uri, session);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_owner_manages_admins_but_keeps_an_owner() {
+        let db = Database::new_in_memory().unwrap();
+        let (root, owner) = login_as(&db, "root", AdminRole::Owner);
+        let app = create_app(db.clone());
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "POST",
+                "/api/admins",
+                &owner,
+                Some(r#"{"username":"operator","password":"long-enough","role":"admin"}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let created: Admin = serde_json::from_slice(&body).unwrap();
+        assert_eq!(created.role, AdminRole::Admin);
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "POST",
+                "/api/admins",
+                &owner,
+                Some(r#"{"username":"short","password":"abc","role":"viewer"}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // The only owner can neither step down nor be removed
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "PUT",
+                &format!("/api/admins/{}", root.id),
+                &owner,
+                Some(r#"{"role":"admin"}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = app
+            .clone()
+            .oneshot(authed_request("DELETE", &format!("/api/admins/{}", root.id), &owner, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = app
+            .oneshot(authed_request(
+                "PUT",
+                &format!("/api/admins/{}", created.id),
+                &owner,
+                Some(r#"{"role":"owner"}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db.count_owners().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_owner_resets_admin_totp() {
+        let db = Database::new_in_memory().unwrap();
+        let (_, owner) = login_as(&db, "root", AdminRole::Owner);
+        let (operator, admin) = login_as(&db, "operator", AdminRole::Admin);
+        db.set_admin_totp_secret(operator.id, "encrypted").unwrap();
+        db.enable_admin_totp(operator.id, 1, &["hash".to_string()]).unwrap();
+        let app = create_app(db.clone());
+
+        let uri = format!("/api/admins/{}/totp", operator.id);
+        let response = app
+            .clone()
+            .oneshot(authed_request("DELETE", &uri, &admin, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .oneshot(authed_request("DELETE", &uri, &owner, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let reset: Admin = serde_json::from_slice(&body).unwrap();
+        assert!(!reset.totp_enabled);
+        assert_eq!(db.count_unused_recovery_codes(operator.id).unwrap(), 0);
+    }
+}
@@ -0,0 +1,602 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::api_keys::ApiKey;
+use crate::db::audit::AuditEvent;
+use crate::db::quota::format_db_time;
+use crate::db::scopes::Scopes;
+use crate::middleware::admin_auth::{AdminSession, RequireAdmin};
+use crate::middleware::client_ip::ClientIp;
+use crate::routes::audit;
+use crate::routes::users::explicit_null;
+use crate::AppState;
+
+#[derive(Debug)]
+pub enum KeyError {
+    UserNotFound,
+    NotFound,
+    Validation(String),
+    DatabaseError(String),
+}
+
+impl IntoResponse for KeyError {
+    fn into_response(self) -> axum::response::Response {
+        let (status, code, message) = match self {
+            Self::UserNotFound => (
+                StatusCode::NOT_FOUND,
+                "NOT_FOUND",
+                "User not found".to_string(),
+            ),
+            Self::NotFound => (
+                StatusCode::NOT_FOUND,
+                "NOT_FOUND",
+                "API key not found".to_string(),
+            ),
+            Self::Validation(msg) => (StatusCode::BAD_REQUEST, "VALIDATION_ERROR", msg),
+            Self::DatabaseError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "INTERNAL_ERROR",
+                format!("Database error: {}", e),
+            ),
+        };
+
+        let body = serde_json::json!({
+            "success": false,
+            "error": message,
+            "code": code
+        });
+
+        (status, Json(body)).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateKeyRequest {
+    name: String,
+    /// RFC 3339 timestamp; omitted keys never expire.
+    expires_at: Option<String>,
+    #[serde(default)]
+    scopes: Scopes,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateKeyRequest {
+    name: Option<String>,
+    /// `null` removes the expiry; omitting the field leaves it unchanged.
+    #[serde(default, deserialize_with = "explicit_null")]
+    expires_at: Option<Option<String>>,
+    /// `null` lifts the key's own restrictions (the user's still apply).
+    #[serde(default, deserialize_with = "explicit_null")]
+    scopes: Option<Option<Scopes>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListKeysResponse {
+    keys: Vec<ApiKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateKeyResponse {
+    #[serde(flatten)]
+    key: ApiKey,
+    api_key: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteResponse {
+    success: bool,
+}
+
+fn validate_name(name: &str) -> Result<&str, KeyError> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err(KeyError::Validation(
+            "Key name must be 1-64 characters".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+fn parse_expires_at(value: &str) -> Result<String, KeyError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| format_db_time(t.with_timezone(&Utc)))
+        .map_err(|_| KeyError::Validation(format!("Invalid expiresAt timestamp: {}", value)))
+}
+
+fn ensure_user_exists(state: &AppState, user_id: i64) -> Result<(), KeyError> {
+    state
+        .db
+        .get_user_by_id(user_id)
+        .map_err(|e| KeyError::DatabaseError(e.to_string()))?
+        .map(|_| ())
+        .ok_or(KeyError::UserNotFound)
+}
+
+pub async fn list_keys(
+    _session: AdminSession,
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> Result<Json<ListKeysResponse>, KeyError> {
+    ensure_user_exists(&state, user_id)?;
+    let keys = state
+        .db
+        .list_api_keys(user_id)
+        .map_err(|e| KeyError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(ListKeysResponse { keys }))
+}
+
+pub async fn create_key(
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+    Json(payload): Json<CreateKeyRequest>,
+) -> Result<(StatusCode, Json<CreateKeyResponse>), KeyError> {
+    let name = validate_name(&payload.name)?;
+    let expires_at = payload
+        .expires_at
+        .as_deref()
+        .map(parse_expires_at)
+        .transpose()?;
+    payload.scopes.validate().map_err(KeyError::Validation)?;
+
+    let (mut key, api_key) = state
+        .db
+        .create_api_key(user_id, name, expires_at.as_deref())
+        .map_err(|e| KeyError::DatabaseError(e.to_string()))?
+        .ok_or(KeyError::UserNotFound)?;
+
+    if !payload.scopes.is_unrestricted() {
+        key = state
+            .db
+            .set_api_key_scopes(user_id, key.id, &payload.scopes)
+            .map_err(|e| KeyError::DatabaseError(e.to_string()))?
+            .ok_or(KeyError::NotFound)?;
+    }
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("api_key.create").target("api_key", key.id).after(&key),
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateKeyResponse { key, api_key }),
+    ))
+}
+
+pub async fn get_key(
+    _session: AdminSession,
+    State(state): State<AppState>,
+    Path((user_id, key_id)): Path<(i64, i64)>,
+) -> Result<Json<ApiKey>, KeyError> {
+    let key = state
+        .db
+        .get_api_key(user_id, key_id)
+        .map_err(|e| KeyError::DatabaseError(e.to_string()))?
+        .ok_or(KeyError::NotFound)?;
+
+    Ok(Json(key))
+}
+
+pub async fn update_key(
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path((user_id, key_id)): Path<(i64, i64)>,
+    Json(payload): Json<UpdateKeyRequest>,
+) -> Result<Json<ApiKey>, KeyError> {
+    let name = payload.name.as_deref().map(validate_name).transpose()?;
+    let expires_at = match payload.expires_at {
+        Some(Some(value)) => Some(Some(parse_expires_at(&value)?)),
+        Some(None) => Some(None),
+        None => None,
+    };
+    if let Some(Some(scopes)) = &payload.scopes {
+        scopes.validate().map_err(KeyError::Validation)?;
+    }
+    let before = state
+        .db
+        .get_api_key(user_id, key_id)
+        .map_err(|e| KeyError::DatabaseError(e.to_string()))?
+        .ok_or(KeyError::NotFound)?;
+
+    let mut key = state
+        .db
+        .update_api_key(
+            user_id,
+            key_id,
+            name,
+            expires_at.as_ref().map(|e| e.as_deref()),
+        )
+        .map_err(|e| KeyError::DatabaseError(e.to_string()))?
+        .ok_or(KeyError::NotFound)?;
+
+    if let Some(scopes) = payload.scopes {
+        key = state
+            .db
+            .set_api_key_scopes(user_id, key_id, &scopes.unwrap_or_default())
+            .map_err(|e| KeyError::DatabaseError(e.to_string()))?
+            .ok_or(KeyError::NotFound)?;
+    }
+    state.key_cache.invalidate_key(key.id);
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("api_key.update").target("api_key", key_id).before(&before).after(&key),
+    );
+    Ok(Json(key))
+}
+
+pub async fn revoke_key(
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path((user_id, key_id)): Path<(i64, i64)>,
+) -> Result<Json<ApiKey>, KeyError> {
+    let key = state
+        .db
+        .revoke_api_key(user_id, key_id)
+        .map_err(|e| KeyError::DatabaseError(e.to_string()))?
+        .ok_or(KeyError::NotFound)?;
+    state.key_cache.invalidate_key(key.id);
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("api_key.revoke").target("api_key", key_id),
+    );
+    Ok(Json(key))
+}
+
+pub async fn delete_key(
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path((user_id, key_id)): Path<(i64, i64)>,
+) -> Result<Json<DeleteResponse>, KeyError> {
+    let deleted = state
+        .db
+        .delete_api_key(user_id, key_id)
+        .map_err(|e| KeyError::DatabaseError(e.to_string()))?;
+    state.key_cache.invalidate_key(key_id);
+
+    if !deleted {
+        return Err(KeyError::NotFound);
+    }
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("api_key.delete").target("api_key", key_id),
+    );
+    Ok(Json(DeleteResponse { success: true }))
+}
+
+/// Mounted under `/api/users/:id/keys`.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_keys).post(create_key))
+        .route("/:key_id", get(get_key).put(update_key).delete(delete_key))
+        .route("/:key_id/revoke", post(revoke_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cliproxy::{
+        MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe,
+    };
+    use crate::db::Database;
+    use crate::middleware::api_key_auth::ApiKeyAuth;
+    use crate::middleware::key_cache::ApiKeyCache;
+    use crate::middleware::rate_limit::RateLimiter;
+    use axum::{body::Body, http::Request};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn create_app(db: Database) -> (Router, String) {
+        create_app_with_cache(db, Arc::new(ApiKeyCache::default()))
+    }
+
+    fn create_app_with_cache(db: Database, key_cache: Arc<ApiKeyCache>) -> (Router, String) {
+        let session_id = "test-session-id";
+        let admin = db
+            .create_admin("admin", "", crate::db::admins::AdminRole::Owner)
+            .unwrap();
+        db.create_session(session_id, "test-csrf-token", admin.id, 7).unwrap();
+
+        let state = AppState {
+            db,
+            rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache,
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
+            proxy_client: Arc::new(MockProxyManagementClient::default()),
+            proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
+        };
+        let app = Router::new()
+            .nest("/api/users", crate::routes::users::router())
+            .route("/v1/whoami", get(whoami))
+            .with_state(state);
+
+        (app, session_id.to_string())
+    }
+
+    async fn whoami(ApiKeyAuth { user }: ApiKeyAuth) -> Json<serde_json::Value> {
+        Json(serde_json::json!({ "userId": user.id, "keyId": user.api_key_id }))
+    }
+
+    fn authed_request(
+        method: &str,
+        uri: &str,
+        session_id: &str,
+        body: Option<&str>,
+    ) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Cookie", format!("session={}", session_id));
+        if body.is_some() {
+            builder = builder.header("Content-Type", "application/json");
+        }
+        builder
+            .body(
+                body.map(|b| Body::from(b.to_string()))
+                    .unwrap_or(Body::empty()),
+            )
+            .unwrap()
+    }
+
+    fn key_request(api_key: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/v1/whoami")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn json_body<T: serde::de::DeserializeOwned>(response: axum::response::Response) -> T {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_old_and_new_keys_overlap_until_old_is_revoked() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, old_key) = db.create_user("agent", None).unwrap();
+        let (app, session_id) = create_app(db);
+
+        let request = authed_request(
+            "POST",
+            &format!("/api/users/{}/keys", user.id),
+            &session_id,
+            Some(r#"{"name":"rotation-2025"}"#),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created: CreateKeyResponse = json_body(response).await;
+        assert_eq!(created.key.name, "rotation-2025");
+        assert!(created.key.active);
+
+        let response = app.clone().oneshot(key_request(&old_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .clone()
+            .oneshot(key_request(&created.api_key))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value = json_body(response).await;
+        assert_eq!(json["keyId"], created.key.id);
+
+        let request = authed_request(
+            "GET",
+            &format!("/api/users/{}/keys", user.id),
+            &session_id,
+            None,
+        );
+        let keys: ListKeysResponse = json_body(app.clone().oneshot(request).await.unwrap()).await;
+        assert_eq!(keys.keys.len(), 2);
+        let old = keys.keys.iter().find(|k| k.name == "default").unwrap();
+        assert!(old.last_used_at.is_some());
+
+        let request = authed_request(
+            "POST",
+            &format!("/api/users/{}/keys/{}/revoke", user.id, old.id),
+            &session_id,
+            None,
+        );
+        let revoked: ApiKey = json_body(app.clone().oneshot(request).await.unwrap()).await;
+        assert!(!revoked.active);
+
+        let response = app.clone().oneshot(key_request(&old_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.oneshot(key_request(&created.api_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_expired_key_is_rejected_and_expiry_can_be_cleared() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, _) = db.create_user("agent", None).unwrap();
+        let (app, session_id) = create_app(db);
+
+        let request = authed_request(
+            "POST",
+            &format!("/api/users/{}/keys", user.id),
+            &session_id,
+            Some(r#"{"name":"temp","expiresAt":"2001-01-01T00:00:00Z"}"#),
+        );
+        let created: CreateKeyResponse =
+            json_body(app.clone().oneshot(request).await.unwrap()).await;
+        assert_eq!(
+            created.key.expires_at.as_deref(),
+            Some("2001-01-01 00:00:00")
+        );
+        assert!(!created.key.active);
+
+        let response = app
+            .clone()
+            .oneshot(key_request(&created.api_key))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let request = authed_request(
+            "PUT",
+            &format!("/api/users/{}/keys/{}", user.id, created.key.id),
+            &session_id,
+            Some(r#"{"expiresAt":null}"#),
+        );
+        let updated: ApiKey = json_body(app.clone().oneshot(request).await.unwrap()).await;
+        assert!(updated.expires_at.is_none());
+
+        let response = app.oneshot(key_request(&created.api_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_key_routes_validate_input_and_ownership() {
+        let db = Database::new_in_memory().unwrap();
+        let (alice, _) = db.create_user("alice", None).unwrap();
+        let (bob, _) = db.create_user("bob", None).unwrap();
+        let alice_key = db.list_api_keys(alice.id).unwrap()[0].id;
+        let (app, session_id) = create_app(db);
+
+        let request = authed_request(
+            "POST",
+            &format!("/api/users/{}/keys", alice.id),
+            &session_id,
+            Some(r#"{"name":"x","expiresAt":"tomorrow"}"#),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = authed_request(
+            "POST",
+            "/api/users/9999/keys",
+            &session_id,
+            Some(r#"{"name":"x"}"#),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = authed_request(
+            "DELETE",
+            &format!("/api/users/{}/keys/{}", bob.id, alice_key),
+            &session_id,
+            None,
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = authed_request(
+            "DELETE",
+            &format!("/api/users/{}/keys/{}", alice.id, alice_key),
+            &session_id,
+            None,
+        );
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_cached_keys_are_dropped_on_regenerate_disable_and_delete() {
+        let db = Database::new_in_memory().unwrap();
+        let (alice, alice_key) = db.create_user("alice", None).unwrap();
+        let (bob, bob_key) = db.create_user("bob", None).unwrap();
+        let (carol, carol_key) = db.create_user("carol", None).unwrap();
+        let cache = Arc::new(ApiKeyCache::default());
+        let (app, session_id) = create_app_with_cache(db, cache.clone());
+
+        for key in [&alice_key, &bob_key, &carol_key, &alice_key] {
+            let response = app.clone().oneshot(key_request(key)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 3, 3));
+
+        let uri = format!("/api/users/{}/regenerate-key", alice.id);
+        let response = app
+            .clone()
+            .oneshot(authed_request("POST", &uri, &session_id, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let uri = format!("/api/users/{}", bob.id);
+        let request = authed_request("PUT", &uri, &session_id, Some(r#"{"enabled":false}"#));
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        let uri = format!("/api/users/{}", carol.id);
+        let request = authed_request("DELETE", &uri, &session_id, None);
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        assert_eq!(cache.stats().entries, 0);
+        let response = app.clone().oneshot(key_request(&alice_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.clone().oneshot(key_request(&bob_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app.oneshot(key_request(&carol_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_key_scopes_can_be_set_and_cleared() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, _) = db.create_user("agent", None).unwrap();
+        let (app, session_id) = create_app(db);
+        let uri = format!("/api/users/{}/keys", user.id);
+
+        let request = authed_request(
+            "POST",
+            &uri,
+            &session_id,
+            Some(r#"{"name":"bad","scopes":{"models":[""]}}"#),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = authed_request(
+            "POST",
+            &uri,
+            &session_id,
+            Some(r#"{"name":"embed","scopes":{"endpoints":["embeddings"],"models":["text-embedding-*"]}}"#),
+        );
+        let created: CreateKeyResponse = json_body(app.clone().oneshot(request).await.unwrap()).await;
+        assert_eq!(
+            created.key.scopes.endpoints,
+            Some(vec![crate::db::scopes::EndpointFamily::Embeddings])
+        );
+
+        let request = authed_request(
+            "PUT",
+            &format!("{}/{}", uri, created.key.id),
+            &session_id,
+            Some(r#"{"scopes":null}"#),
+        );
+        let updated: ApiKey = json_body(app.oneshot(request).await.unwrap()).await;
+        assert!(updated.scopes.is_unrestricted());
+    }
+}
@@ -0,0 +1,233 @@
+use axum::{
+    extract::{Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::db::admins::Admin;
+use crate::db::audit::{AuditEntry, AuditEvent, AuditFilter};
+use crate::db::quota::parse_db_time;
+use crate::middleware::admin_auth::RequireAdmin;
+use crate::AppState;
+
+/// Most rows one CSV export returns.
+const MAX_EXPORT_ROWS: u32 = 10_000;
+
+/// Logs an admin action. A failure to write is logged rather than failing
+/// the action, which has already happened.
+pub fn record(state: &AppState, actor: &Admin, ip: &str, event: AuditEvent) {
+    if let Err(e) = state.db.record_audit(Some(actor), Some(ip), &event) {
+        tracing::warn!("Failed to write audit log entry {}: {}", event.action, e);
+    }
+}
+
+#[derive(Debug)]
+pub enum AuditError {
+    Validation(String),
+    DatabaseError(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub error: String,
+    pub code: String,
+}
+
+impl IntoResponse for AuditError {
+    fn into_response(self) -> axum::response::Response {
+        let (status, error, code) = match self {
+            AuditError::Validation(msg) => (
+                StatusCode::BAD_REQUEST,
+                msg,
+                "VALIDATION_ERROR".to_string(),
+            ),
+            AuditError::DatabaseError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+                "INTERNAL_ERROR".to_string(),
+            ),
+        };
+
+        let body = Json(ErrorResponse {
+            success: false,
+            error,
+            code,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    page: Option<u32>,
+    limit: Option<u32>,
+    actor: Option<String>,
+    action: Option<String>,
+    target_type: Option<String>,
+    target_id: Option<String>,
+    /// `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS` (UTC), inclusive.
+    since: Option<String>,
+    /// As `since`, exclusive.
+    until: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogResponse {
+    entries: Vec<AuditEntry>,
+    total: u64,
+    page: u32,
+    limit: u32,
+}
+
+fn validate_time(field: &str, value: Option<String>) -> Result<Option<String>, AuditError> {
+    match value {
+        Some(v) if NaiveDate::parse_from_str(&v, "%Y-%m-%d").is_err() && parse_db_time(&v).is_none() => {
+            Err(AuditError::Validation(format!(
+                "{} must be YYYY-MM-DD or YYYY-MM-DD HH:MM:SS",
+                field
+            )))
+        }
+        value => Ok(value),
+    }
+}
+
+impl AuditQuery {
+    fn filter(&mut self) -> Result<AuditFilter, AuditError> {
+        Ok(AuditFilter {
+            actor: self.actor.take(),
+            action: self.action.take(),
+            target_type: self.target_type.take(),
+            target_id: self.target_id.take(),
+            since: validate_time("since", self.since.take())?,
+            until: validate_time("until", self.until.take())?,
+        })
+    }
+}
+
+pub async fn list_audit_log(
+    _session: RequireAdmin,
+    State(state): State<AppState>,
+    Query(mut query): Query<AuditQuery>,
+) -> Result<Json<AuditLogResponse>, AuditError> {
+    let filter = query.filter()?;
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+
+    let (entries, total) = state
+        .db
+        .list_audit_log(&filter, limit, (page - 1) * limit)
+        .map_err(|e| AuditError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(AuditLogResponse {
+        entries,
+        total,
+        page,
+        limit,
+    }))
+}
+
+fn csv_field(value: &str) -> String {
+    // Spreadsheets evaluate cells starting with these as formulas; a leading
+    // quote keeps an actor-controlled value such as a username inert.
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn to_csv(entries: &[AuditEntry]) -> String {
+    let mut csv = String::from("id,created_at,actor,action,target_type,target_id,ip,changes\n");
+    for entry in entries {
+        let changes = entry.changes.as_ref().map(|c| c.to_string());
+        let fields = [
+            entry.id.to_string(),
+            entry.created_at.clone(),
+            entry.actor.clone(),
+            entry.action.clone(),
+            entry.target_type.clone().unwrap_or_default(),
+            entry.target_id.clone().unwrap_or_default(),
+            entry.ip.clone().unwrap_or_default(),
+            changes.unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// The same filters as the list, as CSV, newest first.
+pub async fn export_audit_log(
+    _session: RequireAdmin,
+    State(state): State<AppState>,
+    Query(mut query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, AuditError> {
+    let filter = query.filter()?;
+    let (entries, _) = state
+        .db
+        .list_audit_log(&filter, MAX_EXPORT_ROWS, 0)
+        .map_err(|e| AuditError::DatabaseError(e.to_string()))?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (CONTENT_DISPOSITION, "attachment; filename=\"audit-log.csv\""),
+        ],
+        to_csv(&entries),
+    ))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_audit_log))
+        .route("/export", get(export_audit_log))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        let entry = AuditEntry {
+            id: 7,
+            actor_id: Some(1),
+            actor: "root".to_string(),
+            action: "config.update".to_string(),
+            target_type: None,
+            target_id: None,
+            changes: Some(serde_json::json!({ "log_level": { "before": "info", "after": "debug" } })),
+            ip: Some("203.0.113.9".to_string()),
+            created_at: "2024-01-15 12:00:00".to_string(),
+        };
+        let csv = to_csv(&[entry]);
+        let row = csv.lines().nth(1).unwrap();
+        assert_eq!(
+            row,
+            "7,2024-01-15 12:00:00,root,config.update,,,203.0.113.9,\
+             \"{\"\"log_level\"\":{\"\"after\"\":\"\"debug\"\",\"\"before\"\":\"\"info\"\"}}\""
+        );
+
+        assert_eq!(csv_field("=HYPERLINK(\"http://x\")"), "\"'=HYPERLINK(\"\"http://x\"\")\"");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-1"), "'-1");
+        assert_eq!(csv_field("@SUM(A1,A2)"), "\"'@SUM(A1,A2)\"");
+        assert_eq!(csv_field("user.update"), "user.update");
+    }
+}
@@ -1,28 +1,119 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{
+        header::{RETRY_AFTER, USER_AGENT},
+        HeaderMap, StatusCode,
+    },
     response::IntoResponse,
     Json,
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
-use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use serde::{Deserialize, Serialize};
 use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 
+use crate::crypto::{self, totp};
+use crate::db::admins::{verify_login_password, Admin, AdminRole};
+use crate::db::audit::AuditEvent;
+use crate::db::failed_logins::FailedLogin;
+use crate::db::sessions::{SessionClient, SessionInfo};
+use crate::db::totp::{generate_recovery_codes, hash_recovery_code};
+use crate::middleware::admin_auth::{load_session, session_is_idle, AdminSession};
+use crate::middleware::client_ip::{ClientIp, UNKNOWN_IP};
+use crate::middleware::login_guard::{LoginBlock, LoginGuardStatus, LoginThrottled};
+use crate::routes::audit;
 use crate::AppState;
 
+/// Login name assumed when a client sends only a password, as single-admin
+/// setups did.
+pub const DEFAULT_ADMIN_USERNAME: &str = "admin";
+
+/// Issuer shown in authenticator apps.
+const TOTP_ISSUER: &str = "ProxyPal";
+/// How long the second login step stays open after the password is accepted.
+const LOGIN_CHALLENGE_TTL_MINUTES: i64 = 5;
+
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
+    #[serde(default)]
+    pub username: Option<String>,
     pub password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct TotpLoginRequest {
+    pub challenge: String,
+    /// A 6-digit TOTP code or an unused recovery code.
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpSetupResponse {
+    pub success: bool,
+    pub secret: String,
+    /// `otpauth://` URI to render as a QR code.
+    pub provisioning_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub success: bool,
+    /// Shown once; each works a single time in place of a TOTP code.
+    pub recovery_codes: Vec<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
     pub success: bool,
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expires_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<AdminRole>,
+    /// Set when the password was accepted but a TOTP code is still needed;
+    /// send it with `challenge` to `/login/totp`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totp_required: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LockoutsQuery {
+    pub ip: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockoutsResponse {
+    pub login_guard: LoginGuardStatus,
+    pub failed_logins: Vec<FailedLogin>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionsQuery {
+    /// Owners only: list every admin's sessions.
+    #[serde(default)]
+    pub all: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevokeSessionsResponse {
+    pub success: bool,
+    pub revoked: u64,
 }
 
 #[derive(Debug, Serialize)]
@@ -35,6 +126,24 @@ pub struct StatusResponse {
     pub authenticated: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expires_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<AdminRole>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp_enabled: Option<bool>,
+}
+
+impl StatusResponse {
+    fn unauthenticated() -> Self {
+        Self {
+            authenticated: false,
+            expires_at: None,
+            username: None,
+            role: None,
+            totp_enabled: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -47,32 +156,58 @@ pub struct ErrorResponse {
 #[derive(Debug)]
 pub enum AuthError {
     InvalidCredentials,
+    InvalidCode,
+    ChallengeExpired,
+    Throttled(LoginThrottled),
+    TotpState(String),
+    SessionNotFound,
     NotConfigured,
+    EncryptionError(String),
     DatabaseError(String),
-    HashError(String),
 }
 
 impl IntoResponse for AuthError {
     fn into_response(self) -> axum::response::Response {
+        if let AuthError::Throttled(throttled) = self {
+            return throttled_response(throttled);
+        }
+
         let (status, error, code) = match self {
             AuthError::InvalidCredentials => (
                 StatusCode::UNAUTHORIZED,
-                "Invalid password".to_string(),
+                "Invalid username or password".to_string(),
                 "UNAUTHORIZED".to_string(),
             ),
+            AuthError::InvalidCode => (
+                StatusCode::UNAUTHORIZED,
+                "Invalid authentication code".to_string(),
+                "INVALID_TOTP_CODE".to_string(),
+            ),
+            AuthError::ChallengeExpired => (
+                StatusCode::UNAUTHORIZED,
+                "Login challenge expired; sign in again".to_string(),
+                "CHALLENGE_EXPIRED".to_string(),
+            ),
+            AuthError::Throttled(_) => unreachable!("handled above"),
+            AuthError::TotpState(msg) => (StatusCode::CONFLICT, msg, "CONFLICT".to_string()),
+            AuthError::SessionNotFound => (
+                StatusCode::NOT_FOUND,
+                "Session not found".to_string(),
+                "NOT_FOUND".to_string(),
+            ),
             AuthError::NotConfigured => (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Admin password not configured".to_string(),
+                "No admin accounts configured".to_string(),
                 "NOT_CONFIGURED".to_string(),
             ),
-            AuthError::DatabaseError(e) => (
+            AuthError::EncryptionError(e) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
+                format!("Encryption error: {}", e),
                 "INTERNAL_ERROR".to_string(),
             ),
-            AuthError::HashError(e) => (
+            AuthError::DatabaseError(e) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Authentication error: {}", e),
+                format!("Database error: {}", e),
                 "INTERNAL_ERROR".to_string(),
             ),
         };
@@ -87,6 +222,67 @@ impl IntoResponse for AuthError {
     }
 }
 
+fn throttled_response(throttled: LoginThrottled) -> axum::response::Response {
+    let retry_after = throttled.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let (error, code) = match throttled.block {
+        LoginBlock::Backoff => (
+            format!("Too many failed logins; try again in {} seconds", retry_after),
+            "LOGIN_THROTTLED",
+        ),
+        LoginBlock::Locked => (
+            format!("Too many failed logins from this address; locked for {} seconds", retry_after),
+            "LOGIN_LOCKED",
+        ),
+        LoginBlock::Global => (
+            format!("Password login is paused after too many failures; try again in {} seconds", retry_after),
+            "LOGIN_LOCKED",
+        ),
+    };
+
+    let body = Json(ErrorResponse {
+        success: false,
+        error,
+        code: code.to_string(),
+    });
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, retry_after.to_string())],
+        body,
+    )
+        .into_response()
+}
+
+/// Turns the attempt away if this address or the server is locked out.
+fn check_login_guard(state: &AppState, ip: &str, username: Option<&str>) -> Result<(), AuthError> {
+    state.login_guard.check(ip).map_err(|throttled| {
+        if let Err(e) = state.db.record_failed_login(ip, username, "throttled") {
+            tracing::warn!("Failed to record failed login: {}", e);
+        }
+        AuthError::Throttled(throttled)
+    })
+}
+
+fn record_login_failure(state: &AppState, ip: &str, username: Option<&str>, reason: &str) {
+    state.login_guard.record_failure(ip);
+    if let Err(e) = state.db.record_failed_login(ip, username, reason) {
+        tracing::warn!("Failed to record failed login: {}", e);
+    }
+}
+
+/// Longest user agent kept with a session.
+const MAX_USER_AGENT_LEN: usize = 512;
+
+pub(crate) fn session_client(ip: String, headers: &HeaderMap) -> SessionClient {
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|ua| ua.chars().take(MAX_USER_AGENT_LEN).collect());
+    SessionClient {
+        ip: Some(ip),
+        user_agent,
+    }
+}
+
 fn create_session_cookie(session_id: &str, expires_at: OffsetDateTime) -> Cookie<'static> {
     Cookie::build(("session", session_id.to_owned()))
         .http_only(true)
@@ -121,29 +317,124 @@ const SESSION_TTL_DAYS: i64 = 7;
 
 pub async fn login(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
     jar: CookieJar,
     Json(payload): Json<LoginRequest>,
 ) -> Result<(CookieJar, Json<LoginResponse>), AuthError> {
-    let admin_password_hash = state
+    let username = payload.username.as_deref().unwrap_or(DEFAULT_ADMIN_USERNAME);
+    check_login_guard(&state, &ip, Some(username))?;
+
+    let admin_count = state
+        .db
+        .count_admins()
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    if admin_count == 0 {
+        return Err(AuthError::NotConfigured);
+    }
+
+    let found = state
+        .db
+        .find_admin_by_username(username)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    let hash = found.as_ref().map(|(_, hash)| hash.as_str());
+    let verified = verify_login_password(&payload.password, hash);
+    let Some((admin, _)) = found.filter(|_| verified) else {
+        record_login_failure(&state, &ip, Some(username), "invalid_credentials");
+        return Err(AuthError::InvalidCredentials);
+    };
+
+    if admin.totp_enabled {
+        let challenge = state
+            .db
+            .create_login_challenge(admin.id, LOGIN_CHALLENGE_TTL_MINUTES)
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        return Ok((
+            jar,
+            Json(LoginResponse {
+                success: true,
+                message: "Authentication code required".to_string(),
+                expires_at: None,
+                username: None,
+                role: None,
+                totp_required: Some(true),
+                challenge: Some(challenge),
+            }),
+        ));
+    }
+
+    state.login_guard.record_success(&ip);
+    let client = session_client(ip, &headers);
+    let (jar, response) = start_session(&state, jar, admin, &client)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    Ok((jar, Json(response)))
+}
+
+/// Second login step for admins with TOTP enabled.
+pub async fn login_totp(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    jar: CookieJar,
+    Json(payload): Json<TotpLoginRequest>,
+) -> Result<(CookieJar, Json<LoginResponse>), AuthError> {
+    check_login_guard(&state, &ip, None)?;
+
+    let admin_id = state
         .db
-        .get_setting("admin_password_hash")
+        .get_login_challenge(&payload.challenge)
         .map_err(|e| AuthError::DatabaseError(e.to_string()))?
-        .ok_or(AuthError::NotConfigured)?;
+        .ok_or(AuthError::ChallengeExpired)?;
 
-    let parsed_hash = PasswordHash::new(&admin_password_hash)
-        .map_err(|e| AuthError::HashError(e.to_string()))?;
+    if !check_second_factor(&state, admin_id, &payload.code)? {
+        state
+            .db
+            .fail_login_challenge(&payload.challenge)
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        record_login_failure(&state, &ip, None, "invalid_totp_code");
+        return Err(AuthError::InvalidCode);
+    }
 
-    Argon2::default()
-        .verify_password(payload.password.as_bytes(), &parsed_hash)
-        .map_err(|_| AuthError::InvalidCredentials)?;
+    state
+        .db
+        .delete_login_challenge(&payload.challenge)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    let admin = state
+        .db
+        .get_admin(admin_id)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?
+        .ok_or(AuthError::ChallengeExpired)?;
 
+    state.login_guard.record_success(&ip);
+    let client = session_client(ip, &headers);
+    let (jar, response) = start_session(&state, jar, admin, &client)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    Ok((jar, Json(response)))
+}
+
+/// Opens a session for an authenticated admin and sets its session and CSRF
+/// cookies. Shared by password and SSO login.
+pub(crate) fn start_session(
+    state: &AppState,
+    jar: CookieJar,
+    admin: Admin,
+    client: &SessionClient,
+) -> anyhow::Result<(CookieJar, LoginResponse)> {
     let session_id = Uuid::new_v4().to_string();
     let csrf_token = Uuid::new_v4().to_string();
 
     let session = state
         .db
-        .create_session(&session_id, &csrf_token, SESSION_TTL_DAYS)
-        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        .create_session_for(&session_id, &csrf_token, admin.id, SESSION_TTL_DAYS, client)?;
+    if let Err(e) = state.db.touch_admin_login(admin.id) {
+        tracing::warn!("Failed to record admin login: {}", e);
+    }
+    audit::record(
+        state,
+        &admin,
+        client.ip.as_deref().unwrap_or(UNKNOWN_IP),
+        AuditEvent::new("auth.login").target("admin", admin.id),
+    );
 
     let expires_at = OffsetDateTime::now_utc() + Duration::days(SESSION_TTL_DAYS);
 
@@ -153,20 +444,207 @@ pub async fn login(
 
     Ok((
         jar,
-        Json(LoginResponse {
+        LoginResponse {
             success: true,
             message: "Logged in successfully".to_string(),
             expires_at: Some(session.expires_at),
-        }),
+            username: Some(admin.username),
+            role: Some(admin.role),
+            totp_required: None,
+            challenge: None,
+        },
     ))
 }
 
+fn unix_now() -> u64 {
+    OffsetDateTime::now_utc().unix_timestamp() as u64
+}
+
+fn decrypt_totp_secret(encrypted: &str) -> Result<String, AuthError> {
+    let value = crypto::decrypt_tokens(encrypted)
+        .map_err(|e| AuthError::EncryptionError(e.to_string()))?;
+    value["secret"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| AuthError::EncryptionError("malformed TOTP secret".to_string()))
+}
+
+/// Checks a TOTP code against the admin's stored secret (pending or enabled)
+/// and returns its time step. Does not mark the step as used.
+fn verify_totp_code(state: &AppState, admin_id: i64, code: &str) -> Result<Option<i64>, AuthError> {
+    let Some(encrypted) = state
+        .db
+        .get_admin_totp(admin_id)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?
+        .and_then(|totp| totp.secret)
+    else {
+        return Ok(None);
+    };
+    let secret = decrypt_totp_secret(&encrypted)?;
+    Ok(totp::verify(&secret, code, unix_now()).map(|step| step as i64))
+}
+
+/// Accepts an unused TOTP code for an enabled secret.
+fn check_totp_code(state: &AppState, admin_id: i64, code: &str) -> Result<bool, AuthError> {
+    let Some(step) = verify_totp_code(state, admin_id, code)? else {
+        return Ok(false);
+    };
+    state
+        .db
+        .record_totp_step(admin_id, step)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))
+}
+
+/// Accepts either a TOTP code or one of the admin's unused recovery codes.
+fn check_second_factor(state: &AppState, admin_id: i64, code: &str) -> Result<bool, AuthError> {
+    let code = code.trim();
+    if code.len() == 6 && code.bytes().all(|b| b.is_ascii_digit()) {
+        return check_totp_code(state, admin_id, code);
+    }
+    state
+        .db
+        .use_recovery_code(admin_id, &hash_recovery_code(code))
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))
+}
+
+fn ensure_totp_enabled(auth: &AdminSession) -> Result<(), AuthError> {
+    if !auth.admin.totp_enabled {
+        return Err(AuthError::TotpState("TOTP is not enabled".to_string()));
+    }
+    Ok(())
+}
+
+fn new_recovery_codes(state: &AppState, admin_id: i64) -> Result<Vec<String>, AuthError> {
+    let codes = generate_recovery_codes();
+    let hashes: Vec<String> = codes.iter().map(|c| hash_recovery_code(c)).collect();
+    state
+        .db
+        .replace_recovery_codes(admin_id, &hashes)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    Ok(codes)
+}
+
+/// Starts TOTP enrolment with a fresh secret. Nothing changes at login until
+/// `/totp/enable` confirms a code from it.
+pub async fn totp_setup(
+    auth: AdminSession,
+    State(state): State<AppState>,
+) -> Result<Json<TotpSetupResponse>, AuthError> {
+    if auth.admin.totp_enabled {
+        return Err(AuthError::TotpState("TOTP is already enabled".to_string()));
+    }
+
+    let secret = totp::generate_secret();
+    let encrypted = crypto::encrypt_tokens(&serde_json::json!({ "secret": secret }))
+        .map_err(|e| AuthError::EncryptionError(e.to_string()))?;
+    state
+        .db
+        .set_admin_totp_secret(auth.admin.id, &encrypted)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+
+    let provisioning_uri = totp::provisioning_uri(&secret, &auth.admin.username, TOTP_ISSUER);
+    Ok(Json(TotpSetupResponse {
+        success: true,
+        secret,
+        provisioning_uri,
+    }))
+}
+
+pub async fn totp_enable(
+    auth: AdminSession,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AuthError> {
+    if auth.admin.totp_enabled {
+        return Err(AuthError::TotpState("TOTP is already enabled".to_string()));
+    }
+    let step = verify_totp_code(&state, auth.admin.id, &payload.code)?
+        .ok_or(AuthError::InvalidCode)?;
+
+    let recovery_codes = generate_recovery_codes();
+    let hashes: Vec<String> = recovery_codes.iter().map(|c| hash_recovery_code(c)).collect();
+    state
+        .db
+        .enable_admin_totp(auth.admin.id, step, &hashes)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    audit::record(
+        &state,
+        &auth.admin,
+        &ip,
+        AuditEvent::new("auth.totp_enable").target("admin", auth.admin.id),
+    );
+
+    Ok(Json(RecoveryCodesResponse {
+        success: true,
+        recovery_codes,
+    }))
+}
+
+pub async fn totp_disable(
+    auth: AdminSession,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<Json<LogoutResponse>, AuthError> {
+    ensure_totp_enabled(&auth)?;
+    if !check_second_factor(&state, auth.admin.id, &payload.code)? {
+        return Err(AuthError::InvalidCode);
+    }
+
+    state
+        .db
+        .disable_admin_totp(auth.admin.id)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    audit::record(
+        &state,
+        &auth.admin,
+        &ip,
+        AuditEvent::new("auth.totp_disable").target("admin", auth.admin.id),
+    );
+    Ok(Json(LogoutResponse { success: true }))
+}
+
+/// Replaces all recovery codes; needs a current TOTP code.
+pub async fn totp_recovery_codes(
+    auth: AdminSession,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AuthError> {
+    ensure_totp_enabled(&auth)?;
+    if !check_totp_code(&state, auth.admin.id, &payload.code)? {
+        return Err(AuthError::InvalidCode);
+    }
+
+    let recovery_codes = new_recovery_codes(&state, auth.admin.id)?;
+    audit::record(
+        &state,
+        &auth.admin,
+        &ip,
+        AuditEvent::new("auth.recovery_codes_regenerate").target("admin", auth.admin.id),
+    );
+    Ok(Json(RecoveryCodesResponse {
+        success: true,
+        recovery_codes,
+    }))
+}
+
 pub async fn logout(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     jar: CookieJar,
 ) -> Result<(CookieJar, Json<LogoutResponse>), AuthError> {
     if let Some(session_cookie) = jar.get("session") {
         let session_id = session_cookie.value();
+        if let Ok(Some((_, admin))) = state.db.get_session_admin(session_id) {
+            audit::record(
+                &state,
+                &admin,
+                &ip,
+                AuditEvent::new("auth.logout").target("admin", admin.id),
+            );
+        }
         let _ = state.db.delete_session(session_id);
     }
 
@@ -186,75 +664,184 @@ pub async fn status(
 ) -> Result<Json<StatusResponse>, AuthError> {
     let session_cookie = match jar.get("session") {
         Some(cookie) => cookie,
-        None => {
-            return Ok(Json(StatusResponse {
-                authenticated: false,
-                expires_at: None,
-            }));
-        }
+        None => return Ok(Json(StatusResponse::unauthenticated())),
     };
 
     let session_id = session_cookie.value();
 
-    match state.db.get_session(session_id) {
-        Ok(Some(session)) => {
+    match load_session(&state.db, session_id) {
+        Ok(Some((session, admin))) => {
             Ok(Json(StatusResponse {
                 authenticated: true,
                 expires_at: Some(session.expires_at),
+                username: Some(admin.username),
+                role: Some(admin.role),
+                totp_enabled: Some(admin.totp_enabled),
             }))
         }
-        Ok(None) => Ok(Json(StatusResponse {
-            authenticated: false,
-            expires_at: None,
-        })),
+        Ok(None) => Ok(Json(StatusResponse::unauthenticated())),
         Err(e) => Err(AuthError::DatabaseError(e.to_string())),
     }
 }
 
+/// Current throttling and lockouts, with the failed attempts behind them.
+pub async fn lockouts(
+    _session: AdminSession,
+    State(state): State<AppState>,
+    Query(query): Query<LockoutsQuery>,
+) -> Result<Json<LockoutsResponse>, AuthError> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let failed_logins = state
+        .db
+        .list_failed_logins(query.ip.as_deref(), limit)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(LockoutsResponse {
+        login_guard: state.login_guard.status(),
+        failed_logins,
+    }))
+}
+
+/// The cookie value of the caller's session; empty when they used a token.
+fn current_session_id(session: &AdminSession) -> &str {
+    session.session.as_ref().map_or("", |s| s.id.as_str())
+}
+
+/// The caller's active sessions, or everyone's for an owner passing `all`.
+pub async fn list_sessions(
+    session: AdminSession,
+    State(state): State<AppState>,
+    Query(query): Query<SessionsQuery>,
+) -> Result<Json<SessionsResponse>, AuthError> {
+    let admin_id = (!(query.all && session.has_role(AdminRole::Owner))).then_some(session.admin.id);
+    let mut sessions = state
+        .db
+        .list_sessions(admin_id, current_session_id(&session))
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    sessions.retain(|s| !session_is_idle(&s.last_accessed));
+
+    Ok(Json(SessionsResponse { sessions }))
+}
+
+/// Ends one session. Admins may end their own; owners anyone's.
+pub async fn revoke_session(
+    session: AdminSession,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<RevokeSessionsResponse>, AuthError> {
+    let admin_id = (!session.has_role(AdminRole::Owner)).then_some(session.admin.id);
+    let revoked = state
+        .db
+        .revoke_session(&id, admin_id)
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    if !revoked {
+        return Err(AuthError::SessionNotFound);
+    }
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("auth.session_revoke").target("session", &id),
+    );
+
+    Ok(Json(RevokeSessionsResponse {
+        success: true,
+        revoked: 1,
+    }))
+}
+
+/// Ends all of the caller's sessions except the current one, or all of them
+/// when called with an API token.
+pub async fn revoke_other_sessions(
+    session: AdminSession,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+) -> Result<Json<RevokeSessionsResponse>, AuthError> {
+    let revoked = state
+        .db
+        .revoke_other_sessions(session.admin.id, current_session_id(&session))
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("auth.session_revoke_others")
+            .target("admin", session.admin.id)
+            .after(&serde_json::json!({ "revoked": revoked })),
+    );
+
+    Ok(Json(RevokeSessionsResponse {
+        success: true,
+        revoked,
+    }))
+}
+
 pub fn router() -> axum::Router<AppState> {
-    use axum::routing::{get, post};
+    use axum::routing::{delete, get, post};
     
     axum::Router::new()
         .route("/login", post(login))
+        .route("/login/totp", post(login_totp))
         .route("/logout", post(logout))
         .route("/status", get(status))
+        .route("/lockouts", get(lockouts))
+        .route("/sessions", get(list_sessions).delete(revoke_other_sessions))
+        .route("/sessions/:id", delete(revoke_session))
+        .route("/totp/setup", post(totp_setup))
+        .route("/totp/enable", post(totp_enable))
+        .route("/totp/disable", post(totp_disable))
+        .route("/totp/recovery-codes", post(totp_recovery_codes))
+        .nest("/tokens", crate::routes::admin_tokens::router())
+        .nest("/oidc", crate::routes::oidc::router())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::admins::hash_password;
     use crate::db::Database;
-    use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
     use axum::{body::Body, http::Request};
-    use rand::rngs::OsRng;
-    use tempfile::tempdir;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serial_test::serial;
+    use tempfile::{tempdir, TempDir};
     use tower::ServiceExt;
     
-    fn create_test_db() -> Database {
+    fn create_test_db() -> (Database, TempDir) {
         let dir = tempdir().unwrap();
         let path = dir.path().join("test.db");
         let db = Database::new(path).unwrap();
-        db
+        (db, dir)
     }
-    
-    fn hash_password(password: &str) -> String {
-        let salt = SaltString::generate(&mut OsRng);
-        Argon2::default()
-            .hash_password(password.as_bytes(), &salt)
+
+    fn create_owner(db: &Database, password: &str) -> i64 {
+        db.create_admin(DEFAULT_ADMIN_USERNAME, &hash_password(password).unwrap(), AdminRole::Owner)
             .unwrap()
-            .to_string()
+            .id
     }
     
     fn create_app(db: Database) -> axum::Router {
+        create_app_with_guard(db, crate::middleware::login_guard::LoginGuard::default())
+    }
+
+    fn create_app_with_guard(
+        db: Database,
+        login_guard: crate::middleware::login_guard::LoginGuard,
+    ) -> axum::Router {
         use std::sync::Arc;
+        use crate::middleware::key_cache::ApiKeyCache;
         use crate::middleware::rate_limit::RateLimiter;
-        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager};
+        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
         
         let state = AppState { 
             db, 
             rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(login_guard),
             proxy_client: Arc::new(MockProxyManagementClient::default()),
             proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         };
         axum::Router::new()
             .nest("/api/auth", router())
@@ -263,7 +850,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_login_without_password_configured_returns_error() {
-        let db = create_test_db();
+        let (db, _dir) = create_test_db();
         let app = create_app(db);
         
         let request = Request::builder()
@@ -279,9 +866,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_login_with_invalid_password_returns_401() {
-        let db = create_test_db();
-        let hash = hash_password("correct_password");
-        db.set_setting("admin_password_hash", &hash).unwrap();
+        let (db, _dir) = create_test_db();
+        create_owner(&db, "correct_password");
         
         let app = create_app(db);
         
@@ -296,11 +882,36 @@ mod tests {
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 
+    #[tokio::test]
+    async fn test_unknown_username_is_verified_like_a_wrong_password() {
+        use crate::db::admins::LOGIN_VERIFICATIONS;
+
+        let (db, _dir) = create_test_db();
+        create_owner(&db, "correct_password");
+        let app = create_app(db);
+
+        for body in [
+            r#"{"username":"admin","password":"wrong_password"}"#,
+            r#"{"username":"nobody","password":"wrong_password"}"#,
+        ] {
+            let before = LOGIN_VERIFICATIONS.with(|count| count.get());
+            let request = Request::builder()
+                .method("POST")
+                .uri("/api/auth/login")
+                .header("Content-Type", "application/json")
+                .body(Body::from(body))
+                .unwrap();
+
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(LOGIN_VERIFICATIONS.with(|count| count.get()), before + 1);
+        }
+    }
+
     #[tokio::test]
     async fn test_login_with_valid_password_returns_200_and_cookies() {
-        let db = create_test_db();
-        let hash = hash_password("correct_password");
-        db.set_setting("admin_password_hash", &hash).unwrap();
+        let (db, _dir) = create_test_db();
+        create_owner(&db, "correct_password");
         
         let app = create_app(db);
         
@@ -331,7 +942,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_status_without_session_returns_unauthenticated() {
-        let db = create_test_db();
+        let (db, _dir) = create_test_db();
         let app = create_app(db);
         
         let request = Request::builder()
@@ -350,10 +961,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_status_with_valid_session_returns_authenticated() {
-        let db = create_test_db();
+        let (db, _dir) = create_test_db();
         let session_id = "test-session-id";
         let csrf_token = "test-csrf-token";
-        db.create_session(session_id, csrf_token, 7).unwrap();
+        let admin_id = create_owner(&db, "password");
+        db.create_session(session_id, csrf_token, admin_id, 7).unwrap();
         
         let app = create_app(db);
         
@@ -371,14 +983,67 @@ mod tests {
         let json: StatusResponse = serde_json::from_slice(&body).unwrap();
         assert!(json.authenticated);
         assert!(json.expires_at.is_some());
+        assert_eq!(json.username.as_deref(), Some(DEFAULT_ADMIN_USERNAME));
+        assert_eq!(json.role, Some(AdminRole::Owner));
+    }
+
+    #[tokio::test]
+    async fn test_login_by_username_ties_session_to_admin() {
+        let (db, _dir) = create_test_db();
+        create_owner(&db, "owner-password");
+        let viewer = db
+            .create_admin("auditor", &hash_password("viewer-password").unwrap(), AdminRole::Viewer)
+            .unwrap();
+
+        let app = create_app(db.clone());
+        let login = |body: &'static str| {
+            Request::builder()
+                .method("POST")
+                .uri("/api/auth/login")
+                .header("Content-Type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        // Another admin's password does not work for this username
+        let response = app
+            .clone()
+            .oneshot(login(r#"{"username":"auditor","password":"owner-password"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .oneshot(login(r#"{"username":"auditor","password":"viewer-password"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let session_id = response
+            .headers()
+            .get_all("set-cookie")
+            .iter()
+            .filter_map(|c| c.to_str().ok())
+            .find_map(|c| c.strip_prefix("session="))
+            .and_then(|c| c.split(';').next())
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["role"], "viewer");
+
+        let (session, admin) = db.get_session_admin(&session_id).unwrap().unwrap();
+        assert_eq!(session.admin_id, Some(viewer.id));
+        assert_eq!(admin.username, "auditor");
+        assert!(db.get_admin(viewer.id).unwrap().unwrap().last_login_at.is_some());
     }
 
     #[tokio::test]
     async fn test_logout_clears_session() {
-        let db = create_test_db();
+        let (db, _dir) = create_test_db();
         let session_id = "test-session-id";
         let csrf_token = "test-csrf-token";
-        db.create_session(session_id, csrf_token, 7).unwrap();
+        let admin_id = create_owner(&db, "password");
+        db.create_session(session_id, csrf_token, admin_id, 7).unwrap();
         
         let app = create_app(db.clone());
         
@@ -396,4 +1061,275 @@ mod tests {
         let session = db.get_session(session_id).unwrap();
         assert!(session.is_none());
     }
+
+    fn post_json(uri: &str, session_id: Option<&str>, body: serde_json::Value) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("Content-Type", "application/json");
+        if let Some(session_id) = session_id {
+            builder = builder.header("Cookie", format!("session={}", session_id));
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn json_body(response: axum::response::Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_totp_enrolment_makes_login_two_steps() {
+        std::env::set_var("ENCRYPTION_KEY", STANDARD.encode([0u8; 32]));
+        let (db, _dir) = create_test_db();
+        let admin_id = create_owner(&db, "correct_password");
+        db.create_session("enrol-session", "csrf", admin_id, 7).unwrap();
+        let app = create_app(db.clone());
+
+        let response = app
+            .clone()
+            .oneshot(post_json("/api/auth/totp/setup", Some("enrol-session"), serde_json::json!({})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let setup = json_body(response).await;
+        let secret = setup["secret"].as_str().unwrap().to_string();
+        assert!(setup["provisioning_uri"]
+            .as_str()
+            .unwrap()
+            .starts_with("otpauth://totp/ProxyPal:admin?secret="));
+        // Stored encrypted, never as the plain base32 secret
+        let stored = db.get_admin_totp(admin_id).unwrap().unwrap().secret.unwrap();
+        assert_ne!(stored, secret);
+
+        let step = totp::current_step(unix_now());
+        let response = app
+            .clone()
+            .oneshot(post_json(
+                "/api/auth/totp/enable",
+                Some("enrol-session"),
+                serde_json::json!({ "code": totp::code_at(&secret, step).unwrap() }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let recovery_codes: Vec<String> =
+            serde_json::from_value(json_body(response).await["recovery_codes"].clone()).unwrap();
+        assert_eq!(recovery_codes.len(), crate::db::totp::RECOVERY_CODE_COUNT);
+
+        // The password alone now only yields a challenge, without cookies
+        let login = || post_json("/api/auth/login", None, serde_json::json!({ "password": "correct_password" }));
+        let response = app.clone().oneshot(login()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("set-cookie").is_none());
+        let json = json_body(response).await;
+        assert_eq!(json["totp_required"], true);
+        let challenge = json["challenge"].as_str().unwrap().to_string();
+
+        // The code used during enrolment cannot be replayed
+        let second_step = |code: &str| {
+            post_json(
+                "/api/auth/login/totp",
+                None,
+                serde_json::json!({ "challenge": challenge, "code": code }),
+            )
+        };
+        let replayed = totp::code_at(&secret, step).unwrap();
+        let response = app.clone().oneshot(second_step(&replayed)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let next = totp::code_at(&secret, step + 1).unwrap();
+        let response = app.clone().oneshot(second_step(&next)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .get_all("set-cookie")
+            .iter()
+            .any(|c| c.to_str().unwrap().starts_with("session=")));
+
+        // The challenge is spent; a recovery code works once on a new one
+        let response = app.clone().oneshot(second_step(&recovery_codes[0])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.clone().oneshot(login()).await.unwrap();
+        let challenge = json_body(response).await["challenge"].as_str().unwrap().to_string();
+        let recovery_step = |code: &str| {
+            post_json(
+                "/api/auth/login/totp",
+                None,
+                serde_json::json!({ "challenge": challenge, "code": code }),
+            )
+        };
+        let typed = recovery_codes[0].to_uppercase();
+        let response = app.clone().oneshot(recovery_step(&typed)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db.count_unused_recovery_codes(admin_id).unwrap(), 9);
+
+        let response = app
+            .oneshot(post_json(
+                "/api/auth/totp/disable",
+                Some("enrol-session"),
+                serde_json::json!({ "code": recovery_codes[1] }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!db.get_admin(admin_id).unwrap().unwrap().totp_enabled);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_failed_logins_lock_out_the_address() {
+        use crate::middleware::login_guard::{LoginGuard, LoginGuardConfig};
+        use axum::extract::ConnectInfo;
+        use std::net::SocketAddr;
+        use std::time::Duration;
+
+        let (db, _dir) = create_test_db();
+        let admin_id = create_owner(&db, "correct_password");
+        db.create_session("viewer-session", "csrf", admin_id, 7).unwrap();
+        let app = create_app_with_guard(
+            db.clone(),
+            LoginGuard::new(LoginGuardConfig {
+                free_attempts: 1,
+                lockout_after: 2,
+                lockout: Duration::from_secs(600),
+                ..LoginGuardConfig::default()
+            }),
+        );
+        let login_from = |addr: &str, password: &str| {
+            let mut request = post_json(
+                "/api/auth/login",
+                None,
+                serde_json::json!({ "password": password }),
+            );
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(addr.parse::<SocketAddr>().unwrap()));
+            request
+        };
+
+        for _ in 0..2 {
+            let response = app.clone().oneshot(login_from("203.0.113.9:4000", "wrong")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        // Even the right password is turned away while locked
+        let response = app
+            .clone()
+            .oneshot(login_from("203.0.113.9:4001", "correct_password"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "600");
+        assert_eq!(json_body(response).await["code"], "LOGIN_LOCKED");
+
+        let response = app
+            .clone()
+            .oneshot(login_from("198.51.100.1:4000", "correct_password"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .uri("/api/auth/lockouts?ip=203.0.113.9")
+            .header("Cookie", "session=viewer-session")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json = json_body(response).await;
+        assert_eq!(json["loginGuard"]["blockedIps"][0]["ip"], "203.0.113.9");
+        assert_eq!(json["loginGuard"]["blockedIps"][0]["block"], "locked");
+        let reasons: Vec<_> = json["failedLogins"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["reason"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(reasons, ["throttled", "invalid_credentials", "invalid_credentials"]);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_can_be_listed_and_revoked() {
+        let (db, _dir) = create_test_db();
+        let admin_id = create_owner(&db, "correct_password");
+        let app = create_app(db.clone());
+
+        let mut request = post_json(
+            "/api/auth/login",
+            None,
+            serde_json::json!({ "password": "correct_password" }),
+        );
+        request.headers_mut().insert(USER_AGENT, "Firefox/128.0".parse().unwrap());
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let session_id = response
+            .headers()
+            .get_all("set-cookie")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(|v| v.strip_prefix("session="))
+            .and_then(|v| v.split(';').next())
+            .unwrap()
+            .to_string();
+        db.create_session("phone", "csrf", admin_id, 7).unwrap();
+        db.create_session("tablet", "csrf", admin_id, 7).unwrap();
+
+        let get_sessions = || {
+            Request::builder()
+                .uri("/api/auth/sessions")
+                .header("Cookie", format!("session={}", session_id))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let json = json_body(app.clone().oneshot(get_sessions()).await.unwrap()).await;
+        let sessions = json["sessions"].as_array().unwrap();
+        assert_eq!(sessions.len(), 3);
+        let current = sessions.iter().find(|s| s["current"] == true).unwrap();
+        assert_eq!(current["userAgent"], "Firefox/128.0");
+        assert_eq!(current["ip"], crate::middleware::client_ip::UNKNOWN_IP);
+        let other_id = sessions.iter().find(|s| s["current"] == false).unwrap()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let revoke = |uri: String| {
+            Request::builder()
+                .method("DELETE")
+                .uri(uri)
+                .header("Cookie", format!("session={}", session_id))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app
+            .clone()
+            .oneshot(revoke(format!("/api/auth/sessions/{}", other_id)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .clone()
+            .oneshot(revoke(format!("/api/auth/sessions/{}", other_id)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app.clone().oneshot(revoke("/api/auth/sessions".to_string())).await.unwrap();
+        assert_eq!(json_body(response).await["revoked"], 1);
+        let json = json_body(app.clone().oneshot(get_sessions()).await.unwrap()).await;
+        assert_eq!(json["sessions"].as_array().unwrap().len(), 1);
+
+        // A session left idle past the timeout no longer authenticates
+        db.with_conn(|conn| {
+            conn.execute(
+                "UPDATE sessions SET last_accessed = datetime('now', '-1 day') WHERE id = ?1",
+                [&session_id],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+        let response = app.oneshot(get_sessions()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(db.get_session(&session_id).unwrap().is_none());
+    }
 }
@@ -11,7 +11,10 @@ use std::collections::HashMap;
 use crate::cliproxy::{
     generate_proxy_config, load_server_config, save_server_config, ServerConfig,
 };
-use crate::middleware::admin_auth::AdminSession;
+use crate::db::audit::AuditEvent;
+use crate::middleware::admin_auth::{AdminSession, RequireOwner};
+use crate::middleware::client_ip::ClientIp;
+use crate::routes::audit;
 use crate::AppState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +55,8 @@ pub struct UpdateConfigRequest {
     pub log_level: Option<String>,
     pub auto_start_proxy: Option<bool>,
     pub model_mappings: Option<HashMap<String, String>>,
+    pub model_fallbacks: Option<HashMap<String, Vec<String>>>,
+    pub fallback_timeout_secs: Option<u64>,
     pub rate_limits: Option<RateLimitsRequest>,
 }
 
@@ -59,6 +64,8 @@ pub struct UpdateConfigRequest {
 pub struct RateLimitsRequest {
     pub requests_per_minute: Option<u64>,
     pub tokens_per_day: Option<i64>,
+    pub max_concurrent_requests: Option<u64>,
+    pub tokens_per_minute: Option<u64>,
 }
 
 pub async fn get_config(
@@ -71,12 +78,14 @@ pub async fn get_config(
 }
 
 pub async fn update_config(
-    _session: AdminSession,
+    session: RequireOwner,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
     Json(payload): Json<UpdateConfigRequest>,
 ) -> Result<Json<UpdateConfigResponse>, ConfigError> {
     let mut config =
         load_server_config(&state.db).map_err(|e| ConfigError::Internal(e.to_string()))?;
+    let before = config.clone();
 
     let old_admin_port = config.admin_port;
     let old_proxy_port = config.proxy_port;
@@ -99,6 +108,18 @@ pub async fn update_config(
     if let Some(mappings) = payload.model_mappings {
         config.model_mappings = mappings;
     }
+    if let Some(fallbacks) = payload.model_fallbacks {
+        validate_model_fallbacks(&fallbacks)?;
+        config.model_fallbacks = fallbacks;
+    }
+    if let Some(timeout) = payload.fallback_timeout_secs {
+        if timeout == 0 {
+            return Err(ConfigError::ValidationError(
+                "fallback_timeout_secs must be at least 1".to_string(),
+            ));
+        }
+        config.fallback_timeout_secs = timeout;
+    }
     if let Some(limits) = payload.rate_limits {
         if let Some(rpm) = limits.requests_per_minute {
             config.rate_limits.requests_per_minute = rpm;
@@ -106,9 +127,30 @@ pub async fn update_config(
         if let Some(tpd) = limits.tokens_per_day {
             config.rate_limits.tokens_per_day = Some(tpd);
         }
+        if let Some(concurrent) = limits.max_concurrent_requests {
+            config.rate_limits.max_concurrent_requests = concurrent;
+        }
+        if let Some(tpm) = limits.tokens_per_minute {
+            config.rate_limits.tokens_per_minute = tpm;
+        }
     }
 
     save_server_config(&state.db, &config).map_err(|e| ConfigError::Internal(e.to_string()))?;
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("config.update").before(&before).after(&config),
+    );
+    state
+        .rate_limiter
+        .set_limit(config.rate_limits.requests_per_minute);
+    state
+        .rate_limiter
+        .set_concurrency_limit(config.rate_limits.max_concurrent_requests);
+    state
+        .rate_limiter
+        .set_token_limit(config.rate_limits.tokens_per_minute);
 
     let restart_required = config.admin_port != old_admin_port;
 
@@ -147,6 +189,18 @@ fn validate_log_level(level: &str) -> Result<(), ConfigError> {
     }
 }
 
+fn validate_model_fallbacks(fallbacks: &HashMap<String, Vec<String>>) -> Result<(), ConfigError> {
+    for (model, chain) in fallbacks {
+        if model.is_empty() || chain.is_empty() || chain.iter().any(|m| m.is_empty()) {
+            return Err(ConfigError::ValidationError(format!(
+                "Invalid fallback chain for '{}': model names must be non-empty",
+                model
+            )));
+        }
+    }
+    Ok(())
+}
+
 fn get_proxy_config_path() -> std::path::PathBuf {
     std::env::var("DATA_DIR")
         .map(std::path::PathBuf::from)
@@ -161,8 +215,9 @@ pub fn router() -> Router<AppState> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager};
+    use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
     use crate::db::Database;
+    use crate::middleware::key_cache::ApiKeyCache;
     use crate::middleware::rate_limit::RateLimiter;
     use axum::{body::Body, http::Request};
     use std::sync::Arc;
@@ -177,15 +232,26 @@ mod tests {
     }
 
     fn create_app(db: Database) -> (Router, String) {
+        create_app_with_limiter(db, Arc::new(RateLimiter::new(60)))
+    }
+
+    fn create_app_with_limiter(db: Database, rate_limiter: Arc<RateLimiter>) -> (Router, String) {
         let session_id = "test-session-id";
         let csrf_token = "test-csrf-token";
-        db.create_session(session_id, csrf_token, 7).unwrap();
+        let admin = db
+            .create_admin("admin", "", crate::db::admins::AdminRole::Owner)
+            .unwrap();
+        db.create_session(session_id, csrf_token, admin.id, 7).unwrap();
 
         let state = AppState {
             db,
-            rate_limiter: Arc::new(RateLimiter::new(60)),
+            rate_limiter,
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
             proxy_client: Arc::new(MockProxyManagementClient::default()),
             proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         };
         let app = Router::new()
             .nest("/api/config", router())
@@ -366,8 +432,12 @@ mod tests {
         let state = AppState {
             db,
             rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
             proxy_client: Arc::new(MockProxyManagementClient::default()),
             proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         };
         let app = Router::new()
             .nest("/api/config", router())
@@ -404,6 +474,26 @@ mod tests {
         assert_eq!(loaded.rate_limits.tokens_per_day, Some(1000000));
     }
 
+    #[tokio::test]
+    async fn test_update_config_applies_rate_limit_live() {
+        let (db, _dir) = create_test_db();
+        let limiter = Arc::new(RateLimiter::new(60));
+        let (app, session_id) = create_app_with_limiter(db, limiter.clone());
+
+        let request = authed_request(
+            "PUT",
+            "/api/config",
+            &session_id,
+            Some(r#"{"rate_limits": {"requests_per_minute": 5, "tokens_per_minute": 20000}}"#),
+        );
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(limiter.limit(), 5);
+        assert_eq!(limiter.concurrency_limit(), 0);
+        assert_eq!(limiter.token_limit(), 20000);
+    }
+
     #[tokio::test]
     async fn test_update_config_with_model_mappings() {
         let (db, _dir) = create_test_db();
@@ -426,4 +516,35 @@ mod tests {
             Some(&"claude-3-opus".to_string())
         );
     }
+
+    #[tokio::test]
+    async fn test_update_config_with_model_fallbacks() {
+        let (db, _dir) = create_test_db();
+        let (app, session_id) = create_app(db.clone());
+
+        let request = authed_request(
+            "PUT",
+            "/api/config",
+            &session_id,
+            Some(r#"{"model_fallbacks": {"claude-opus-4-1": []}}"#),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = authed_request(
+            "PUT",
+            "/api/config",
+            &session_id,
+            Some(r#"{"model_fallbacks": {"claude-opus-4-1": ["claude-sonnet-4", "gemini-2.5-pro"]}, "fallback_timeout_secs": 30}"#),
+        );
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let loaded = load_server_config(&db).unwrap();
+        assert_eq!(
+            loaded.model_fallbacks["claude-opus-4-1"],
+            vec!["claude-sonnet-4".to_string(), "gemini-2.5-pro".to_string()]
+        );
+        assert_eq!(loaded.fallback_timeout_secs, 30);
+    }
 }
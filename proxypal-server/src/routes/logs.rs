@@ -34,6 +34,8 @@ pub struct LogEntry {
     pub user_name: String,
     pub provider: String,
     pub model: String,
+    /// The model the client named, before aliasing.
+    pub requested_model: Option<String>,
     pub tokens_input: i64,
     pub tokens_output: i64,
     pub duration_ms: i64,
@@ -0,0 +1,349 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::cliproxy::load_server_config;
+use crate::db::api_keys::ApiKey;
+use crate::db::audit::AuditEvent;
+use crate::db::quota::QuotaPeriod;
+use crate::db::scopes::Scopes;
+use crate::db::usage::{DailyUsage, UsageLog};
+use crate::middleware::api_key_auth::{tokens_used_today, ApiKeyIdentity};
+use crate::middleware::client_ip::ClientIp;
+use crate::AppState;
+
+#[derive(Debug)]
+pub enum MeError {
+    KeyNotActive,
+    DatabaseError(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub error: String,
+    pub code: String,
+}
+
+impl IntoResponse for MeError {
+    fn into_response(self) -> axum::response::Response {
+        let (status, error, code) = match self {
+            MeError::KeyNotActive => (
+                StatusCode::CONFLICT,
+                "API key is no longer active".to_string(),
+                "KEY_NOT_ACTIVE".to_string(),
+            ),
+            MeError::DatabaseError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+                "INTERNAL_ERROR".to_string(),
+            ),
+        };
+
+        let body = Json(ErrorResponse {
+            success: false,
+            error,
+            code,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeResponse {
+    id: i64,
+    name: String,
+    enabled: bool,
+    team_id: Option<i64>,
+    quota_tokens: Option<i64>,
+    used_tokens: i64,
+    /// `None` without a quota.
+    remaining_tokens: Option<i64>,
+    quota_period: QuotaPeriod,
+    quota_timezone: String,
+    quota_period_started_at: Option<String>,
+    tokens_used_today: i64,
+    /// The server-wide daily limit, if one is set.
+    tokens_per_day: Option<i64>,
+    scopes: Scopes,
+    /// The key this request was made with.
+    key: ApiKey,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DailyQuery {
+    days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyResponse {
+    days: u32,
+    data: Vec<DailyUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogsQuery {
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsResponse {
+    logs: Vec<UsageLog>,
+    total: u64,
+    limit: u32,
+    offset: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeysResponse {
+    keys: Vec<ApiKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateKeyResponse {
+    #[serde(flatten)]
+    key: ApiKey,
+    api_key: String,
+}
+
+/// Quota and usage of the calling user. Still answers once the quota is
+/// exhausted, which is when users most need it.
+pub async fn get_me(
+    State(state): State<AppState>,
+    ApiKeyIdentity { user, key }: ApiKeyIdentity,
+) -> Result<Json<MeResponse>, MeError> {
+    let tokens_used_today =
+        tokens_used_today(&state, &user).map_err(|e| MeError::DatabaseError(e.to_string()))?;
+    let tokens_per_day = load_server_config(&state.db)
+        .ok()
+        .and_then(|config| config.rate_limits.tokens_per_day);
+
+    Ok(Json(MeResponse {
+        id: user.id,
+        name: user.name,
+        enabled: user.enabled,
+        team_id: user.team_id,
+        quota_tokens: user.quota_tokens,
+        used_tokens: user.used_tokens,
+        remaining_tokens: user
+            .quota_tokens
+            .map(|quota| (quota - user.used_tokens).max(0)),
+        quota_period: user.quota_period,
+        quota_timezone: user.quota_timezone,
+        quota_period_started_at: user.quota_period_started_at,
+        tokens_used_today,
+        tokens_per_day,
+        scopes: user.scopes,
+        key,
+    }))
+}
+
+pub async fn get_daily_usage(
+    State(state): State<AppState>,
+    ApiKeyIdentity { user, .. }: ApiKeyIdentity,
+    Query(query): Query<DailyQuery>,
+) -> Result<Json<DailyResponse>, MeError> {
+    let days = query.days.unwrap_or(30).min(90);
+    let data = state
+        .db
+        .get_daily_usage(days, Some(user.id), None, None)
+        .map_err(|e| MeError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(DailyResponse { days, data }))
+}
+
+pub async fn get_logs(
+    State(state): State<AppState>,
+    ApiKeyIdentity { user, .. }: ApiKeyIdentity,
+    Query(query): Query<LogsQuery>,
+) -> Result<Json<LogsResponse>, MeError> {
+    let limit = query.limit.unwrap_or(50).min(200);
+    let offset = query.offset.unwrap_or(0);
+
+    let (logs, total) = state
+        .db
+        .get_usage_logs_paginated(limit, offset, Some(user.id), None)
+        .map_err(|e| MeError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(LogsResponse {
+        logs,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+pub async fn list_keys(
+    State(state): State<AppState>,
+    ApiKeyIdentity { user, .. }: ApiKeyIdentity,
+) -> Result<Json<KeysResponse>, MeError> {
+    let keys = state
+        .db
+        .list_api_keys(user.id)
+        .map_err(|e| MeError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(KeysResponse { keys }))
+}
+
+/// Replaces the key this request was made with. The old key stops working
+/// immediately; the new one is shown only in this response.
+pub async fn rotate_key(
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    ApiKeyIdentity { user, key }: ApiKeyIdentity,
+) -> Result<(StatusCode, Json<RotateKeyResponse>), MeError> {
+    let (new_key, api_key) = state
+        .db
+        .rotate_api_key(user.id, key.id)
+        .map_err(|e| MeError::DatabaseError(e.to_string()))?
+        .ok_or(MeError::KeyNotActive)?;
+    state.key_cache.invalidate_key(key.id);
+
+    let event = AuditEvent::new("api_key.rotate")
+        .target("api_key", key.id)
+        .after(&new_key);
+    if let Err(e) = state
+        .db
+        .record_audit_as(None, &format!("user:{}", user.name), Some(&ip), &event)
+    {
+        tracing::warn!("Failed to write audit log entry {}: {}", event.action, e);
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(RotateKeyResponse {
+            key: new_key,
+            api_key,
+        }),
+    ))
+}
+
+/// Mounted under `/api/me`.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_me))
+        .route("/usage/daily", get(get_daily_usage))
+        .route("/logs", get(get_logs))
+        .route("/keys", get(list_keys))
+        .route("/keys/rotate", post(rotate_key))
+}
+
+/// `/v1/me`, next to the proxy routes clients are already configured for.
+pub fn v1_router() -> Router<AppState> {
+    Router::new().route("/me", get(get_me))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cliproxy::{
+        MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe,
+    };
+    use crate::db::Database;
+    use crate::middleware::key_cache::ApiKeyCache;
+    use crate::middleware::rate_limit::RateLimiter;
+    use axum::{body::Body, http::Request};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn create_app(db: Database) -> Router {
+        let state = AppState {
+            db,
+            rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
+            proxy_client: Arc::new(MockProxyManagementClient::default()),
+            proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
+        };
+        Router::new()
+            .nest("/api/me", router())
+            .nest("/v1", v1_router())
+            .with_state(state)
+    }
+
+    fn key_request(method: &str, uri: &str, api_key: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn json_body(response: axum::response::Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_user_can_still_see_their_quota() {
+        let db = Database::new_in_memory().unwrap();
+        let (user, api_key) = db.create_user("alice", Some(1000)).unwrap();
+        db.log_usage(user.id, "openai", "gpt-4o", 600, 600, 100, "success").unwrap();
+        let (bob, _) = db.create_user("bob", None).unwrap();
+        db.log_usage(bob.id, "openai", "gpt-4o", 5, 5, 100, "success").unwrap();
+        let app = create_app(db);
+
+        let response = app.clone().oneshot(key_request("GET", "/v1/me", &api_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let me = json_body(response).await;
+        assert_eq!(me["name"], "alice");
+        assert_eq!(me["quotaTokens"], 1000);
+        assert_eq!(me["usedTokens"], 1200);
+        assert_eq!(me["remainingTokens"], 0);
+        assert_eq!(me["tokensUsedToday"], 1200);
+        assert_eq!(me["key"]["name"], "default");
+
+        let response = app.clone().oneshot(key_request("GET", "/api/me/logs", &api_key)).await.unwrap();
+        let logs = json_body(response).await;
+        assert_eq!(logs["total"], 1);
+        assert_eq!(logs["logs"][0]["userId"], user.id);
+
+        let response = app.oneshot(key_request("GET", "/api/me", "sk-pp-bogus")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_rotating_own_key_revokes_the_old_one() {
+        let db = Database::new_in_memory().unwrap();
+        let (_, old_key) = db.create_user("alice", None).unwrap();
+        let app = create_app(db);
+
+        let response = app.clone().oneshot(key_request("GET", "/api/me", &old_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(key_request("POST", "/api/me/keys/rotate", &old_key))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let rotated = json_body(response).await;
+        let new_key = rotated["apiKey"].as_str().unwrap();
+
+        let response = app.clone().oneshot(key_request("GET", "/api/me", &old_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app.oneshot(key_request("GET", "/api/me/keys", new_key)).await.unwrap();
+        let keys = json_body(response).await;
+        let keys = keys["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys.iter().filter(|k| k["active"] == true).count(), 1);
+    }
+}
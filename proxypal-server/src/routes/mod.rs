@@ -1,8 +1,15 @@
+pub mod admin_tokens;
+pub mod admins;
+pub mod api_keys;
+pub mod audit;
 pub mod auth;
 pub mod config;
 pub mod logs;
+pub mod me;
+pub mod oidc;
 pub mod providers;
 pub mod proxy;
+pub mod teams;
 pub mod usage;
 pub mod users;
 pub mod v1_proxy;
@@ -7,7 +7,10 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::middleware::admin_auth::AdminSession;
+use crate::db::audit::AuditEvent;
+use crate::middleware::admin_auth::{AdminSession, RequireAdmin};
+use crate::middleware::client_ip::ClientIp;
+use crate::routes::audit;
 use crate::AppState;
 
 const VALID_PROVIDERS: &[&str] = &["claude", "chatgpt", "gemini", "copilot"];
@@ -115,7 +118,8 @@ impl IntoResponse for ProviderError {
 
 pub async fn start_oauth(
     State(state): State<AppState>,
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     Path(provider): Path<String>,
 ) -> Result<Json<OAuthStartResponse>, ProviderError> {
     if !is_valid_provider(&provider) {
@@ -128,6 +132,12 @@ pub async fn start_oauth(
         .await
         .map_err(|e| ProviderError::ProxyError(e.to_string()))?;
 
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("provider.oauth_start").target("provider", &provider),
+    );
     Ok(Json(OAuthStartResponse {
         auth_url,
         state: oauth_state,
@@ -354,15 +364,30 @@ pub async fn get_provider_status(
 
 pub async fn update_provider_settings(
     State(state): State<AppState>,
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     Path(provider): Path<String>,
     Json(payload): Json<UpdateProviderSettingsRequest>,
 ) -> Result<Json<ProviderSummary>, ProviderError> {
+    let before = state
+        .db
+        .get_provider_by_name(&provider)
+        .map_err(|e| ProviderError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ProviderError::NotFound(provider.clone()))?;
     let updated = state
         .db
         .update_provider(&provider, None, Some(&payload.settings))
         .map_err(|e| ProviderError::DatabaseError(e.to_string()))?
         .ok_or_else(|| ProviderError::NotFound(provider.clone()))?;
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("provider.update_settings")
+            .target("provider", &updated.name)
+            .before(&before.settings)
+            .after(&updated.settings),
+    );
 
     let accounts_count = state
         .db
@@ -390,9 +415,15 @@ pub async fn update_provider_settings(
 
 pub async fn delete_provider(
     State(state): State<AppState>,
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     Path(provider): Path<String>,
 ) -> Result<Json<SuccessResponse>, ProviderError> {
+    let before = state
+        .db
+        .get_provider_by_name(&provider)
+        .map_err(|e| ProviderError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ProviderError::NotFound(provider.clone()))?;
     let deleted = state
         .db
         .delete_provider(&provider)
@@ -402,6 +433,12 @@ pub async fn delete_provider(
         return Err(ProviderError::NotFound(provider));
     }
 
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("provider.delete").target("provider", &provider).before(&before),
+    );
     Ok(Json(SuccessResponse { success: true }))
 }
 
@@ -421,8 +458,12 @@ pub fn oauth_callback_router() -> Router<AppState> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyProviderStatus};
+    use crate::cliproxy::{
+        MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe,
+        ProxyProviderStatus,
+    };
     use crate::db::Database;
+    use crate::middleware::key_cache::ApiKeyCache;
     use crate::middleware::rate_limit::RateLimiter;
     use axum::{body::Body, http::Request};
     use base64::{engine::general_purpose::STANDARD, Engine};
@@ -460,8 +501,12 @@ mod tests {
         let state = AppState {
             db,
             rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
             proxy_client: mock,
             proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         };
 
         Router::new()
@@ -475,7 +520,10 @@ mod tests {
     }
 
     fn create_app_with_session(db: Database, session_id: &str) -> (Router, String) {
-        db.create_session(session_id, "csrf-token", 7).unwrap();
+        let admin = db
+            .create_admin("admin", "", crate::db::admins::AdminRole::Owner)
+            .unwrap();
+        db.create_session(session_id, "csrf-token", admin.id, 7).unwrap();
         (create_app(db), session_id.to_string())
     }
 
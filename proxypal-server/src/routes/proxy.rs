@@ -9,7 +9,10 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 use crate::cliproxy::config_gen::{generate_proxy_config, load_server_config};
-use crate::middleware::admin_auth::AdminSession;
+use crate::db::audit::AuditEvent;
+use crate::middleware::admin_auth::{AdminSession, RequireAdmin};
+use crate::middleware::client_ip::ClientIp;
+use crate::routes::audit;
 use crate::AppState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -117,7 +120,8 @@ pub async fn get_proxy_status(
 }
 
 pub async fn start_proxy(
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
 ) -> Result<(StatusCode, Json<StartProxyResponse>), ProxyError> {
     if state.proxy_manager.is_running() {
@@ -138,6 +142,7 @@ pub async fn start_proxy(
         .await
         .map_err(|e| ProxyError::Internal(format!("Failed to start proxy: {}", e)))?;
 
+    audit::record(&state, &session.admin, &ip, AuditEvent::new("proxy.start"));
     Ok((
         StatusCode::OK,
         Json(StartProxyResponse {
@@ -150,16 +155,19 @@ pub async fn start_proxy(
 }
 
 pub async fn stop_proxy(
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
 ) -> Result<Json<StopProxyResponse>, ProxyError> {
     let _ = state.proxy_manager.stop().await;
 
+    audit::record(&state, &session.admin, &ip, AuditEvent::new("proxy.stop"));
     Ok(Json(StopProxyResponse { success: true }))
 }
 
 pub async fn restart_proxy(
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
 ) -> Result<Json<RestartProxyResponse>, ProxyError> {
     let _ = state.proxy_manager.stop().await;
@@ -178,6 +186,7 @@ pub async fn restart_proxy(
         .await
         .map_err(|e| ProxyError::Internal(format!("Failed to start proxy: {}", e)))?;
 
+    audit::record(&state, &session.admin, &ip, AuditEvent::new("proxy.restart"));
     Ok(Json(RestartProxyResponse {
         success: true,
         pid: Some(pid),
@@ -196,8 +205,9 @@ pub fn router() -> Router<AppState> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager};
+    use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
     use crate::db::Database;
+    use crate::middleware::key_cache::ApiKeyCache;
     use crate::middleware::rate_limit::RateLimiter;
     use axum::{body::Body, http::Request};
     use base64::{engine::general_purpose::STANDARD, Engine};
@@ -225,8 +235,12 @@ mod tests {
         AppState {
             db,
             rate_limiter,
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
             proxy_client,
             proxy_manager,
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         }
     }
 
@@ -237,9 +251,13 @@ mod tests {
     }
 
     fn create_app_with_session(state: AppState, session_id: &str) -> (axum::Router, String) {
+        let admin = state
+            .db
+            .create_admin("admin", "", crate::db::admins::AdminRole::Owner)
+            .unwrap();
         state
             .db
-            .create_session(session_id, "csrf-token", 7)
+            .create_session(session_id, "csrf-token", admin.id, 7)
             .unwrap();
         (create_app(state), session_id.to_string())
     }
@@ -529,7 +547,10 @@ mod tests {
             mock_manager.set_running(true, 12345);
         }
 
-        state.db.create_session("test-session", "csrf-token", 7).unwrap();
+        let admin = state.db
+            .create_admin("admin", "", crate::db::admins::AdminRole::Owner)
+            .unwrap();
+        state.db.create_session("test-session", "csrf-token", admin.id, 7).unwrap();
         let app = create_app(state.clone());
 
         let request = Request::builder()
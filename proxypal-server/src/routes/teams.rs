@@ -0,0 +1,560 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use super::users::explicit_null;
+use crate::db::audit::AuditEvent;
+use crate::db::quota::{parse_timezone, QuotaPeriod};
+use crate::db::teams::Team;
+use crate::db::users::{User, UserLimits};
+use crate::middleware::admin_auth::{AdminSession, RequireAdmin};
+use crate::middleware::client_ip::ClientIp;
+use crate::routes::audit;
+use crate::AppState;
+
+#[derive(Debug)]
+pub enum TeamError {
+    NotFound,
+    Conflict(String),
+    Validation(String),
+    DatabaseError(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub error: String,
+    pub code: String,
+}
+
+impl IntoResponse for TeamError {
+    fn into_response(self) -> axum::response::Response {
+        let (status, error, code) = match self {
+            TeamError::NotFound => (
+                StatusCode::NOT_FOUND,
+                "Team not found".to_string(),
+                "NOT_FOUND".to_string(),
+            ),
+            TeamError::Conflict(msg) => (StatusCode::CONFLICT, msg, "CONFLICT".to_string()),
+            TeamError::Validation(msg) => (
+                StatusCode::BAD_REQUEST,
+                msg,
+                "VALIDATION_ERROR".to_string(),
+            ),
+            TeamError::DatabaseError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+                "INTERNAL_ERROR".to_string(),
+            ),
+        };
+
+        let body = Json(ErrorResponse {
+            success: false,
+            error,
+            code,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+fn db_error(e: anyhow::Error) -> TeamError {
+    let msg = e.to_string();
+    if msg.contains("already exists") {
+        TeamError::Conflict(msg)
+    } else {
+        TeamError::DatabaseError(msg)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTeamsResponse {
+    teams: Vec<Team>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTeamRequest {
+    name: String,
+    quota_tokens: Option<i64>,
+    quota_period: Option<QuotaPeriod>,
+    /// IANA timezone for period boundaries; defaults to UTC.
+    quota_timezone: Option<String>,
+    #[serde(flatten)]
+    limits: UserLimits,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateTeamRequest {
+    name: Option<String>,
+    /// `null` removes the shared quota; omitting the field leaves it unchanged.
+    #[serde(default, deserialize_with = "explicit_null")]
+    quota_tokens: Option<Option<i64>>,
+    quota_period: Option<QuotaPeriod>,
+    quota_timezone: Option<String>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    requests_per_minute: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    tokens_per_minute: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    max_concurrent_requests: Option<Option<i64>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamMembersResponse {
+    members: Vec<User>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetUsageResponse {
+    success: bool,
+    previous_used_tokens: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteResponse {
+    success: bool,
+}
+
+fn validate_team(
+    name: Option<&str>,
+    quota_tokens: Option<i64>,
+    quota_timezone: Option<&str>,
+    limits: &UserLimits,
+) -> Result<(), TeamError> {
+    if name.is_some_and(|n| n.trim().is_empty()) {
+        return Err(TeamError::Validation("name must not be empty".to_string()));
+    }
+    if let Some(tz) = quota_timezone.filter(|tz| parse_timezone(tz).is_none()) {
+        return Err(TeamError::Validation(format!("Unknown timezone: {}", tz)));
+    }
+    let values = [
+        ("quotaTokens", quota_tokens),
+        ("requestsPerMinute", limits.requests_per_minute),
+        ("tokensPerMinute", limits.tokens_per_minute),
+        ("maxConcurrentRequests", limits.max_concurrent_requests),
+    ];
+    for (field, value) in values {
+        if value.is_some_and(|v| v < 0) {
+            return Err(TeamError::Validation(format!("{} must not be negative", field)));
+        }
+    }
+    Ok(())
+}
+
+pub async fn list_teams(
+    _session: AdminSession,
+    State(state): State<AppState>,
+) -> Result<Json<ListTeamsResponse>, TeamError> {
+    let teams = state
+        .db
+        .list_teams()
+        .map_err(|e| TeamError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(ListTeamsResponse { teams }))
+}
+
+pub async fn create_team(
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(payload): Json<CreateTeamRequest>,
+) -> Result<(StatusCode, Json<Team>), TeamError> {
+    validate_team(
+        Some(&payload.name),
+        payload.quota_tokens,
+        payload.quota_timezone.as_deref(),
+        &payload.limits,
+    )?;
+
+    let quota_period = (payload.quota_period.is_some() || payload.quota_timezone.is_some()).then(|| {
+        (
+            payload.quota_period.unwrap_or_default(),
+            payload.quota_timezone.as_deref().unwrap_or("UTC"),
+        )
+    });
+    let team = state
+        .db
+        .create_team_with(&payload.name, payload.quota_tokens, &payload.limits, quota_period)
+        .map_err(db_error)?;
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("team.create").target("team", team.id).after(&team),
+    );
+    Ok((StatusCode::CREATED, Json(team)))
+}
+
+pub async fn get_team(
+    _session: AdminSession,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Team>, TeamError> {
+    let team = state
+        .db
+        .get_team(id)
+        .map_err(|e| TeamError::DatabaseError(e.to_string()))?
+        .ok_or(TeamError::NotFound)?;
+
+    Ok(Json(team))
+}
+
+pub async fn update_team(
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(payload): Json<UpdateTeamRequest>,
+) -> Result<Json<Team>, TeamError> {
+    let before = state
+        .db
+        .get_team(id)
+        .map_err(|e| TeamError::DatabaseError(e.to_string()))?
+        .ok_or(TeamError::NotFound)?;
+
+    let limits_changed = payload.requests_per_minute.is_some()
+        || payload.tokens_per_minute.is_some()
+        || payload.max_concurrent_requests.is_some();
+    let limits = UserLimits {
+        requests_per_minute: payload
+            .requests_per_minute
+            .unwrap_or(before.limits.requests_per_minute),
+        tokens_per_minute: payload
+            .tokens_per_minute
+            .unwrap_or(before.limits.tokens_per_minute),
+        max_concurrent_requests: payload
+            .max_concurrent_requests
+            .unwrap_or(before.limits.max_concurrent_requests),
+    };
+    validate_team(
+        payload.name.as_deref(),
+        payload.quota_tokens.flatten(),
+        payload.quota_timezone.as_deref(),
+        &limits,
+    )?;
+
+    let quota_period = (payload.quota_period.is_some() || payload.quota_timezone.is_some()).then(|| {
+        (
+            payload.quota_period.unwrap_or(before.quota_period),
+            payload
+                .quota_timezone
+                .as_deref()
+                .unwrap_or(&before.quota_timezone),
+        )
+    });
+    let team = state
+        .db
+        .update_team(
+            id,
+            payload.name.as_deref(),
+            payload.quota_tokens,
+            limits_changed.then_some(&limits),
+            quota_period,
+        )
+        .map_err(db_error)?
+        .ok_or(TeamError::NotFound)?;
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("team.update").target("team", id).before(&before).after(&team),
+    );
+    Ok(Json(team))
+}
+
+pub async fn delete_team(
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<DeleteResponse>, TeamError> {
+    let before = state
+        .db
+        .get_team(id)
+        .map_err(|e| TeamError::DatabaseError(e.to_string()))?
+        .ok_or(TeamError::NotFound)?;
+    let deleted = state
+        .db
+        .delete_team(id)
+        .map_err(|e| TeamError::DatabaseError(e.to_string()))?;
+
+    if !deleted {
+        return Err(TeamError::NotFound);
+    }
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("team.delete").target("team", id).before(&before),
+    );
+    Ok(Json(DeleteResponse { success: true }))
+}
+
+pub async fn list_members(
+    _session: AdminSession,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<TeamMembersResponse>, TeamError> {
+    state
+        .db
+        .get_team(id)
+        .map_err(|e| TeamError::DatabaseError(e.to_string()))?
+        .ok_or(TeamError::NotFound)?;
+
+    let members = state
+        .db
+        .list_team_members(id)
+        .map_err(|e| TeamError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(TeamMembersResponse { members }))
+}
+
+pub async fn reset_usage(
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<ResetUsageResponse>, TeamError> {
+    let previous_used_tokens = state
+        .db
+        .reset_team_used_tokens(id)
+        .map_err(|e| TeamError::DatabaseError(e.to_string()))?
+        .ok_or(TeamError::NotFound)?;
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("team.reset_usage")
+            .target("team", id)
+            .before(&serde_json::json!({ "usedTokens": previous_used_tokens }))
+            .after(&serde_json::json!({ "usedTokens": 0 })),
+    );
+    Ok(Json(ResetUsageResponse {
+        success: true,
+        previous_used_tokens,
+    }))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_teams).post(create_team))
+        .route("/:id", get(get_team).put(update_team).delete(delete_team))
+        .route("/:id/members", get(list_members))
+        .route("/:id/reset-usage", post(reset_usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    fn create_app(db: Database) -> (Router, String) {
+        use std::sync::Arc;
+        use crate::middleware::key_cache::ApiKeyCache;
+        use crate::middleware::rate_limit::RateLimiter;
+        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
+
+        let session_id = "test-session-id";
+        let admin = db
+            .create_admin("admin", "", crate::db::admins::AdminRole::Owner)
+            .unwrap();
+        db.create_session(session_id, "test-csrf-token", admin.id, 7).unwrap();
+
+        let state = AppState {
+            db,
+            rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
+            proxy_client: Arc::new(MockProxyManagementClient::default()),
+            proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
+        };
+        let app = Router::new()
+            .nest("/api/teams", router())
+            .with_state(state);
+
+        (app, session_id.to_string())
+    }
+
+    fn authed_request(method: &str, uri: &str, session_id: &str, body: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Cookie", format!("session={}", session_id));
+
+        if body.is_some() {
+            builder = builder.header("Content-Type", "application/json");
+        }
+
+        builder
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or(Body::empty()))
+            .unwrap()
+    }
+
+    async fn json_body(response: axum::response::Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_team_crud() {
+        let db = Database::new_in_memory().unwrap();
+        let (app, session_id) = create_app(db);
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "POST",
+                "/api/teams",
+                &session_id,
+                Some(r#"{"name":"research","quotaTokens":5000,"quotaPeriod":"weekly","requestsPerMinute":30}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let team = json_body(response).await;
+        assert_eq!(team["name"], "research");
+        assert_eq!(team["quotaTokens"], 5000);
+        assert_eq!(team["quotaPeriod"], "weekly");
+        assert_eq!(team["quotaTimezone"], "UTC");
+        assert!(team["quotaPeriodStartedAt"].is_string());
+        assert_eq!(team["requestsPerMinute"], 30);
+        assert_eq!(team["memberCount"], 0);
+        let id = team["id"].as_i64().unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "POST",
+                "/api/teams",
+                &session_id,
+                Some(r#"{"name":"research"}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "PUT",
+                &format!("/api/teams/{}", id),
+                &session_id,
+                Some(r#"{"quotaTokens":null,"tokensPerMinute":1000}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let team = json_body(response).await;
+        assert!(team["quotaTokens"].is_null());
+        assert_eq!(team["requestsPerMinute"], 30);
+        assert_eq!(team["tokensPerMinute"], 1000);
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "PUT",
+                &format!("/api/teams/{}", id),
+                &session_id,
+                Some(r#"{"requestsPerMinute":-1}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "PUT",
+                &format!("/api/teams/{}", id),
+                &session_id,
+                Some(r#"{"quotaPeriod":"daily","quotaTimezone":"Mars/Olympus_Mons"}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(authed_request("DELETE", &format!("/api/teams/{}", id), &session_id, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(authed_request("GET", &format!("/api/teams/{}", id), &session_id, None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_members_and_reset_usage() {
+        let db = Database::new_in_memory().unwrap();
+        let team = db.create_team("research", Some(1000), &UserLimits::default()).unwrap();
+        let (alice, _) = db.create_user("alice", None).unwrap();
+        db.create_user("bob", None).unwrap();
+        db.set_user_team(alice.id, Some(team.id)).unwrap();
+        db.log_usage(alice.id, "openai", "gpt-4o", 100, 50, 10, "success").unwrap();
+        let (app, session_id) = create_app(db);
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "GET",
+                &format!("/api/teams/{}/members", team.id),
+                &session_id,
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let members = json_body(response).await;
+        let names: Vec<_> = members["members"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, vec!["alice"]);
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "POST",
+                &format!("/api/teams/{}/reset-usage", team.id),
+                &session_id,
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json_body(response).await["previousUsedTokens"], 150);
+
+        let response = app
+            .oneshot(authed_request("GET", &format!("/api/teams/{}", team.id), &session_id, None))
+            .await
+            .unwrap();
+        assert_eq!(json_body(response).await["usedTokens"], 0);
+    }
+}
@@ -8,13 +8,14 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::db::usage::{DailyUsage, UsageLog};
+use crate::db::usage::{DailyTeamUsage, DailyUsage, TeamUsage, UsageLog};
 use crate::middleware::admin_auth::AdminSession;
 use crate::AppState;
 
 #[derive(Debug)]
 pub enum UsageError {
     UserNotFound,
+    TeamNotFound,
     DatabaseError(String),
 }
 
@@ -33,6 +34,11 @@ impl IntoResponse for UsageError {
                 "User not found".to_string(),
                 "NOT_FOUND".to_string(),
             ),
+            UsageError::TeamNotFound => (
+                StatusCode::NOT_FOUND,
+                "Team not found".to_string(),
+                "NOT_FOUND".to_string(),
+            ),
             UsageError::DatabaseError(e) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Database error: {}", e),
@@ -50,9 +56,18 @@ impl IntoResponse for UsageError {
     }
 }
 
+/// Extra breakdown the aggregate usage routes can add.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    Team,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UsageQuery {
     period: Option<String>,
+    team_id: Option<i64>,
+    group_by: Option<GroupBy>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -63,6 +78,11 @@ pub struct UsageResponse {
     total_tokens_input: i64,
     total_tokens_output: i64,
     by_provider: HashMap<String, ProviderUsageResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    team_id: Option<i64>,
+    /// Present with `group_by=team`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    by_team: Option<Vec<TeamUsage>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -90,6 +110,8 @@ pub struct DailyUsageQuery {
     days: Option<u32>,
     user_id: Option<i64>,
     provider: Option<String>,
+    team_id: Option<i64>,
+    group_by: Option<GroupBy>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -97,6 +119,9 @@ pub struct DailyUsageQuery {
 pub struct DailyUsageResponse {
     days: u32,
     data: Vec<DailyUsage>,
+    /// Present with `group_by=team`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    by_team: Option<Vec<DailyTeamUsage>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -122,17 +147,32 @@ pub async fn get_usage(
     Query(query): Query<UsageQuery>,
 ) -> Result<Json<UsageResponse>, UsageError> {
     let period = query.period.unwrap_or_else(|| "month".to_string());
+    ensure_team_exists(&state, query.team_id)?;
 
     let stats = state
         .db
-        .get_usage_stats(&period)
+        .get_usage_stats(&period, query.team_id)
         .map_err(|e| UsageError::DatabaseError(e.to_string()))?;
 
     let provider_usage = state
         .db
-        .get_usage_by_provider(&period)
+        .get_usage_by_provider(&period, query.team_id)
         .map_err(|e| UsageError::DatabaseError(e.to_string()))?;
 
+    let by_team = match query.group_by {
+        Some(GroupBy::Team) => {
+            let mut teams = state
+                .db
+                .get_usage_by_team(&period)
+                .map_err(|e| UsageError::DatabaseError(e.to_string()))?;
+            if let Some(team_id) = query.team_id {
+                teams.retain(|t| t.team_id == Some(team_id));
+            }
+            Some(teams)
+        }
+        None => None,
+    };
+
     let by_provider: HashMap<String, ProviderUsageResponse> = provider_usage
         .into_iter()
         .map(|p| {
@@ -153,9 +193,22 @@ pub async fn get_usage(
         total_tokens_input: stats.total_tokens_input,
         total_tokens_output: stats.total_tokens_output,
         by_provider,
+        team_id: query.team_id,
+        by_team,
     }))
 }
 
+fn ensure_team_exists(state: &AppState, team_id: Option<i64>) -> Result<(), UsageError> {
+    if let Some(team_id) = team_id {
+        state
+            .db
+            .get_team(team_id)
+            .map_err(|e| UsageError::DatabaseError(e.to_string()))?
+            .ok_or(UsageError::TeamNotFound)?;
+    }
+    Ok(())
+}
+
 pub async fn get_user_usage(
     _session: AdminSession,
     State(state): State<AppState>,
@@ -194,13 +247,24 @@ pub async fn get_daily_usage(
     Query(query): Query<DailyUsageQuery>,
 ) -> Result<Json<DailyUsageResponse>, UsageError> {
     let days = query.days.unwrap_or(30).min(90);
+    ensure_team_exists(&state, query.team_id)?;
 
     let data = state
         .db
-        .get_daily_usage(days, query.user_id, query.provider.as_deref())
+        .get_daily_usage(days, query.user_id, query.provider.as_deref(), query.team_id)
         .map_err(|e| UsageError::DatabaseError(e.to_string()))?;
 
-    Ok(Json(DailyUsageResponse { days, data }))
+    let by_team = match query.group_by {
+        Some(GroupBy::Team) => Some(
+            state
+                .db
+                .get_daily_usage_by_team(days, query.user_id, query.provider.as_deref(), query.team_id)
+                .map_err(|e| UsageError::DatabaseError(e.to_string()))?,
+        ),
+        None => None,
+    };
+
+    Ok(Json(DailyUsageResponse { days, data, by_team }))
 }
 
 pub async fn get_logs(
@@ -249,18 +313,26 @@ mod tests {
 
     fn create_app(db: Database) -> (Router, String) {
         use std::sync::Arc;
+        use crate::middleware::key_cache::ApiKeyCache;
         use crate::middleware::rate_limit::RateLimiter;
-        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager};
+        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
         
         let session_id = "test-session-id";
         let csrf_token = "test-csrf-token";
-        db.create_session(session_id, csrf_token, 7).unwrap();
+        let admin = db
+            .create_admin("admin", "", crate::db::admins::AdminRole::Owner)
+            .unwrap();
+        db.create_session(session_id, csrf_token, admin.id, 7).unwrap();
 
         let state = AppState { 
             db, 
             rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
             proxy_client: Arc::new(MockProxyManagementClient::default()),
             proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         };
         let app = Router::new()
             .nest("/api/usage", router())
@@ -319,6 +391,66 @@ mod tests {
         assert!(json.data.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_usage_filtered_and_grouped_by_team() {
+        let (db, _dir) = create_test_db();
+        let team = db
+            .create_team("research", None, &crate::db::users::UserLimits::default())
+            .unwrap();
+        let (alice, _) = db.create_user("alice", None).unwrap();
+        let (bob, _) = db.create_user("bob", None).unwrap();
+        db.set_user_team(alice.id, Some(team.id)).unwrap();
+        db.log_usage(alice.id, "openai", "gpt-4o", 100, 50, 10, "success").unwrap();
+        db.log_usage(alice.id, "claude", "claude-sonnet-4", 10, 5, 10, "success").unwrap();
+        db.log_usage(bob.id, "openai", "gpt-4o", 1000, 500, 10, "success").unwrap();
+        let (app, session_id) = create_app(db);
+
+        let uri = format!("/api/usage?team_id={}&group_by=team", team.id);
+        let response = app.clone().oneshot(authed_request("GET", &uri, &session_id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: UsageResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.total_requests, 2);
+        assert_eq!(json.total_tokens_input, 110);
+        assert_eq!(json.by_provider["openai"].tokens_input, 100);
+        let by_team = json.by_team.unwrap();
+        assert_eq!(by_team.len(), 1);
+        assert_eq!(by_team[0].team_name.as_deref(), Some("research"));
+
+        let response = app
+            .clone()
+            .oneshot(authed_request("GET", "/api/usage/daily?group_by=team", &session_id))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: DailyUsageResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.data[0].requests, 3);
+        let by_team = json.by_team.unwrap();
+        assert_eq!(by_team.len(), 2);
+        assert_eq!(by_team[0].team_id, Some(team.id));
+        assert_eq!(by_team[1].team_id, None);
+        assert_eq!(by_team[1].tokens_input, 1000);
+
+        let uri = format!("/api/usage/daily?team_id={}", team.id);
+        let response = app.clone().oneshot(authed_request("GET", &uri, &session_id)).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: DailyUsageResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.data[0].tokens_input, 110);
+        assert!(json.by_team.is_none());
+
+        let response = app
+            .oneshot(authed_request("GET", "/api/usage?team_id=999", &session_id))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_get_logs_returns_empty_list_initially() {
         let (db, _dir) = create_test_db();
@@ -380,15 +512,20 @@ mod tests {
     #[tokio::test]
     async fn test_unauthenticated_request_returns_401() {
         use std::sync::Arc;
+        use crate::middleware::key_cache::ApiKeyCache;
         use crate::middleware::rate_limit::RateLimiter;
-        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager};
+        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
         
         let (db, _dir) = create_test_db();
         let state = AppState { 
             db, 
             rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
             proxy_client: Arc::new(MockProxyManagementClient::default()),
             proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         };
         let app = Router::new()
             .nest("/api/usage", router())
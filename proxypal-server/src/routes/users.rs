@@ -5,16 +5,23 @@ use axum::{
     routing::{get, post},
     Json, Router,
 };
-use serde::{Deserialize, Serialize};
-
-use crate::db::users::User;
-use crate::middleware::admin_auth::AdminSession;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+
+use crate::db::audit::AuditEvent;
+use crate::db::quota::{parse_timezone, QuotaPeriod, QuotaPeriodRecord};
+use crate::db::scopes::Scopes;
+use crate::db::users::{User, UserChanges, UserLimits};
+use crate::middleware::admin_auth::{AdminSession, RequireAdmin};
+use crate::middleware::client_ip::ClientIp;
+use crate::routes::audit;
 use crate::AppState;
 
 #[derive(Debug)]
 pub enum UserError {
     NotFound,
     Conflict(String),
+    Validation(String),
     DatabaseError(String),
 }
 
@@ -34,6 +41,11 @@ impl IntoResponse for UserError {
                 "NOT_FOUND".to_string(),
             ),
             UserError::Conflict(msg) => (StatusCode::CONFLICT, msg, "CONFLICT".to_string()),
+            UserError::Validation(msg) => (
+                StatusCode::BAD_REQUEST,
+                msg,
+                "VALIDATION_ERROR".to_string(),
+            ),
             UserError::DatabaseError(e) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Database error: {}", e),
@@ -71,6 +83,15 @@ pub struct ListUsersResponse {
 pub struct CreateUserRequest {
     name: String,
     quota_tokens: Option<i64>,
+    /// Replaced by `scopes.models`; see [`reject_allowed_models`].
+    allowed_models: Option<serde_json::Value>,
+    #[serde(flatten)]
+    limits: UserLimits,
+    quota_period: Option<QuotaPeriod>,
+    quota_timezone: Option<String>,
+    scopes: Option<Scopes>,
+    team_id: Option<i64>,
+    model_aliases: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -87,6 +108,103 @@ pub struct UpdateUserRequest {
     name: Option<String>,
     quota_tokens: Option<Option<i64>>,
     enabled: Option<bool>,
+    /// Replaced by `scopes.models`; see [`reject_allowed_models`].
+    allowed_models: Option<serde_json::Value>,
+    /// Limit overrides follow the same rule: `null` reverts to the global default.
+    #[serde(default, deserialize_with = "explicit_null")]
+    requests_per_minute: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    tokens_per_minute: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    max_concurrent_requests: Option<Option<i64>>,
+    quota_period: Option<QuotaPeriod>,
+    quota_timezone: Option<String>,
+    /// `null` lifts all scope restrictions.
+    #[serde(default, deserialize_with = "explicit_null")]
+    scopes: Option<Option<Scopes>>,
+    /// `null` removes the user from their team.
+    #[serde(default, deserialize_with = "explicit_null")]
+    team_id: Option<Option<i64>>,
+    /// Replaces all of the user's aliases; `null` or `{}` removes them.
+    #[serde(default, deserialize_with = "explicit_null")]
+    model_aliases: Option<Option<HashMap<String, String>>>,
+}
+
+fn validate_model_aliases(aliases: Option<&HashMap<String, String>>) -> Result<(), UserError> {
+    let Some(aliases) = aliases else {
+        return Ok(());
+    };
+    if aliases.iter().any(|(from, to)| from.trim().is_empty() || to.trim().is_empty()) {
+        return Err(UserError::Validation(
+            "modelAliases entries must map a non-empty model to a non-empty model".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Model allow-lists are set through `scopes.models` now. The old field is
+/// refused rather than ignored, so a restriction is never silently dropped.
+fn reject_allowed_models(allowed_models: Option<&serde_json::Value>) -> Result<(), UserError> {
+    match allowed_models {
+        Some(_) => Err(UserError::Validation(
+            "allowedModels has been replaced by scopes.models".to_string(),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn validate_scopes(scopes: Option<&Scopes>) -> Result<(), UserError> {
+    scopes
+        .map(Scopes::validate)
+        .transpose()
+        .map(|_| ())
+        .map_err(UserError::Validation)
+}
+
+fn validate_team(state: &AppState, team_id: Option<i64>) -> Result<(), UserError> {
+    let Some(team_id) = team_id else {
+        return Ok(());
+    };
+    let team = state
+        .db
+        .get_team(team_id)
+        .map_err(|e| UserError::DatabaseError(e.to_string()))?;
+    match team {
+        Some(_) => Ok(()),
+        None => Err(UserError::Validation(format!("Team {} does not exist", team_id))),
+    }
+}
+
+fn validate_timezone(timezone: Option<&str>) -> Result<(), UserError> {
+    match timezone {
+        Some(tz) if parse_timezone(tz).is_none() => {
+            Err(UserError::Validation(format!("Unknown timezone: {}", tz)))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_limits(limits: &UserLimits) -> Result<(), UserError> {
+    let values = [
+        ("requestsPerMinute", limits.requests_per_minute),
+        ("tokensPerMinute", limits.tokens_per_minute),
+        ("maxConcurrentRequests", limits.max_concurrent_requests),
+    ];
+    for (field, value) in values {
+        if value.is_some_and(|v| v < 0) {
+            return Err(UserError::Validation(format!("{} must not be negative", field)));
+        }
+    }
+    Ok(())
+}
+
+/// Distinguishes an explicit `null` (`Some(None)`) from a missing field (`None`).
+pub(crate) fn explicit_null<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -132,13 +250,31 @@ pub async fn list_users(
 }
 
 pub async fn create_user(
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
     Json(payload): Json<CreateUserRequest>,
 ) -> Result<(StatusCode, Json<CreateUserResponse>), UserError> {
+    validate_limits(&payload.limits)?;
+    validate_timezone(payload.quota_timezone.as_deref())?;
+    reject_allowed_models(payload.allowed_models.as_ref())?;
+    validate_scopes(payload.scopes.as_ref())?;
+    validate_team(&state, payload.team_id)?;
+    validate_model_aliases(payload.model_aliases.as_ref())?;
+
+    let quota_timezone = payload.quota_timezone.as_deref().unwrap_or("UTC");
+    let changes = UserChanges {
+        limits: (payload.limits != UserLimits::default()).then(|| payload.limits.clone()),
+        quota_period: (payload.quota_period.is_some() || payload.quota_timezone.is_some())
+            .then(|| (payload.quota_period.unwrap_or_default(), quota_timezone)),
+        scopes: payload.scopes.as_ref(),
+        team_id: payload.team_id.map(Some),
+        model_aliases: payload.model_aliases.as_ref(),
+        ..Default::default()
+    };
     let (user, api_key) = state
         .db
-        .create_user(&payload.name, payload.quota_tokens)
+        .create_user_with(&payload.name, payload.quota_tokens, &changes)
         .map_err(|e| {
             let msg = e.to_string();
             if msg.contains("already exists") {
@@ -148,6 +284,12 @@ pub async fn create_user(
             }
         })?;
 
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("user.create").target("user", user.id).after(&user),
+    );
     Ok((StatusCode::CREATED, Json(CreateUserResponse { user, api_key })))
 }
 
@@ -166,44 +308,113 @@ pub async fn get_user(
 }
 
 pub async fn update_user(
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
     Path(id): Path<i64>,
     Json(payload): Json<UpdateUserRequest>,
 ) -> Result<Json<User>, UserError> {
+    let limits_changed = payload.requests_per_minute.is_some()
+        || payload.tokens_per_minute.is_some()
+        || payload.max_concurrent_requests.is_some();
+    let requested_limits = UserLimits {
+        requests_per_minute: payload.requests_per_minute.flatten(),
+        tokens_per_minute: payload.tokens_per_minute.flatten(),
+        max_concurrent_requests: payload.max_concurrent_requests.flatten(),
+    };
+    validate_limits(&requested_limits)?;
+    validate_timezone(payload.quota_timezone.as_deref())?;
+    reject_allowed_models(payload.allowed_models.as_ref())?;
+    validate_scopes(payload.scopes.as_ref().and_then(|s| s.as_ref()))?;
+    validate_team(&state, payload.team_id.flatten())?;
+    validate_model_aliases(payload.model_aliases.as_ref().and_then(|a| a.as_ref()))?;
+
+    let before = state
+        .db
+        .get_user_by_id(id)
+        .map_err(|e| UserError::DatabaseError(e.to_string()))?
+        .ok_or(UserError::NotFound)?;
+    let scopes = payload.scopes.map(Option::unwrap_or_default);
+    let model_aliases = payload.model_aliases.map(Option::unwrap_or_default);
+    let changes = UserChanges {
+        name: payload.name.as_deref(),
+        quota_tokens: payload.quota_tokens,
+        enabled: payload.enabled,
+        limits: limits_changed.then(|| UserLimits {
+            requests_per_minute: payload
+                .requests_per_minute
+                .unwrap_or(before.limits.requests_per_minute),
+            tokens_per_minute: payload
+                .tokens_per_minute
+                .unwrap_or(before.limits.tokens_per_minute),
+            max_concurrent_requests: payload
+                .max_concurrent_requests
+                .unwrap_or(before.limits.max_concurrent_requests),
+        }),
+        quota_period: (payload.quota_period.is_some() || payload.quota_timezone.is_some()).then(|| {
+            (
+                payload.quota_period.unwrap_or(before.quota_period),
+                payload
+                    .quota_timezone
+                    .as_deref()
+                    .unwrap_or(&before.quota_timezone),
+            )
+        }),
+        scopes: scopes.as_ref(),
+        team_id: payload.team_id,
+        model_aliases: model_aliases.as_ref(),
+    };
     let user = state
         .db
-        .update_user(
-            id,
-            payload.name.as_deref(),
-            payload.quota_tokens,
-            payload.enabled,
-        )
+        .apply_user_changes(id, &changes)
         .map_err(|e| UserError::DatabaseError(e.to_string()))?
         .ok_or(UserError::NotFound)?;
+    if payload.enabled == Some(false) {
+        state.key_cache.invalidate_user(id);
+    }
 
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("user.update").target("user", id).before(&before).after(&user),
+    );
     Ok(Json(user))
 }
 
 pub async fn delete_user(
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
     Path(id): Path<i64>,
 ) -> Result<Json<DeleteResponse>, UserError> {
+    let before = state
+        .db
+        .get_user_by_id(id)
+        .map_err(|e| UserError::DatabaseError(e.to_string()))?
+        .ok_or(UserError::NotFound)?;
     let deleted = state
         .db
         .delete_user(id)
         .map_err(|e| UserError::DatabaseError(e.to_string()))?;
+    state.key_cache.invalidate_user(id);
 
     if !deleted {
         return Err(UserError::NotFound);
     }
 
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("user.delete").target("user", id).before(&before),
+    );
     Ok(Json(DeleteResponse { success: true }))
 }
 
 pub async fn regenerate_key(
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
     Path(id): Path<i64>,
 ) -> Result<Json<RegenerateKeyResponse>, UserError> {
@@ -212,12 +423,20 @@ pub async fn regenerate_key(
         .regenerate_api_key(id)
         .map_err(|e| UserError::DatabaseError(e.to_string()))?
         .ok_or(UserError::NotFound)?;
-
+    state.key_cache.invalidate_user(id);
+
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("user.regenerate_key").target("user", id),
+    );
     Ok(Json(RegenerateKeyResponse { user, api_key }))
 }
 
 pub async fn reset_usage(
-    _session: AdminSession,
+    session: RequireAdmin,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
     Path(id): Path<i64>,
 ) -> Result<Json<ResetUsageResponse>, UserError> {
@@ -227,18 +446,61 @@ pub async fn reset_usage(
         .map_err(|e| UserError::DatabaseError(e.to_string()))?
         .ok_or(UserError::NotFound)?;
 
+    audit::record(
+        &state,
+        &session.admin,
+        &ip,
+        AuditEvent::new("user.reset_usage")
+            .target("user", id)
+            .before(&serde_json::json!({ "usedTokens": previous_used_tokens }))
+            .after(&serde_json::json!({ "usedTokens": 0 })),
+    );
     Ok(Json(ResetUsageResponse {
         success: true,
         previous_used_tokens,
     }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct QuotaHistoryQuery {
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaHistoryResponse {
+    periods: Vec<QuotaPeriodRecord>,
+}
+
+pub async fn quota_history(
+    _session: AdminSession,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(query): Query<QuotaHistoryQuery>,
+) -> Result<Json<QuotaHistoryResponse>, UserError> {
+    state
+        .db
+        .get_user_by_id(id)
+        .map_err(|e| UserError::DatabaseError(e.to_string()))?
+        .ok_or(UserError::NotFound)?;
+
+    let limit = query.limit.unwrap_or(12).clamp(1, 100);
+    let periods = state
+        .db
+        .list_quota_history(id, limit)
+        .map_err(|e| UserError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(QuotaHistoryResponse { periods }))
+}
+
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(list_users).post(create_user))
         .route("/:id", get(get_user).put(update_user).delete(delete_user))
         .route("/:id/regenerate-key", post(regenerate_key))
         .route("/:id/reset-usage", post(reset_usage))
+        .route("/:id/quota-history", get(quota_history))
+        .nest("/:id/keys", super::api_keys::router())
 }
 
 #[cfg(test)]
@@ -258,18 +520,26 @@ mod tests {
 
     fn create_app(db: Database) -> (Router, String) {
         use std::sync::Arc;
+        use crate::middleware::key_cache::ApiKeyCache;
         use crate::middleware::rate_limit::RateLimiter;
-        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager};
+        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
         
         let session_id = "test-session-id";
         let csrf_token = "test-csrf-token";
-        db.create_session(session_id, csrf_token, 7).unwrap();
+        let admin = db
+            .create_admin("admin", "", crate::db::admins::AdminRole::Owner)
+            .unwrap();
+        db.create_session(session_id, csrf_token, admin.id, 7).unwrap();
 
         let state = AppState { 
             db, 
             rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
             proxy_client: Arc::new(MockProxyManagementClient::default()),
             proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         };
         let app = Router::new()
             .nest("/api/users", router())
@@ -334,7 +604,7 @@ mod tests {
         let json: CreateUserResponse = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(json.user.name, "testuser");
-        assert!(json.api_key.starts_with("sk-testuser-"));
+        assert!(json.api_key.starts_with("sk-pp-"));
         assert!(json.user.enabled);
     }
 
@@ -390,6 +660,222 @@ mod tests {
         assert!(!json.enabled);
     }
 
+    #[tokio::test]
+    async fn test_model_allow_list_is_set_through_scopes() {
+        let (db, _dir) = create_test_db();
+        let (user, _) = db.create_user("testuser", None).unwrap();
+
+        let (app, session_id) = create_app(db);
+
+        let request = authed_request(
+            "PUT",
+            &format!("/api/users/{}", user.id),
+            &session_id,
+            Some(r#"{"allowedModels":["claude-*"]}"#),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = authed_request(
+            "PUT",
+            &format!("/api/users/{}", user.id),
+            &session_id,
+            Some(r#"{"scopes":{"models":["claude-*","gpt-4o"]}}"#),
+        );
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: User = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json.scopes.models,
+            Some(vec!["claude-*".to_string(), "gpt-4o".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_user_sets_and_clears_model_aliases() {
+        let (db, _dir) = create_test_db();
+        let (user, _) = db.create_user("testuser", None).unwrap();
+
+        let (app, session_id) = create_app(db);
+        let uri = format!("/api/users/{}", user.id);
+
+        let request = authed_request("PUT", &uri, &session_id, Some(r#"{"modelAliases":{"gpt-*":""}}"#));
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = authed_request("PUT", &uri, &session_id, Some(r#"{"modelAliases":{"gpt-*":"claude-*"}}"#));
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: User = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.model_aliases.get("gpt-*").map(String::as_str), Some("claude-*"));
+
+        let request = authed_request("PUT", &uri, &session_id, Some(r#"{"modelAliases":null}"#));
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: User = serde_json::from_slice(&body).unwrap();
+        assert!(json.model_aliases.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_and_update_user_limits() {
+        let (db, _dir) = create_test_db();
+        let (app, session_id) = create_app(db);
+
+        let request = authed_request(
+            "POST",
+            "/api/users",
+            &session_id,
+            Some(r#"{"name":"ci-bot","requestsPerMinute":600,"maxConcurrentRequests":8}"#),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let created: CreateUserResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(created.user.limits.requests_per_minute, Some(600));
+        assert_eq!(created.user.limits.tokens_per_minute, None);
+        assert_eq!(created.user.limits.max_concurrent_requests, Some(8));
+
+        let request = authed_request(
+            "PUT",
+            &format!("/api/users/{}", created.user.id),
+            &session_id,
+            Some(r#"{"tokensPerMinute":50000,"requestsPerMinute":null}"#),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: User = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.limits.requests_per_minute, None);
+        assert_eq!(json.limits.tokens_per_minute, Some(50000));
+        assert_eq!(json.limits.max_concurrent_requests, Some(8));
+
+        let request = authed_request(
+            "PUT",
+            &format!("/api/users/{}", created.user.id),
+            &session_id,
+            Some(r#"{"maxConcurrentRequests":-1}"#),
+        );
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_update_user_quota_period_and_history() {
+        let (db, _dir) = create_test_db();
+        let (user, _) = db.create_user("testuser", Some(1000)).unwrap();
+
+        let (app, session_id) = create_app(db.clone());
+
+        let request = authed_request(
+            "PUT",
+            &format!("/api/users/{}", user.id),
+            &session_id,
+            Some(r#"{"quotaPeriod":"weekly","quotaTimezone":"Asia/Ho_Chi_Minh"}"#),
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: User = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.quota_period, QuotaPeriod::Weekly);
+        assert_eq!(json.quota_timezone, "Asia/Ho_Chi_Minh");
+        assert!(json.quota_period_started_at.is_some());
+
+        db.log_usage(user.id, "openai", "gpt-4o", 10, 20, 5, "success").unwrap();
+        let next_week = chrono::Utc::now() + chrono::Duration::days(8);
+        db.roll_over_quotas(next_week).unwrap();
+
+        let request = authed_request(
+            "GET",
+            &format!("/api/users/{}/quota-history", user.id),
+            &session_id,
+            None,
+        );
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let history: QuotaHistoryResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(history.periods.len(), 1);
+        assert_eq!(history.periods[0].used_tokens, 30);
+
+        let request = authed_request(
+            "PUT",
+            &format!("/api/users/{}", user.id),
+            &session_id,
+            Some(r#"{"quotaTimezone":"Mars/Olympus"}"#),
+        );
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_assign_and_remove_team() {
+        let (db, _dir) = create_test_db();
+        let team = db.create_team("research", None, &UserLimits::default()).unwrap();
+        let (app, session_id) = create_app(db);
+
+        let body = format!(r#"{{"name":"alice","teamId":{}}}"#, team.id);
+        let response = app
+            .clone()
+            .oneshot(authed_request("POST", "/api/users", &session_id, Some(&body)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: CreateUserResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.user.team_id, Some(team.id));
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                "PUT",
+                &format!("/api/users/{}", json.user.id),
+                &session_id,
+                Some(r#"{"teamId":999}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .oneshot(authed_request(
+                "PUT",
+                &format!("/api/users/{}", json.user.id),
+                &session_id,
+                Some(r#"{"teamId":null}"#),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let user: User = serde_json::from_slice(&body).unwrap();
+        assert_eq!(user.team_id, None);
+    }
+
     #[tokio::test]
     async fn test_delete_user_removes_user() {
         let (db, _dir) = create_test_db();
@@ -433,7 +919,7 @@ mod tests {
             .unwrap();
         let json: RegenerateKeyResponse = serde_json::from_slice(&body).unwrap();
 
-        assert!(json.api_key.starts_with("sk-testuser-"));
+        assert!(json.api_key.starts_with("sk-pp-"));
         assert_ne!(json.api_key, original_key);
     }
 
@@ -469,15 +955,20 @@ mod tests {
     #[tokio::test]
     async fn test_unauthenticated_request_returns_401() {
         use std::sync::Arc;
+        use crate::middleware::key_cache::ApiKeyCache;
         use crate::middleware::rate_limit::RateLimiter;
-        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager};
+        use crate::cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe};
         
         let (db, _dir) = create_test_db();
         let state = AppState { 
             db, 
             rate_limiter: Arc::new(RateLimiter::new(60)),
+            key_cache: Arc::new(ApiKeyCache::default()),
+            login_guard: Arc::new(crate::middleware::login_guard::LoginGuard::default()),
             proxy_client: Arc::new(MockProxyManagementClient::default()),
             proxy_manager: Arc::new(MockProxyProcessManager::default()),
+            proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+            oidc: None,
         };
         let app = Router::new()
             .nest("/api/users", router())
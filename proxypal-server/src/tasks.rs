@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::task::JoinHandle;
+
+use crate::db::Database;
+
+const QUOTA_ROLLOVER_INTERVAL: Duration = Duration::from_secs(60);
+const SESSION_CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Periodically closes finished quota periods, resetting `used_tokens` and
+/// recording the period in the user's history, and resets team pools whose
+/// period ended. Runs once immediately.
+pub fn spawn_quota_rollover(db: Database) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(QUOTA_ROLLOVER_INTERVAL);
+        loop {
+            interval.tick().await;
+            match db.roll_over_quotas(Utc::now()) {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Rolled over quota period for {} user(s)", count),
+                Err(e) => tracing::error!("Quota rollover failed: {}", e),
+            }
+            match db.roll_over_team_quotas(Utc::now()) {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Rolled over quota period for {} team(s)", count),
+                Err(e) => tracing::error!("Team quota rollover failed: {}", e),
+            }
+        }
+    })
+}
+
+/// Periodically deletes expired sessions and OAuth states, and sessions idle
+/// longer than `idle_timeout`. Runs once immediately.
+pub fn spawn_session_cleanup(db: Database, idle_timeout: Option<chrono::Duration>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SESSION_CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = cleanup_sessions(&db, idle_timeout) {
+                tracing::error!("Session cleanup failed: {}", e);
+            }
+        }
+    })
+}
+
+fn cleanup_sessions(db: &Database, idle_timeout: Option<chrono::Duration>) -> anyhow::Result<()> {
+    let mut sessions = db.cleanup_expired_sessions()?;
+    if let Some(timeout) = idle_timeout {
+        sessions += db.cleanup_idle_sessions(timeout.num_minutes())?;
+    }
+    let states = db.cleanup_expired_oauth_states()?;
+    if sessions + states > 0 {
+        tracing::info!("Removed {} stale session(s) and {} OAuth state(s)", sessions, states);
+    }
+    Ok(())
+}
@@ -7,8 +7,13 @@ use axum::http::{header::HeaderName, HeaderValue};
 use axum_test::TestServer;
 use base64::{engine::general_purpose::STANDARD, Engine};
 use proxypal_server::{
-    cliproxy::{MockProxyManagementClient, MockProxyProcessManager, ProxyProviderStatus},
-    db::Database,
+    cliproxy::{
+        MockProxyManagementClient, MockProxyProcessManager, ProxyBackend, ProxyProbe,
+        ProxyProviderStatus,
+    },
+    db::{admins, Database},
+    middleware::key_cache::ApiKeyCache,
+    middleware::login_guard::LoginGuard,
     middleware::rate_limit::RateLimiter,
     routes, AppState,
 };
@@ -30,17 +35,6 @@ fn cleanup_env() {
     std::env::remove_var("ENCRYPTION_KEY");
 }
 
-fn hash_password(password: &str) -> String {
-    use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
-    use rand::rngs::OsRng;
-
-    let salt = SaltString::generate(&mut OsRng);
-    Argon2::default()
-        .hash_password(password.as_bytes(), &salt)
-        .unwrap()
-        .to_string()
-}
-
 fn create_test_db() -> Database {
     setup_test_env();
     let dir = tempdir().unwrap();
@@ -61,8 +55,12 @@ fn create_test_state() -> AppState {
     AppState {
         db,
         rate_limiter,
+        key_cache: Arc::new(ApiKeyCache::default()),
+        login_guard: Arc::new(LoginGuard::default()),
         proxy_client,
         proxy_manager,
+        proxy_probe: Arc::new(ProxyProbe::unchecked(ProxyBackend::Mock)),
+        oidc: None,
     }
 }
 
@@ -87,16 +85,22 @@ fn create_full_app(state: AppState) -> axum::Router {
 
     let admin_api = Router::new()
         .nest("/auth", routes::auth::router())
+        .nest("/me", routes::me::router())
+        .nest("/admins", routes::admins::router())
         .nest("/users", routes::users::router())
+        .nest("/teams", routes::teams::router())
         .nest("/usage", routes::usage::router())
         .nest("/providers", routes::providers::router())
         .nest("/proxy", routes::proxy::router())
-        .nest("/config", routes::config::router());
+        .nest("/config", routes::config::router())
+        .nest("/audit", routes::audit::router())
+        .layer(axum::middleware::from_fn(proxypal_server::middleware::csrf::csrf_protection));
 
     Router::new()
         .route("/healthz", get(health_check))
         .nest("/api", admin_api)
         .nest("/oauth", routes::providers::oauth_callback_router())
+        .nest("/v1", routes::me::v1_router())
         .with_state(state)
 }
 
@@ -121,8 +125,8 @@ async fn create_test_server() -> TestServer {
 
 async fn create_test_server_with_admin(password: &str) -> TestServer {
     let state = create_test_state();
-    let hash = hash_password(password);
-    state.db.set_setting("admin_password_hash", &hash).unwrap();
+    let hash = admins::hash_password(password).unwrap();
+    state.db.create_admin("admin", &hash, admins::AdminRole::Owner).unwrap();
     let app = create_full_app(state);
     TestServer::new(app).unwrap()
 }
@@ -131,15 +135,38 @@ fn cookie_header() -> HeaderName {
     HeaderName::from_static("cookie")
 }
 
+fn csrf_header() -> HeaderName {
+    HeaderName::from_static("x-csrf-token")
+}
+
+/// Returns the `session` and `csrf_token` cookies set by a login response,
+/// ready to be sent back in a `Cookie` header.
 fn extract_session_cookie(response: &axum_test::TestResponse) -> String {
-    let cookies: Vec<_> = response.iter_headers_by_name("set-cookie").collect();
-    for cookie in cookies {
-        let cookie_str = cookie.to_str().unwrap();
-        if cookie_str.starts_with("session=") {
-            return cookie_str.split(';').next().unwrap().to_string();
+    let mut session = None;
+    let mut csrf = None;
+    for cookie in response.iter_headers_by_name("set-cookie") {
+        let pair = cookie.to_str().unwrap().split(';').next().unwrap().to_string();
+        if pair.starts_with("session=") {
+            session = Some(pair);
+        } else if pair.starts_with("csrf_token=") {
+            csrf = Some(pair);
         }
     }
-    panic!("No session cookie found");
+    format!(
+        "{}; {}",
+        session.expect("No session cookie found"),
+        csrf.expect("No csrf_token cookie found")
+    )
+}
+
+/// The `X-CSRF-Token` value matching the `csrf_token` cookie in `cookies`.
+fn csrf_token(cookies: impl AsRef<[u8]>) -> HeaderValue {
+    let token = std::str::from_utf8(cookies.as_ref())
+        .unwrap()
+        .split("; ")
+        .find_map(|pair| pair.strip_prefix("csrf_token="))
+        .expect("No csrf_token cookie found");
+    HeaderValue::from_str(token).unwrap()
 }
 
 // =============================================================================
@@ -281,6 +308,7 @@ mod admin_auth_flow {
 
         let logout_response = server
             .post("/api/auth/logout")
+            .add_header(csrf_header(), csrf_token(&session_cookie))
             .add_header(cookie_header(), HeaderValue::from_str(&session_cookie).unwrap())
             .await;
 
@@ -322,6 +350,7 @@ mod user_management_flow {
 
         let response = server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "testuser", "quotaTokens": 1000000}))
             .await;
@@ -330,7 +359,7 @@ mod user_management_flow {
 
         let body: Value = response.json();
         assert_eq!(body["name"], "testuser");
-        assert!(body["apiKey"].as_str().unwrap().starts_with("sk-testuser-"));
+        assert!(body["apiKey"].as_str().unwrap().starts_with("sk-pp-"));
         assert!(body["enabled"].as_bool().unwrap());
         assert_eq!(body["quotaTokens"], 1000000);
 
@@ -346,12 +375,14 @@ mod user_management_flow {
 
         server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "user1"}))
             .await;
 
         server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "user2", "quotaTokens": 500000}))
             .await;
@@ -386,6 +417,7 @@ mod user_management_flow {
 
         let create_response = server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "testuser"}))
             .await;
@@ -396,6 +428,7 @@ mod user_management_flow {
 
         let response = server
             .post(&format!("/api/users/{}/regenerate-key", user_id))
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .await;
 
@@ -403,7 +436,7 @@ mod user_management_flow {
 
         let body: Value = response.json();
         let new_key = body["apiKey"].as_str().unwrap();
-        assert!(new_key.starts_with("sk-testuser-"));
+        assert!(new_key.starts_with("sk-pp-"));
         assert_ne!(new_key, original_key);
 
         cleanup_env();
@@ -418,6 +451,7 @@ mod user_management_flow {
 
         let create_response = server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "testuser"}))
             .await;
@@ -427,6 +461,7 @@ mod user_management_flow {
 
         let update_response = server
             .put(&format!("/api/users/{}", user_id))
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"quotaTokens": 2000000, "enabled": false}))
             .await;
@@ -449,6 +484,7 @@ mod user_management_flow {
 
         let create_response = server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "testuser"}))
             .await;
@@ -458,6 +494,7 @@ mod user_management_flow {
 
         let delete_response = server
             .delete(&format!("/api/users/{}", user_id))
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .await;
 
@@ -485,12 +522,14 @@ mod user_management_flow {
 
         server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "testuser"}))
             .await;
 
         let response = server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "testuser"}))
             .await;
@@ -550,6 +589,7 @@ mod usage_tracking_flow {
 
         let create_response = server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "testuser"}))
             .await;
@@ -658,6 +698,7 @@ mod proxy_management_flow {
 
         let response = server
             .post("/api/proxy/start")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .await;
 
@@ -680,6 +721,7 @@ mod proxy_management_flow {
 
         let response = server
             .post("/api/proxy/stop")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .await;
 
@@ -700,6 +742,7 @@ mod proxy_management_flow {
 
         let response = server
             .post("/api/proxy/restart")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .await;
 
@@ -782,6 +825,7 @@ mod config_management_flow {
 
         let update_response = server
             .put("/api/config")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"proxy_port": 9000, "log_level": "debug"}))
             .await;
@@ -812,6 +856,7 @@ mod config_management_flow {
 
         let response = server
             .put("/api/config")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"admin_port": 4000}))
             .await;
@@ -834,6 +879,7 @@ mod config_management_flow {
 
         let response = server
             .put("/api/config")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"proxy_port": 80}))
             .await;
@@ -852,6 +898,7 @@ mod config_management_flow {
 
         let response = server
             .put("/api/config")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"log_level": "invalid"}))
             .await;
@@ -903,6 +950,7 @@ mod full_user_lifecycle {
         // Step 1: Create user with quota
         let create_response = server
             .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"name": "lifecycle_user", "quotaTokens": 1000000}))
             .await;
@@ -911,7 +959,7 @@ mod full_user_lifecycle {
         let created: Value = create_response.json();
         let user_id = created["id"].as_i64().unwrap();
         let original_key = created["apiKey"].as_str().unwrap().to_string();
-        assert!(original_key.starts_with("sk-lifecycle_user-"));
+        assert!(original_key.starts_with("sk-pp-"));
 
         // Step 2: Verify user appears in list
         let list_response = server
@@ -935,6 +983,7 @@ mod full_user_lifecycle {
         // Step 4: Update user quota and disable
         let update_response = server
             .put(&format!("/api/users/{}", user_id))
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"quotaTokens": 2000000, "enabled": false}))
             .await;
@@ -947,6 +996,7 @@ mod full_user_lifecycle {
         // Step 5: Regenerate API key
         let regen_response = server
             .post(&format!("/api/users/{}/regenerate-key", user_id))
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .await;
 
@@ -969,6 +1019,7 @@ mod full_user_lifecycle {
         // Step 7: Re-enable user
         let enable_response = server
             .put(&format!("/api/users/{}", user_id))
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .json(&json!({"enabled": true}))
             .await;
@@ -980,6 +1031,7 @@ mod full_user_lifecycle {
         // Step 8: Delete user
         let delete_response = server
             .delete(&format!("/api/users/{}", user_id))
+            .add_header(csrf_header(), csrf_token(&session))
             .add_header(cookie_header(), HeaderValue::from_str(&session).unwrap())
             .await;
 
@@ -998,7 +1050,305 @@ mod full_user_lifecycle {
 }
 
 // =============================================================================
-// Scenario 7: Health Check
+// Scenario 7: Audit Log
+// =============================================================================
+
+mod audit_log {
+    use super::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_admin_actions_are_audited_and_exported() {
+        cleanup_env();
+        let server = create_test_server_with_admin("admin123").await;
+        let login = server
+            .post("/api/auth/login")
+            .json(&json!({"password": "admin123"}))
+            .await;
+        let session = HeaderValue::from_str(&extract_session_cookie(&login)).unwrap();
+
+        let created: Value = server
+            .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
+            .add_header(cookie_header(), session.clone())
+            .json(&json!({"name": "audited", "quotaTokens": 1000}))
+            .await
+            .json();
+        let user_id = created["id"].as_i64().unwrap();
+        server
+            .put(&format!("/api/users/{}", user_id))
+            .add_header(csrf_header(), csrf_token(&session))
+            .add_header(cookie_header(), session.clone())
+            .json(&json!({"quotaTokens": 5000}))
+            .await
+            .assert_status_ok();
+        server
+            .put("/api/config")
+            .add_header(csrf_header(), csrf_token(&session))
+            .add_header(cookie_header(), session.clone())
+            .json(&json!({"log_level": "debug"}))
+            .await
+            .assert_status_ok();
+        server
+            .post("/api/proxy/start")
+            .add_header(csrf_header(), csrf_token(&session))
+            .add_header(cookie_header(), session.clone())
+            .await
+            .assert_status_ok();
+
+        let response = server
+            .get("/api/audit")
+            .add_header(cookie_header(), session.clone())
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        let actions: Vec<&str> = body["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            actions,
+            ["proxy.start", "config.update", "user.update", "user.create", "auth.login"]
+        );
+        let config = &body["entries"][1];
+        assert_eq!(config["actor"], "admin");
+        assert_eq!(
+            config["changes"]["log_level"],
+            json!({"before": "info", "after": "debug"})
+        );
+
+        let update: Value = server
+            .get("/api/audit")
+            .add_query_param("action", "user")
+            .add_query_param("target_id", user_id)
+            .add_query_param("limit", 1)
+            .add_header(cookie_header(), session.clone())
+            .await
+            .json();
+        assert_eq!(update["total"], 2);
+        assert_eq!(
+            update["entries"][0]["changes"]["quotaTokens"],
+            json!({"before": 1000, "after": 5000})
+        );
+
+        let export = server
+            .get("/api/audit/export")
+            .add_query_param("action", "config")
+            .add_header(cookie_header(), session.clone())
+            .await;
+        export.assert_status_ok();
+        assert!(export.header("content-type").to_str().unwrap().starts_with("text/csv"));
+        let csv = export.text();
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.lines().nth(1).unwrap().contains(",admin,config.update,"));
+
+        server
+            .get("/api/audit")
+            .add_query_param("since", "yesterday")
+            .add_header(cookie_header(), session)
+            .await
+            .assert_status_bad_request();
+
+        cleanup_env();
+    }
+}
+
+// =============================================================================
+// Scenario 8: Self-Service Portal
+// =============================================================================
+
+mod self_service_portal {
+    use super::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_user_checks_quota_and_rotates_own_key() {
+        cleanup_env();
+        let server = create_test_server_with_admin("admin123").await;
+        let login = server
+            .post("/api/auth/login")
+            .json(&json!({"password": "admin123"}))
+            .await;
+        let session = HeaderValue::from_str(&extract_session_cookie(&login)).unwrap();
+
+        let created: Value = server
+            .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
+            .add_header(cookie_header(), session.clone())
+            .json(&json!({"name": "portal-user", "quotaTokens": 5000}))
+            .await
+            .json();
+        let api_key = created["apiKey"].as_str().unwrap().to_string();
+        let bearer = |key: &str| HeaderValue::from_str(&format!("Bearer {}", key)).unwrap();
+        let authorization = HeaderName::from_static("authorization");
+
+        server.get("/api/me").await.assert_status_unauthorized();
+
+        let me: Value = server
+            .get("/v1/me")
+            .add_header(authorization.clone(), bearer(&api_key))
+            .await
+            .json();
+        assert_eq!(me["name"], "portal-user");
+        assert_eq!(me["quotaTokens"], 5000);
+        assert_eq!(me["remainingTokens"], 5000);
+        assert_eq!(me["key"]["name"], "default");
+
+        let daily: Value = server
+            .get("/api/me/usage/daily")
+            .add_query_param("days", 7)
+            .add_header(authorization.clone(), bearer(&api_key))
+            .await
+            .json();
+        assert_eq!(daily["days"], 7);
+
+        let rotated = server
+            .post("/api/me/keys/rotate")
+            .add_header(authorization.clone(), bearer(&api_key))
+            .await;
+        rotated.assert_status(axum::http::StatusCode::CREATED);
+        let new_key = rotated.json::<Value>()["apiKey"].as_str().unwrap().to_string();
+
+        server
+            .get("/api/me")
+            .add_header(authorization.clone(), bearer(&api_key))
+            .await
+            .assert_status_unauthorized();
+        server
+            .get("/api/me/logs")
+            .add_header(authorization, bearer(&new_key))
+            .await
+            .assert_status_ok();
+
+        let audit: Value = server
+            .get("/api/audit")
+            .add_query_param("action", "api_key.rotate")
+            .add_header(cookie_header(), session)
+            .await
+            .json();
+        assert_eq!(audit["entries"][0]["actor"], "user:portal-user");
+
+        cleanup_env();
+    }
+}
+
+// =============================================================================
+// Scenario 9: Admin API Tokens
+// =============================================================================
+
+mod admin_api_tokens {
+    use super::*;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_script_provisions_users_with_a_token() {
+        cleanup_env();
+        let server = create_test_server_with_admin("admin123").await;
+        let login = server
+            .post("/api/auth/login")
+            .json(&json!({"password": "admin123"}))
+            .await;
+        let session = HeaderValue::from_str(&extract_session_cookie(&login)).unwrap();
+
+        let created = server
+            .post("/api/auth/tokens")
+            .add_header(csrf_header(), csrf_token(&session))
+            .add_header(cookie_header(), session.clone())
+            .json(&json!({"name": "provisioning", "role": "admin"}))
+            .await;
+        created.assert_status(axum::http::StatusCode::CREATED);
+        let created: Value = created.json();
+        assert_eq!(created["role"], "admin");
+        let authorization = HeaderName::from_static("authorization");
+        let bearer =
+            HeaderValue::from_str(&format!("Bearer {}", created["secret"].as_str().unwrap())).unwrap();
+
+        server
+            .post("/api/users")
+            .add_header(authorization.clone(), bearer.clone())
+            .json(&json!({"name": "provisioned"}))
+            .await
+            .assert_status(axum::http::StatusCode::CREATED);
+        server
+            .put("/api/config")
+            .add_header(authorization.clone(), bearer.clone())
+            .json(&json!({"log_level": "debug"}))
+            .await
+            .assert_status_forbidden();
+
+        let tokens: Value = server
+            .get("/api/auth/tokens")
+            .add_header(authorization.clone(), bearer.clone())
+            .await
+            .json();
+        assert!(tokens["tokens"][0]["lastUsedAt"].is_string());
+        assert!(tokens["tokens"][0].get("secret").is_none());
+
+        server
+            .delete(&format!("/api/auth/tokens/{}", created["id"]))
+            .add_header(csrf_header(), csrf_token(&session))
+            .add_header(cookie_header(), session)
+            .await
+            .assert_status_ok();
+        server
+            .get("/api/users")
+            .add_header(authorization, bearer)
+            .await
+            .assert_status_unauthorized();
+
+        cleanup_env();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_session_writes_need_the_csrf_header_but_tokens_do_not() {
+        cleanup_env();
+        let server = create_test_server_with_admin("admin123").await;
+        let login = server
+            .post("/api/auth/login")
+            .json(&json!({"password": "admin123"}))
+            .await;
+        let session = HeaderValue::from_str(&extract_session_cookie(&login)).unwrap();
+
+        server
+            .post("/api/users")
+            .add_header(cookie_header(), session.clone())
+            .json(&json!({"name": "forged"}))
+            .await
+            .assert_status_forbidden();
+        server
+            .post("/api/users")
+            .add_header(csrf_header(), csrf_token(&session))
+            .add_header(cookie_header(), session.clone())
+            .json(&json!({"name": "from-the-dashboard"}))
+            .await
+            .assert_status(axum::http::StatusCode::CREATED);
+
+        let created: Value = server
+            .post("/api/auth/tokens")
+            .add_header(csrf_header(), csrf_token(&session))
+            .add_header(cookie_header(), session.clone())
+            .json(&json!({"name": "scripts", "role": "admin"}))
+            .await
+            .json();
+        let bearer =
+            HeaderValue::from_str(&format!("Bearer {}", created["secret"].as_str().unwrap())).unwrap();
+        server
+            .post("/api/users")
+            .add_header(HeaderName::from_static("authorization"), bearer)
+            .add_header(cookie_header(), session)
+            .json(&json!({"name": "from-a-script"}))
+            .await
+            .assert_status(axum::http::StatusCode::CREATED);
+
+        cleanup_env();
+    }
+}
+
+// =============================================================================
+// Scenario 10: Health Check
 // =============================================================================
 
 mod health_check_tests {